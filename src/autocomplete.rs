@@ -3,18 +3,114 @@ use crossterm::{
     execute,
     style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor},
 };
+use crate::editor::{ColumnContext, ImportContext};
+use crate::fuzzy::fuzzy_match;
+use crate::kernel::{CompletionItem, Documentation};
+use crate::path_completion::complete_path;
+use crate::session;
+use crate::sql_context::SqlScope;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
 use std::io::{self, Write};
+use std::path::Path;
+
+/// Ranking bonus for names from the user's code (namespace, tables, columns) over keywords and builtins
+const NAMESPACE_BONUS: i64 = 12;
+
+/// Extra ranking bonus for the columns of the tables a SQL statement reads from
+const SCOPE_BONUS: i64 = 6;
+
+/// Ranking bonus when the typed text is an exact, case-sensitive prefix
+const EXACT_PREFIX_BONUS: i64 = 10;
+
+/// Width of the docstring preview next to the dropdown
+const PREVIEW_WIDTH: usize = 50;
+
+/// Lines of docstring shown in the preview
+const PREVIEW_LINES: usize = 12;
+
+/// Built-in names that are classes rather than functions
+const BUILTIN_CLASSES: &[&str] = &[
+    "bool", "bytearray", "bytes", "classmethod", "complex", "dict", "float", "frozenset",
+    "int", "list", "memoryview", "object", "property", "range", "set", "slice",
+    "staticmethod", "str", "super", "tuple", "type",
+];
+
+/// What a suggestion is, shown as an icon in the dropdown
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompletionKind {
+    Function,
+    Class,
+    Module,
+    Variable,
+    Attribute,
+    Keyword,
+    Table,
+    Column,
+    File,
+    Directory,
+}
+
+impl CompletionKind {
+    /// Kind of a name from the Python type name of its value
+    pub fn from_type_name(type_name: &str) -> Self {
+        match type_name {
+            "module" => CompletionKind::Module,
+            "type" | "ABCMeta" | "pybind11_type" | "EnumMeta" | "EnumType" => CompletionKind::Class,
+            "function" | "builtin_function_or_method" | "method" | "method-wrapper" |
+            "method_descriptor" | "wrapper_descriptor" | "cython_function_or_method" |
+            "_lru_cache_wrapper" | "partial" | "ufunc" => CompletionKind::Function,
+            _ => CompletionKind::Variable,
+        }
+    }
+
+    fn icon(self) -> (char, Color) {
+        match self {
+            CompletionKind::Function => ('ƒ', Color::Rgb { r: 200, g: 160, b: 230 }),
+            CompletionKind::Class => ('C', Color::Rgb { r: 230, g: 190, b: 110 }),
+            CompletionKind::Module => ('M', Color::Rgb { r: 120, g: 180, b: 230 }),
+            CompletionKind::Variable => ('v', Color::Rgb { r: 140, g: 200, b: 220 }),
+            CompletionKind::Attribute => ('·', Color::Rgb { r: 160, g: 160, b: 165 }),
+            CompletionKind::Keyword => ('k', Color::Rgb { r: 220, g: 130, b: 130 }),
+            CompletionKind::Table => ('T', Color::Rgb { r: 130, g: 200, b: 140 }),
+            CompletionKind::Column => ('c', Color::Rgb { r: 170, g: 210, b: 150 }),
+            CompletionKind::File => ('f', Color::Rgb { r: 190, g: 190, b: 195 }),
+            CompletionKind::Directory => ('/', Color::Rgb { r: 120, g: 180, b: 230 }),
+        }
+    }
+
+    /// Whether the kernel can have documentation for a suggestion of this kind
+    fn has_docs(self) -> bool {
+        !matches!(self, CompletionKind::Keyword | CompletionKind::Table | CompletionKind::Column
+            | CompletionKind::File | CompletionKind::Directory)
+    }
+}
+
+/// A suggestion and the char indices that matched the typed text
+struct Suggestion {
+    text: String,
+    matched: Vec<usize>,
+    kind: CompletionKind,
+    detail: String, // Type of a variable, table of a column, ...
+}
 
 /// Autocomplete suggestions dropdown
 pub struct Autocomplete {
-    suggestions: Vec<String>,
+    suggestions: Vec<Suggestion>,
     selected_index: usize,
     visible: bool,
     filter_text: String,
-    dynamic_completions: Vec<String>, // Completions from Python namespace
+    dynamic_completions: Vec<CompletionItem>, // Completions from Python namespace
+    static_completions: Vec<CompletionItem>, // From static analysis, for the word being typed
+    static_word_start: Option<usize>, // Where the word the static completions are for starts
     viewport_offset: usize, // Scroll offset for the visible window
     type_relationships: crate::kernel::TypeRelationships, // Type information for intelligent completion
     sql_metadata: crate::kernel::SqlMetadata, // SQL metadata for SQL autocomplete
+    sql_scope: SqlScope, // Tables and aliases of the SQL statement being edited
+    usage: HashMap<String, u32>, // How often each suggestion was accepted (persisted)
+    docs: HashMap<String, Option<Documentation>>, // Fetched from the kernel as items are selected
+    sql_suggestions: bool, // Whether the current suggestions are SQL (no kernel docs)
+    string_completion: Option<(char, usize)>, // Quote and chars replaced, when suggesting a column name or path in a string
 }
 
 impl Autocomplete {
@@ -25,15 +121,35 @@ impl Autocomplete {
             visible: false,
             filter_text: String::new(),
             dynamic_completions: Vec::new(),
+            static_completions: Vec::new(),
+            static_word_start: None,
             viewport_offset: 0,
             type_relationships: crate::kernel::TypeRelationships::default(),
             sql_metadata: crate::kernel::SqlMetadata::default(),
+            sql_scope: SqlScope::default(),
+            usage: session::completion_usage(),
+            docs: HashMap::new(),
+            sql_suggestions: false,
+            string_completion: None,
         }
     }
 
     /// Add dynamic completions from Python namespace
-    pub fn add_dynamic_completions(&mut self, completions: Vec<String>) {
+    pub fn add_dynamic_completions(&mut self, completions: Vec<CompletionItem>) {
         self.dynamic_completions = completions;
+        // Objects may have been redefined by the code that just ran
+        self.docs.clear();
+    }
+
+    /// Whether static completions still have to be fetched for the word starting at `word_start`
+    pub fn needs_static_completions(&self, word_start: usize) -> bool {
+        self.static_word_start != Some(word_start)
+    }
+
+    /// Set the static-analysis completions for the word starting at `word_start`
+    pub fn set_static_completions(&mut self, word_start: usize, completions: Vec<CompletionItem>) {
+        self.static_completions = completions;
+        self.static_word_start = Some(word_start);
     }
 
     /// Set type relationships for intelligent completion
@@ -46,6 +162,11 @@ impl Autocomplete {
         self.sql_metadata = sql_metadata;
     }
 
+    /// Set the tables and aliases of the SQL statement at the cursor
+    pub fn set_sql_scope(&mut self, sql_scope: SqlScope) {
+        self.sql_scope = sql_scope;
+    }
+
     /// Get SQL keywords
     fn get_sql_keywords() -> Vec<&'static str> {
         vec![
@@ -78,8 +199,8 @@ impl Autocomplete {
     }
 
     /// Get Python keywords and built-in functions
-    fn get_python_completions() -> Vec<&'static str> {
-        vec![
+    fn get_python_completions() -> Vec<(&'static str, CompletionKind)> {
+        let names = [
             // Keywords
             "False", "None", "True", "and", "as", "assert", "async", "await",
             "break", "class", "continue", "def", "del", "elif", "else", "except",
@@ -100,7 +221,21 @@ impl Autocomplete {
             // Common imports
             "pandas", "numpy", "matplotlib", "duckdb", "json", "os", "sys",
             "datetime", "collections", "itertools", "functools", "pathlib",
-        ]
+        ];
+        const KEYWORDS: usize = 35;
+        const BUILTINS: usize = 67;
+        names.iter().enumerate().map(|(i, &name)| {
+            let kind = if i < KEYWORDS {
+                CompletionKind::Keyword
+            } else if i >= KEYWORDS + BUILTINS {
+                CompletionKind::Module
+            } else if BUILTIN_CLASSES.contains(&name) {
+                CompletionKind::Class
+            } else {
+                CompletionKind::Function
+            };
+            (name, kind)
+        }).collect()
     }
 
     /// Update suggestions based on current word prefix
@@ -114,6 +249,7 @@ impl Autocomplete {
     /// is_sql_context: Whether we're inside a SQL string
     pub fn update_with_context(&mut self, base_callable: Option<String>, prefix: &str, is_sql_context: bool) {
         self.filter_text = prefix.to_string();
+        self.string_completion = None;
 
         // Debug output to file
         use std::io::Write;
@@ -131,129 +267,251 @@ impl Autocomplete {
             self.visible = false;
             return;
         }
+        self.sql_suggestions = is_sql_context;
 
-        let mut all_suggestions = Vec::new();
-
-        // If we're in SQL context, use SQL completions
+        // If we're in SQL context, use SQL completions: columns and tables first, then functions, then keywords
         if is_sql_context {
-            // Add SQL keywords
-            let sql_keywords = Self::get_sql_keywords();
-            for keyword in sql_keywords {
-                let keyword_str = keyword.to_string();
-                if prefix.is_empty() || keyword.to_lowercase().starts_with(&prefix.to_lowercase()) {
-                    all_suggestions.push(keyword_str);
-                }
-            }
-
-            // Add SQL tables
-            for table in &self.sql_metadata.tables {
-                if prefix.is_empty() || table.to_lowercase().starts_with(&prefix.to_lowercase()) {
-                    all_suggestions.push(table.clone());
-                }
-            }
-
-            // Add SQL columns
-            for column in &self.sql_metadata.columns {
-                if prefix.is_empty() || column.to_lowercase().starts_with(&prefix.to_lowercase()) {
-                    all_suggestions.push(column.clone());
-                }
-            }
-
-            // Add SQL functions
-            for function in &self.sql_metadata.functions {
-                if prefix.is_empty() || function.to_lowercase().starts_with(&prefix.to_lowercase()) {
-                    all_suggestions.push(function.clone());
-                }
-            }
-
-            self.suggestions = all_suggestions;
-            self.visible = !self.suggestions.is_empty();
-            self.selected_index = 0;
-            self.viewport_offset = 0;
+            let candidates = self.sql_candidates(prefix);
+            let suggestions = self.rank(prefix, candidates.iter()
+                .map(|(name, bonus, kind, detail)| (name.as_str(), *bonus, *kind, detail.as_str())));
+            self.set_suggestions(suggestions);
             return;
         }
 
         // If we have a base callable, try to use type information
         if let Some(ref base) = base_callable {
+            let mut methods: Vec<&str> = Vec::new();
+            let mut type_name = "";
             // Look up the return type of the base callable
             if let Some(return_type) = self.type_relationships.return_types.get(base) {
                 // Get methods for that return type
-                if let Some(methods) = self.type_relationships.type_methods.get(return_type) {
-                    for method in methods {
-                        if prefix.is_empty() || method.starts_with(prefix) {
-                            all_suggestions.push(method.clone());
-                        }
-                    }
+                if let Some(type_methods) = self.type_relationships.type_methods.get(return_type) {
+                    methods.extend(type_methods.iter().map(String::as_str));
+                    type_name = return_type;
                 }
             } else {
                 // Fallback: If we don't know the return type, try to infer from common patterns
                 // For example, if base is "module.function", look for types related to that module
                 if let Some(module_name) = base.split('.').next() {
                     // Collect methods from types that might be related to this module
-                    for (type_name, methods) in &self.type_relationships.type_methods {
+                    for (type_name, type_methods) in &self.type_relationships.type_methods {
                         // Heuristic: If type name contains module name or starts with it
                         if type_name.to_lowercase().contains(&module_name.to_lowercase()) ||
                            type_name.starts_with(&module_name.chars().next().unwrap().to_uppercase().collect::<String>()) {
-                            for method in methods {
-                                if (prefix.is_empty() || method.starts_with(prefix)) && !all_suggestions.contains(method) {
-                                    all_suggestions.push(method.clone());
-                                }
-                            }
+                            methods.extend(type_methods.iter().map(String::as_str));
                         }
                     }
                 }
             }
 
-            // If we found suggestions from type info, use them
-            if !all_suggestions.is_empty() {
-                self.suggestions = all_suggestions;
-                self.visible = true;
-                self.selected_index = 0;
-                self.viewport_offset = 0;
+            // If we found suggestions from type info (or static analysis), use them
+            let candidates = methods.into_iter().map(|m| (m, 0, CompletionKind::Attribute, type_name))
+                .chain(self.static_completions.iter().map(|c| (c.name.as_str(), 0, CompletionKind::from_type_name(&c.item_type), "")));
+            let suggestions = self.rank(prefix, candidates);
+            if !suggestions.is_empty() {
+                self.set_suggestions(suggestions);
                 return;
             }
         }
 
-        // Fallback to regular prefix matching if no type info or no base callable
-        // Add dynamic completions first (they're more relevant)
-        for completion in &self.dynamic_completions {
-            if completion.starts_with(prefix) {
-                all_suggestions.push(completion.clone());
+        // Fallback when there is no type info or no base callable: names from the Python
+        // namespace, then names found by static analysis (completing the typed qualifier),
+        // rank above keywords and builtins
+        let qualifier = prefix.rfind('.').map_or("", |dot| &prefix[..=dot]);
+        let static_names: Vec<(String, CompletionKind)> = self.static_completions.iter()
+            .map(|c| (format!("{}{}", qualifier, c.name), CompletionKind::from_type_name(&c.item_type)))
+            .collect();
+        let candidates = self.dynamic_completions.iter()
+            .map(|c| {
+                let kind = CompletionKind::from_type_name(&c.item_type);
+                let detail = if kind == CompletionKind::Variable { c.item_type.as_str() } else { "" };
+                (c.name.as_str(), NAMESPACE_BONUS, kind, detail)
+            })
+            .chain(static_names.iter().map(|(name, kind)| (name.as_str(), NAMESPACE_BONUS, *kind, "")))
+            .chain(Self::get_python_completions().into_iter().map(|(name, kind)| (name, 0, kind, "")));
+        let suggestions = self.rank(prefix, candidates);
+        self.set_suggestions(suggestions);
+
+        if let Ok(mut f) = std::fs::OpenOptions::new().create(true).append(true).open("/tmp/sage_debug.log") {
+            let _ = writeln!(f, "DEBUG autocomplete: final suggestions_count={}, visible={}",
+                      self.suggestions.len(), self.visible);
+        }
+    }
+
+    /// Suggest the columns of the DataFrame a column name is being typed for. Without a
+    /// known frame, `pl.col("` gets the columns of every frame; a subscript of something
+    /// that isn't a frame gets nothing.
+    pub fn update_columns(&mut self, context: ColumnContext) {
+        self.filter_text = context.typed.clone();
+        let frames = &self.type_relationships.frame_columns;
+        let known = context.frame.as_deref().and_then(|frame| {
+            frames.get_key_value(frame).or_else(|| frames.get_key_value(frame.split('.').next().unwrap_or(frame)))
+        });
+        let candidates: Vec<(&str, &str)> = match known {
+            Some((frame, columns)) => columns.iter().map(|c| (c.as_str(), frame.as_str())).collect(),
+            None if context.subscript => Vec::new(),
+            None => {
+                let mut all: Vec<(&str, &str)> = frames.iter()
+                    .flat_map(|(frame, columns)| columns.iter().map(move |c| (c.as_str(), frame.as_str())))
+                    .collect();
+                all.sort();
+                all
             }
+        };
+        let suggestions = self.rank(&context.typed, candidates.into_iter()
+            .map(|(column, frame)| (column, NAMESPACE_BONUS, CompletionKind::Column, frame)));
+        self.set_suggestions(suggestions);
+        self.sql_suggestions = false;
+        self.string_completion = Some((context.quote, context.typed.chars().count()));
+    }
+
+    /// Suggest the modules (or, after `from package import`, the names) an import can name,
+    /// from the interpreter's `modules`. Private names only show up once `_` is typed.
+    pub fn update_imports(&mut self, context: &ImportContext, modules: &[CompletionItem]) {
+        let qualifier = context.typed.rfind('.').map_or("", |dot| &context.typed[..=dot]);
+        let name = &context.typed[qualifier.len()..];
+        let candidates: Vec<(String, CompletionKind)> = if modules.is_empty() && context.package.is_none() {
+            // No kernel to ask: the common modules
+            Self::get_python_completions().into_iter()
+                .filter(|&(_, kind)| kind == CompletionKind::Module)
+                .map(|(module, kind)| (module.to_string(), kind))
+                .collect()
+        } else {
+            modules.iter()
+                .filter(|m| !context.modules_only || m.item_type == "module")
+                .filter(|m| !m.name.starts_with('_') || name.starts_with('_'))
+                .map(|m| (format!("{}{}", qualifier, m.name), CompletionKind::from_type_name(&m.item_type)))
+                .collect()
+        };
+        self.filter_text = context.typed.clone();
+        let suggestions = self.rank(&context.typed, candidates.iter()
+            .map(|(candidate, kind)| (candidate.as_str(), NAMESPACE_BONUS, *kind, "")));
+        self.set_suggestions(suggestions);
+        self.sql_suggestions = false;
+        self.string_completion = None;
+    }
+
+    /// Suggest the files and directories for a path typed in a string, relative to `base`.
+    /// Directories come first and end with `/`.
+    pub fn update_paths(&mut self, typed: &str, quote: char, base: &Path) {
+        let partial = typed.rsplit('/').next().unwrap_or(typed);
+        let matched: Vec<usize> = (0..partial.chars().count()).collect();
+        let suggestions = complete_path(base, typed).into_iter()
+            .map(|candidate| Suggestion {
+                text: if candidate.is_dir { format!("{}/", candidate.name) } else { candidate.name },
+                matched: matched.clone(),
+                kind: if candidate.is_dir { CompletionKind::Directory } else { CompletionKind::File },
+                detail: String::new(),
+            })
+            .collect();
+        self.filter_text = typed.to_string();
+        self.set_suggestions(suggestions);
+        self.sql_suggestions = false;
+        self.string_completion = Some((quote, partial.chars().count()));
+    }
+
+    /// SQL candidates (name, bonus, kind, detail). After `alias.` or `table.` only the columns
+    /// of that table; otherwise the columns of the tables the statement reads from (all columns
+    /// if none of them are known), aliases, CTEs and tables, then functions and keywords.
+    fn sql_candidates(&self, prefix: &str) -> Vec<(String, i64, CompletionKind, String)> {
+        if let Some((qualifier, _)) = prefix.rsplit_once('.') {
+            let table = self.sql_scope.resolve(qualifier).unwrap_or(qualifier);
+            return self.table_columns(table).into_iter()
+                .map(|(column, column_type)| (format!("{}.{}", qualifier, column), NAMESPACE_BONUS, CompletionKind::Column, column_type.to_string()))
+                .collect();
         }
 
-        // Add static Python completions (if not already present)
-        let static_completions = Self::get_python_completions();
-        for completion in static_completions {
-            let comp_str = completion.to_string();
-            if comp_str.starts_with(prefix) && !all_suggestions.contains(&comp_str) {
-                all_suggestions.push(comp_str);
+        let mut candidates = Vec::new();
+        for table in &self.sql_scope.tables {
+            for (column, column_type) in self.table_columns(&table.name) {
+                let detail = if column_type.is_empty() { &table.name } else { column_type };
+                candidates.push((column.to_string(), NAMESPACE_BONUS + SCOPE_BONUS, CompletionKind::Column, detail.to_string()));
+            }
+        }
+        if candidates.is_empty() {
+            for column in &self.sql_metadata.columns {
+                let table = column.rsplit_once('.').map_or("", |(table, _)| table);
+                let detail = self.sql_metadata.column_types.get(column).map_or(table, String::as_str);
+                candidates.push((column.clone(), NAMESPACE_BONUS, CompletionKind::Column, detail.to_string()));
+            }
+        }
+        for table in &self.sql_scope.tables {
+            if let Some(alias) = &table.alias {
+                candidates.push((alias.clone(), NAMESPACE_BONUS, CompletionKind::Table, table.name.clone()));
             }
         }
+        candidates.extend(self.sql_scope.ctes.iter().map(|cte| (cte.clone(), NAMESPACE_BONUS, CompletionKind::Table, "CTE".to_string())));
+        candidates.extend(self.sql_metadata.tables.iter().map(|t| (t.clone(), NAMESPACE_BONUS, CompletionKind::Table, String::new())));
+        candidates.extend(self.sql_metadata.functions.iter().map(|f| (f.clone(), NAMESPACE_BONUS / 2, CompletionKind::Function, String::new())));
+        candidates.extend(Self::get_sql_keywords().into_iter().map(|k| (k.to_string(), 0, CompletionKind::Keyword, String::new())));
+        candidates
+    }
+
+    /// Columns of `table` (schema-qualified or not) with their types, if known
+    fn table_columns(&self, table: &str) -> Vec<(&str, &str)> {
+        let unqualified = |name: &str| name.rsplit('.').next().unwrap_or(name).to_string();
+        self.sql_metadata.columns.iter()
+            .filter_map(|full_name| {
+                let (column_table, column) = full_name.rsplit_once('.')?;
+                let same_table = column_table.eq_ignore_ascii_case(table)
+                    || unqualified(column_table).eq_ignore_ascii_case(&unqualified(table));
+                let column_type = self.sql_metadata.column_types.get(full_name).map_or("", String::as_str);
+                same_table.then_some((column, column_type))
+            })
+            .collect()
+    }
+
+    /// Match candidates (name, scope bonus, kind, detail) against the typed text and sort
+    /// them best first: match quality, scope, and how often each one was accepted before.
+    /// Ties keep the candidates' order.
+    fn rank<'a>(&self, prefix: &str, candidates: impl Iterator<Item = (&'a str, i64, CompletionKind, &'a str)>) -> Vec<Suggestion> {
+        let mut seen = HashSet::new();
+        let mut ranked: Vec<(i64, Suggestion)> = candidates
+            .filter(|(candidate, ..)| seen.insert(*candidate))
+            .filter_map(|(candidate, bonus, kind, detail)| {
+                let (score, matched) = match_candidate(prefix, candidate)?;
+                let uses = self.usage.get(candidate).copied().unwrap_or(0);
+                let suggestion = Suggestion { text: candidate.to_string(), matched, kind, detail: detail.to_string() };
+                Some((score + bonus + usage_bonus(uses), suggestion))
+            })
+            .collect();
+        ranked.sort_by_key(|(score, _)| Reverse(*score));
+        ranked.into_iter().map(|(_, suggestion)| suggestion).collect()
+    }
 
-        self.suggestions = all_suggestions;
+    fn set_suggestions(&mut self, suggestions: Vec<Suggestion>) {
+        self.suggestions = suggestions;
         self.visible = !self.suggestions.is_empty();
         self.selected_index = 0;
         self.viewport_offset = 0;
+    }
 
-        if let Ok(mut f) = std::fs::OpenOptions::new().create(true).append(true).open("/tmp/sage_debug.log") {
-            let _ = writeln!(f, "DEBUG autocomplete: final suggestions_count={}, visible={}",
-                      self.suggestions.len(), self.visible);
-            if !self.suggestions.is_empty() {
-                let _ = writeln!(f, "DEBUG autocomplete: suggestions={:?}", &self.suggestions[..self.suggestions.len().min(5)]);
-            }
+    /// Remember that a suggestion was accepted so it ranks higher from now on
+    pub fn record_accepted(&mut self, text: &str) {
+        *self.usage.entry(text.to_string()).or_insert(0) += 1;
+        session::save_completion_usage(&self.usage);
+    }
+
+    /// Name whose documentation should be fetched from the kernel for the preview:
+    /// the selected suggestion, unless it was looked up already
+    pub fn pending_docs_request(&self) -> Option<String> {
+        if !self.visible || self.sql_suggestions {
+            return None;
         }
+        let suggestion = self.suggestions.get(self.selected_index)?;
+        (suggestion.kind.has_docs() && !self.docs.contains_key(&suggestion.text)).then(|| suggestion.text.clone())
     }
 
-    /// Show autocomplete at cursor position
-    pub fn show(&mut self, prefix: &str) {
-        self.update(prefix);
+    /// Store documentation fetched for a name (None if the kernel had none)
+    pub fn set_docs(&mut self, name: String, docs: Option<Documentation>) {
+        self.docs.insert(name, docs);
     }
 
     /// Hide autocomplete
     pub fn hide(&mut self) {
         self.visible = false;
         self.suggestions.clear();
+        self.static_word_start = None;
         self.selected_index = 0;
         self.viewport_offset = 0;
     }
@@ -297,10 +555,27 @@ impl Autocomplete {
         }
     }
 
+    /// Text that accepting `suggestion` inserts and how many characters before the cursor it
+    /// replaces: the word at the cursor, or the column name or file name typed in a string
+    /// (escaped for the string, which is closed if it isn't already and the name isn't a directory)
+    pub fn insertion(&self, suggestion: &str, word_at_cursor: &str, next_char: Option<char>) -> (String, usize) {
+        match self.string_completion {
+            Some((quote, typed)) => {
+                let mut text = suggestion.replace('\\', "\\\\").replace(quote, &format!("\\{}", quote));
+                let is_dir = self.suggestions.iter().any(|s| s.text == suggestion && s.kind == CompletionKind::Directory);
+                if next_char != Some(quote) && !is_dir {
+                    text.push(quote);
+                }
+                (text, typed)
+            }
+            None => (suggestion.to_string(), word_at_cursor.chars().count()),
+        }
+    }
+
     /// Get currently selected suggestion
     pub fn get_selected(&self) -> Option<&str> {
         if self.visible && self.selected_index < self.suggestions.len() {
-            Some(&self.suggestions[self.selected_index])
+            Some(&self.suggestions[self.selected_index].text)
         } else {
             None
         }
@@ -338,13 +613,18 @@ impl Autocomplete {
         // Find longest suggestion for width (only check visible ones)
         let max_width = self.suggestions[start_idx..end_idx]
             .iter()
-            .map(|s| s.len())
+            .map(|s| s.text.chars().count())
             .max()
             .unwrap_or(20)
             .max(20);
+        let detail_width = self.suggestions[start_idx..end_idx]
+            .iter()
+            .map(|s| s.detail.chars().count().min(20))
+            .max()
+            .unwrap_or(0);
 
-        // Calculate dropdown width including padding (space + content + space)
-        let dropdown_width = max_width + 2;
+        // Calculate dropdown width including padding (icon + space + content + details + space)
+        let dropdown_width = 2 + max_width + if detail_width > 0 { detail_width + 2 } else { 1 };
 
         // Adjust column position to prevent wrapping at viewport edge
         let dropdown_col = if cursor_col as usize + dropdown_width > max_col as usize {
@@ -362,26 +642,293 @@ impl Autocomplete {
 
             execute!(writer, cursor::MoveTo(dropdown_col, row))?;
 
-            if is_selected {
-                // Highlight selected item
-                execute!(
-                    writer,
-                    SetBackgroundColor(Color::DarkBlue),
-                    SetForegroundColor(Color::White),
-                )?;
-            } else {
+            let background = if is_selected { Color::DarkBlue } else { Color::DarkGrey };
+            let (icon, icon_color) = suggestion.kind.icon();
+            execute!(
+                writer,
+                SetBackgroundColor(background),
+                SetForegroundColor(icon_color),
+                Print(icon),
+                Print(' '),
+            )?;
+
+            // Characters that matched the typed text are highlighted
+            for (i, ch) in suggestion.text.chars().enumerate() {
+                let color = if suggestion.matched.contains(&i) { Color::Yellow } else { Color::White };
+                execute!(writer, SetForegroundColor(color), Print(ch))?;
+            }
+
+            // Pad to max width, then the type (or table) right-aligned
+            let padding = max_width.saturating_sub(suggestion.text.chars().count()) + 1;
+            execute!(writer, Print(" ".repeat(padding)))?;
+            if detail_width > 0 {
+                let detail: String = suggestion.detail.chars().take(detail_width).collect();
                 execute!(
                     writer,
-                    SetBackgroundColor(Color::DarkGrey),
-                    SetForegroundColor(Color::White),
+                    SetForegroundColor(Color::Grey),
+                    Print(format!("{:>width$} ", detail, width = detail_width)),
                 )?;
             }
+            execute!(writer, ResetColor)?;
+        }
 
-            // Pad to max width
-            let padded = format!(" {:<width$} ", suggestion, width = max_width);
-            execute!(writer, Print(padded), ResetColor)?;
+        // Docstring preview for the selected suggestion, beside the dropdown
+        let docs = self.suggestions.get(self.selected_index)
+            .and_then(|s| self.docs.get(&s.text))
+            .and_then(|docs| docs.as_ref());
+        if let Some(docs) = docs {
+            let right = dropdown_col as usize + dropdown_width;
+            let (preview_col, width) = if right + PREVIEW_WIDTH <= max_col as usize {
+                (right, PREVIEW_WIDTH)
+            } else if dropdown_col as usize >= PREVIEW_WIDTH {
+                (dropdown_col as usize - PREVIEW_WIDTH, PREVIEW_WIDTH)
+            } else {
+                (right, (max_col as usize).saturating_sub(right))
+            };
+            if width >= 20 {
+                let selected = &self.suggestions[self.selected_index].text;
+                self.draw_preview(writer, docs, selected, preview_col as u16, dropdown_row, width, max_row)?;
+            }
         }
 
         Ok(())
     }
+
+    /// Signature and the first paragraph of the docstring in a box `width` columns wide
+    #[allow(clippy::too_many_arguments)]
+    fn draw_preview<W: Write>(&self, writer: &mut W, docs: &Documentation, name: &str, col: u16, row: u16, width: usize, max_row: u16) -> io::Result<()> {
+        let inner = width - 2;
+        let mut lines: Vec<(String, bool)> = Vec::new();
+        let short_name = name.rsplit('.').next().unwrap_or(name);
+        let heading = match &docs.signature {
+            Some(signature) => format!("{}{}", short_name, signature),
+            None => format!("{}: {}", short_name, docs.kind),
+        };
+        lines.extend(wrap_text(&heading, inner).into_iter().map(|line| (line, true)));
+        if !docs.doc.is_empty() {
+            lines.extend(wrap_text(&docs.doc, inner).into_iter().map(|line| (line, false)));
+        }
+        let available = max_row.saturating_sub(row + 1) as usize;
+        lines.truncate(PREVIEW_LINES.min(available));
+
+        for (i, (line, heading)) in lines.iter().enumerate() {
+            let color = if *heading { Color::White } else { Color::Rgb { r: 190, g: 190, b: 195 } };
+            execute!(
+                writer,
+                cursor::MoveTo(col, row + i as u16),
+                SetBackgroundColor(Color::Rgb { r: 45, g: 45, b: 52 }),
+                SetForegroundColor(color),
+                Print(format!(" {:<width$} ", line, width = inner)),
+                ResetColor,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Break text into lines at most `width` characters wide, at spaces where possible
+fn wrap_text(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            let needed = if line.is_empty() { 0 } else { line.chars().count() + 1 };
+            if needed + word.chars().count() > width && !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(word);
+            // Words longer than the line are cut
+            while line.chars().count() > width {
+                let cut: String = line.chars().take(width).collect();
+                line = line.chars().skip(width).collect();
+                lines.push(cut);
+            }
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+/// Match typed text against a completion. A dotted prefix (`df.gro`) must match the
+/// completion up to its last dot exactly and completes one more level; the rest is
+/// matched as a subsequence that starts at a word boundary (`grpby` -> `groupby`).
+fn match_candidate(prefix: &str, candidate: &str) -> Option<(i64, Vec<usize>)> {
+    let (qualifier, typed) = match prefix.rfind('.') {
+        Some(dot) => prefix.split_at(dot + 1),
+        None => ("", prefix),
+    };
+    let rest = candidate.strip_prefix(qualifier)?;
+    if rest.contains('.') {
+        return None;
+    }
+
+    let (score, matched) = fuzzy_match(typed, rest)?;
+    if matched.first().is_some_and(|&first| !is_word_start(rest, first)) {
+        return None;
+    }
+    let exact = if !typed.is_empty() && rest.starts_with(typed) { EXACT_PREFIX_BONUS } else { 0 };
+    let offset = qualifier.chars().count();
+    Some((score + exact, matched.into_iter().map(|i| i + offset).collect()))
+}
+
+/// Whether char `index` of `text` starts a word (after `_`, or a camelCase hump)
+fn is_word_start(text: &str, index: usize) -> bool {
+    let chars: Vec<char> = text.chars().collect();
+    match index.checked_sub(1).map(|prev| chars[prev]) {
+        None => true,
+        Some(prev) => !prev.is_alphanumeric() || (prev.is_lowercase() && chars[index].is_uppercase()),
+    }
+}
+
+/// Ranking bonus for a suggestion accepted `uses` times, growing with the log of the count
+fn usage_bonus(uses: u32) -> i64 {
+    (4 * (u32::BITS - uses.leading_zeros()) as i64).min(20)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_match_candidate() {
+        assert!(match_candidate("grpby", "groupby").is_some());
+        assert_eq!(match_candidate("df.gb", "df.groupby").map(|(_, m)| m), Some(vec![3, 8]));
+        // Only one level below the typed qualifier, and nothing from other objects
+        assert!(match_candidate("df", "df.groupby").is_none());
+        assert!(match_candidate("df.g", "pd.get_dummies").is_none());
+        // The first character has to start a word
+        assert!(match_candidate("roup", "groupby").is_none());
+        assert!(match_candidate("dum", "get_dummies").is_some());
+
+        let (exact, _) = match_candidate("Data", "DataFrame").unwrap();
+        let (folded, _) = match_candidate("data", "DataFrame").unwrap();
+        assert!(exact > folded);
+    }
+
+    fn column_context(frame: Option<&str>, subscript: bool, typed: &str, quote: char) -> ColumnContext {
+        ColumnContext { frame: frame.map(str::to_string), subscript, typed: typed.to_string(), quote }
+    }
+
+    fn suggested(autocomplete: &Autocomplete) -> Vec<(&str, &str)> {
+        let mut suggested: Vec<(&str, &str)> = autocomplete.suggestions.iter()
+            .map(|s| (s.text.as_str(), s.detail.as_str()))
+            .collect();
+        suggested.sort();
+        suggested
+    }
+
+    #[test]
+    fn test_update_columns() {
+        let mut autocomplete = Autocomplete::new();
+        let mut relationships = crate::kernel::TypeRelationships::default();
+        relationships.frame_columns.insert("df".to_string(), vec!["id".to_string(), "first name".to_string(), "it's".to_string()]);
+        relationships.frame_columns.insert("other".to_string(), vec!["index".to_string()]);
+        autocomplete.set_type_relationships(relationships);
+
+        // `df.loc[` falls back to the frame's own columns
+        autocomplete.update_columns(column_context(Some("df.loc"), true, "i", '"'));
+        assert_eq!(suggested(&autocomplete), vec![("id", "df"), ("it's", "df")]);
+
+        // A subscript of something that isn't a frame gets nothing
+        autocomplete.update_columns(column_context(Some("items"), true, "i", '"'));
+        assert!(suggested(&autocomplete).is_empty());
+        assert!(!autocomplete.visible);
+
+        // `pl.col(` outside a known frame's method gets every frame's columns
+        autocomplete.update_columns(column_context(None, false, "i", '"'));
+        assert_eq!(suggested(&autocomplete), vec![("id", "df"), ("index", "other"), ("it's", "df")]);
+    }
+
+    #[test]
+    fn test_insertion() {
+        let mut autocomplete = Autocomplete::new();
+        assert_eq!(autocomplete.insertion("groupby", "gro", Some('(')), ("groupby".to_string(), 3));
+
+        // Names in strings replace what was typed after the quote and close the string
+        autocomplete.update_columns(column_context(None, false, "fi", '"'));
+        assert_eq!(autocomplete.insertion("first name", "", None), ("first name\"".to_string(), 2));
+        assert_eq!(autocomplete.insertion("first name", "", Some('"')), ("first name".to_string(), 2));
+        assert_eq!(autocomplete.insertion("say \"hi\"", "", None), ("say \\\"hi\\\"\"".to_string(), 2));
+        assert_eq!(autocomplete.insertion("a\\b", "", None), ("a\\\\b\"".to_string(), 2));
+        assert_eq!(autocomplete.insertion("it's", "", None), ("it's\"".to_string(), 2));
+
+        autocomplete.update_columns(column_context(None, false, "", '\''));
+        assert_eq!(autocomplete.insertion("it's", "", None), ("it\\'s'".to_string(), 0));
+    }
+
+    fn item(name: &str, item_type: &str) -> CompletionItem {
+        CompletionItem { name: name.to_string(), item_type: item_type.to_string() }
+    }
+
+    fn suggestion<'a>(autocomplete: &'a Autocomplete, text: &str) -> Option<&'a Suggestion> {
+        autocomplete.suggestions.iter().find(|s| s.text == text)
+    }
+
+    #[test]
+    fn test_suggestion_kinds() {
+        assert_eq!(CompletionKind::from_type_name("module"), CompletionKind::Module);
+        assert_eq!(CompletionKind::from_type_name("ABCMeta"), CompletionKind::Class);
+        assert_eq!(CompletionKind::from_type_name("builtin_function_or_method"), CompletionKind::Function);
+        assert_eq!(CompletionKind::from_type_name("DataFrame"), CompletionKind::Variable);
+
+        let mut autocomplete = Autocomplete::new();
+        autocomplete.add_dynamic_completions(vec![
+            item("data", "DataFrame"), item("datetime", "module"), item("Dataset", "type"), item("load_data", "function"),
+        ]);
+        autocomplete.update("d");
+        let kind_and_detail = |text: &str| suggestion(&autocomplete, text).map(|s| (s.kind, s.detail.as_str()));
+        // Values show their type; other kinds say what they are with their icon
+        assert_eq!(kind_and_detail("data"), Some((CompletionKind::Variable, "DataFrame")));
+        assert_eq!(kind_and_detail("datetime"), Some((CompletionKind::Module, "")));
+        assert_eq!(kind_and_detail("Dataset"), Some((CompletionKind::Class, "")));
+        assert_eq!(kind_and_detail("load_data"), Some((CompletionKind::Function, "")));
+        assert_eq!(kind_and_detail("def"), Some((CompletionKind::Keyword, "")));
+        assert_eq!(kind_and_detail("dict"), Some((CompletionKind::Class, "")));
+        assert_eq!(kind_and_detail("divmod"), Some((CompletionKind::Function, "")));
+    }
+
+    #[test]
+    fn test_docs_preview() {
+        let mut autocomplete = Autocomplete::new();
+        autocomplete.add_dynamic_completions(vec![item("load_data", "function")]);
+        autocomplete.update("load_d");
+        assert_eq!(autocomplete.get_selected(), Some("load_data"));
+
+        // Documentation is asked for once per selected name
+        assert_eq!(autocomplete.pending_docs_request().as_deref(), Some("load_data"));
+        let docs = Documentation {
+            kind: "function".to_string(),
+            signature: Some("(path, sep=',')".to_string()),
+            doc: "Read a CSV file into a DataFrame.".to_string(),
+        };
+        autocomplete.set_docs("load_data".to_string(), Some(docs.clone()));
+        assert_eq!(autocomplete.pending_docs_request(), None);
+
+        let mut out = Vec::new();
+        autocomplete.draw_preview(&mut out, &docs, "load_data", 0, 0, 28, 20).unwrap();
+        let out = String::from_utf8_lossy(&out);
+        assert!(out.contains("load_data(path, sep=',')"));
+        assert!(out.contains("Read a CSV file into a"));
+        assert!(out.contains("DataFrame."));
+
+        // Nothing to look up for keywords
+        autocomplete.update("whil");
+        assert_eq!(autocomplete.get_selected(), Some("while"));
+        assert_eq!(autocomplete.pending_docs_request(), None);
+
+        // Re-running code may have redefined the name
+        autocomplete.add_dynamic_completions(vec![item("load_data", "function")]);
+        autocomplete.update("load_d");
+        assert_eq!(autocomplete.pending_docs_request().as_deref(), Some("load_data"));
+    }
+
+    #[test]
+    fn test_wrap_text() {
+        assert_eq!(wrap_text("Read a CSV file into a DataFrame.", 12), vec!["Read a CSV", "file into a", "DataFrame."]);
+        assert_eq!(wrap_text("abcdefghij", 4), vec!["abcd", "efgh", "ij"]);
+        assert_eq!(wrap_text("one\ntwo", 10), vec!["one", "two"]);
+    }
 }
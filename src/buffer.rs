@@ -1,8 +1,11 @@
+use crate::state;
+use crate::undo_tree::{UndoHistory, UndoTree};
 use ropey::Rope;
+use serde::{Deserialize, Serialize};
 use std::time::{Duration, Instant};
 
 /// Represents a single edit operation for undo/redo
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum EditOp {
     Insert {
         pos: usize,      // byte position
@@ -14,33 +17,54 @@ pub enum EditOp {
     },
 }
 
+/// A change to the text: `removed` bytes at `pos` were replaced by `inserted` bytes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextChange {
+    pub pos: usize,
+    pub removed: usize,
+    pub inserted: usize,
+}
+
+/// Number of recent changes kept for remapping positions (folds) across edits
+const MAX_LOGGED_CHANGES: usize = 1000;
+
 /// Groups related edit operations (e.g., continuous typing)
-#[derive(Debug)]
-struct UndoGroup {
-    ops: Vec<EditOp>,
-    cursor_before: usize,
-    cursor_after: usize,
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UndoGroup {
+    pub ops: Vec<EditOp>,
+    pub cursor_before: usize,
+    pub cursor_after: usize,
 }
 
 /// Text buffer with undo/redo support
 pub struct Buffer {
     rope: Rope,
-    undo_stack: Vec<UndoGroup>,
-    redo_stack: Vec<UndoGroup>,
+    undo_tree: UndoTree,
+    saved_state: usize, // Undo tree state matching the file on disk
+    saved_hash: u64,    // Hash of the text in that state
     current_group: Option<UndoGroup>,
     last_edit_time: Option<Instant>,
     group_timeout: Duration,
+    grouping: bool, // Inside begin_undo_group: every edit joins the current group
+    revision: u64,  // Incremented on every change to the text
+    changes: Vec<(u64, TextChange)>, // Recent changes tagged with the revision they produced
+    changes_base: u64, // Revision before the oldest logged change
 }
 
 impl Buffer {
     pub fn new() -> Self {
         Self {
             rope: Rope::new(),
-            undo_stack: Vec::new(),
-            redo_stack: Vec::new(),
+            undo_tree: UndoTree::new(),
+            saved_state: 0,
+            saved_hash: state::hash_str(""),
             current_group: None,
             last_edit_time: None,
             group_timeout: Duration::from_millis(300), // Group edits within 300ms
+            grouping: false,
+            revision: 0,
+            changes: Vec::new(),
+            changes_base: 0,
         }
     }
     
@@ -62,12 +86,17 @@ impl Buffer {
             .replace("\r\n", "\n"); // CRLF -> LF
         
         Self {
+            saved_hash: state::hash_str(&s),
             rope: Rope::from_str(&s),
-            undo_stack: Vec::new(),
-            redo_stack: Vec::new(),
+            undo_tree: UndoTree::new(),
+            saved_state: 0,
             current_group: None,
             last_edit_time: None,
             group_timeout: Duration::from_millis(300),
+            grouping: false,
+            revision: 0,
+            changes: Vec::new(),
+            changes_base: 0,
         }
     }
     
@@ -76,6 +105,30 @@ impl Buffer {
         &self.rope
     }
     
+    /// Counter that changes whenever the text changes (for caching derived data)
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Changes made after `revision`, oldest first, or None if they are no longer all logged
+    pub fn changes_since(&self, revision: u64) -> Option<Vec<TextChange>> {
+        if revision < self.changes_base {
+            return None;
+        }
+        Some(self.changes.iter().filter(|(rev, _)| *rev > revision).map(|(_, change)| *change).collect())
+    }
+
+    /// Log a change for the current revision
+    fn log_change(&mut self, pos: usize, removed: usize, inserted: usize) {
+        self.changes.push((self.revision, TextChange { pos, removed, inserted }));
+        if self.changes.len() > MAX_LOGGED_CHANGES {
+            let (rev, _) = self.changes.remove(0);
+            // Every change of that revision must go, or a partial list would be returned
+            self.changes.retain(|(r, _)| *r > rev);
+            self.changes_base = rev;
+        }
+    }
+
     /// Get total byte length
     pub fn len_bytes(&self) -> usize {
         self.rope.len_bytes()
@@ -131,6 +184,8 @@ impl Buffer {
     pub fn insert(&mut self, pos: usize, text: &str, cursor_before: usize, cursor_after: usize) {
         let char_pos = self.byte_to_char(pos);
         self.rope.insert(char_pos, text);
+        self.revision += 1;
+        self.log_change(pos, 0, text.len());
         
         let op = EditOp::Insert {
             pos,
@@ -138,7 +193,6 @@ impl Buffer {
         };
         
         self.push_op(op, cursor_before, cursor_after);
-        self.redo_stack.clear();
     }
     
     /// Delete a range of bytes
@@ -153,6 +207,8 @@ impl Buffer {
         let char_start = self.byte_to_char(start);
         let char_end = self.byte_to_char(end);
         self.rope.remove(char_start..char_end);
+        self.revision += 1;
+        self.log_change(start, end - start, 0);
         
         let op = EditOp::Delete {
             pos: start,
@@ -160,15 +216,56 @@ impl Buffer {
         };
         
         self.push_op(op, cursor_before, cursor_after);
-        self.redo_stack.clear();
     }
     
+    /// Replace a range of bytes with new text as a single undo step
+    pub fn replace(&mut self, start: usize, end: usize, text: &str, cursor_before: usize, cursor_after: usize) {
+        if !self.grouping {
+            self.finalize_undo_group();
+        }
+
+        let start = start.min(self.len_bytes());
+        let end = end.clamp(start, self.len_bytes());
+        let mut ops = Vec::new();
+        if start < end {
+            let deleted = self.rope.byte_slice(start..end).to_string();
+            let char_start = self.byte_to_char(start);
+            let char_end = self.byte_to_char(end);
+            self.rope.remove(char_start..char_end);
+            ops.push(EditOp::Delete { pos: start, text: deleted });
+        }
+        if !text.is_empty() {
+            let char_pos = self.byte_to_char(start);
+            self.rope.insert(char_pos, text);
+            ops.push(EditOp::Insert { pos: start, text: text.to_string() });
+        }
+
+        if !ops.is_empty() {
+            self.revision += 1;
+            for op in &ops {
+                match op {
+                    EditOp::Delete { pos, text } => self.log_change(*pos, text.len(), 0),
+                    EditOp::Insert { pos, text } => self.log_change(*pos, 0, text.len()),
+                }
+            }
+            match self.current_group.as_mut().filter(|_| self.grouping) {
+                Some(group) => {
+                    group.ops.extend(ops);
+                    group.cursor_after = cursor_after;
+                }
+                None => self.undo_tree.push(UndoGroup { ops, cursor_before, cursor_after }),
+            }
+        }
+        // The next edit starts a new group rather than joining this one
+        self.last_edit_time = None;
+    }
+
     /// Push an operation to the current undo group
     fn push_op(&mut self, op: EditOp, cursor_before: usize, cursor_after: usize) {
         let now = Instant::now();
-        let should_start_new_group = self.last_edit_time
+        let should_start_new_group = self.current_group.is_none() || (!self.grouping && self.last_edit_time
             .map(|t| now.duration_since(t) > self.group_timeout)
-            .unwrap_or(true);
+            .unwrap_or(true));
         
         if should_start_new_group {
             self.finalize_undo_group();
@@ -189,81 +286,176 @@ impl Buffer {
     pub fn finalize_undo_group(&mut self) {
         if let Some(group) = self.current_group.take() {
             if !group.ops.is_empty() {
-                self.undo_stack.push(group);
+                self.undo_tree.push(group);
             }
         }
     }
     
+    /// Start a group that every edit joins until `end_undo_group`, however long they take
+    pub fn begin_undo_group(&mut self, cursor_before: usize) {
+        self.finalize_undo_group();
+        self.grouping = true;
+        self.current_group = Some(UndoGroup { ops: Vec::new(), cursor_before, cursor_after: cursor_before });
+    }
+
+    /// Close the current undo group so the next edit starts a new one
+    pub fn end_undo_group(&mut self) {
+        self.grouping = false;
+        self.finalize_undo_group();
+        self.last_edit_time = None;
+    }
+
     /// Undo the last group of operations
     pub fn undo(&mut self) -> Option<usize> {
         self.finalize_undo_group();
-        
-        if let Some(group) = self.undo_stack.pop() {
-            let cursor = group.cursor_before;
-            
-            // Apply operations in reverse
-            for op in group.ops.iter().rev() {
-                match op {
-                    EditOp::Insert { pos, text } => {
-                        // Undo an insert by deleting
-                        let char_start = self.byte_to_char(*pos);
-                        let char_end = char_start + text.chars().count();
-                        self.rope.remove(char_start..char_end);
-                    }
-                    EditOp::Delete { pos, text } => {
-                        // Undo a delete by inserting
-                        let char_pos = self.byte_to_char(*pos);
-                        self.rope.insert(char_pos, text);
-                    }
-                }
-            }
-            
-            self.redo_stack.push(group);
-            return Some(cursor);
-        }
-        
-        None
+        let group = self.undo_tree.undo()?.clone();
+        self.revision += 1;
+        self.revert_ops(&group.ops);
+        Some(group.cursor_before)
     }
     
     /// Redo the last undone group
     pub fn redo(&mut self) -> Option<usize> {
-        if let Some(group) = self.redo_stack.pop() {
-            let cursor = group.cursor_after;
-            
-            // Apply operations forward
-            for op in &group.ops {
-                match op {
-                    EditOp::Insert { pos, text } => {
-                        let char_pos = self.byte_to_char(*pos);
-                        self.rope.insert(char_pos, text);
-                    }
-                    EditOp::Delete { pos, text } => {
-                        let char_start = self.byte_to_char(*pos);
-                        let char_end = char_start + text.chars().count();
-                        self.rope.remove(char_start..char_end);
-                    }
+        // Edits still being grouped come first; redoing on top of them would corrupt the text
+        self.finalize_undo_group();
+        let group = self.undo_tree.redo()?.clone();
+        self.revision += 1;
+        self.apply_ops(&group.ops);
+        Some(group.cursor_after)
+    }
+
+    /// Move to any state of the undo tree, undoing and redoing along the way.
+    /// Returns where the cursor should go.
+    pub fn goto_state(&mut self, target: usize) -> Option<usize> {
+        self.finalize_undo_group();
+        if target >= self.undo_tree.len() || target == self.undo_tree.current() {
+            return None;
+        }
+        let (undo, redo) = self.undo_tree.path_to(target);
+        self.revision += 1;
+        let mut cursor = None;
+        for _ in undo {
+            if let Some(group) = self.undo_tree.undo().cloned() {
+                self.revert_ops(&group.ops);
+                cursor = Some(group.cursor_before);
+            }
+        }
+        for node in redo {
+            self.undo_tree.choose_redo(node);
+            if let Some(group) = self.undo_tree.redo().cloned() {
+                self.apply_ops(&group.ops);
+                cursor = Some(group.cursor_after);
+            }
+        }
+        cursor
+    }
+
+    fn apply_ops(&mut self, ops: &[EditOp]) {
+        for op in ops {
+            match op {
+                EditOp::Insert { pos, text } => {
+                    let char_pos = self.byte_to_char(*pos);
+                    self.rope.insert(char_pos, text);
+                    self.log_change(*pos, 0, text.len());
+                }
+                EditOp::Delete { pos, text } => {
+                    let char_start = self.byte_to_char(*pos);
+                    let char_end = char_start + text.chars().count();
+                    self.rope.remove(char_start..char_end);
+                    self.log_change(*pos, text.len(), 0);
+                }
+            }
+        }
+    }
+
+    /// Apply operations in reverse
+    fn revert_ops(&mut self, ops: &[EditOp]) {
+        for op in ops.iter().rev() {
+            match op {
+                EditOp::Insert { pos, text } => {
+                    // Undo an insert by deleting
+                    let char_start = self.byte_to_char(*pos);
+                    let char_end = char_start + text.chars().count();
+                    self.rope.remove(char_start..char_end);
+                    self.log_change(*pos, text.len(), 0);
+                }
+                EditOp::Delete { pos, text } => {
+                    // Undo a delete by inserting
+                    let char_pos = self.byte_to_char(*pos);
+                    self.rope.insert(char_pos, text);
+                    self.log_change(*pos, 0, text.len());
                 }
             }
-            
-            self.undo_stack.push(group);
-            return Some(cursor);
         }
-        
-        None
+    }
+
+    /// All states the text has been in
+    pub fn undo_tree(&self) -> &UndoTree {
+        &self.undo_tree
+    }
+
+    /// Remember the current state as the one written to disk
+    pub fn mark_saved(&mut self) {
+        self.finalize_undo_group();
+        self.saved_state = self.undo_tree.current();
+        self.saved_hash = state::hash_str(&self.rope.to_string());
+    }
+
+    /// Undo tree state last written to disk
+    pub fn saved_state(&self) -> usize {
+        self.saved_state
+    }
+
+    /// Whether the text is back in the state last written to disk
+    pub fn is_at_saved_state(&self) -> bool {
+        self.current_group.is_none() && self.undo_tree.current() == self.saved_state
+    }
+
+    /// Undo tree to keep with the file, positioned at the state on disk. Unsaved
+    /// edits stay reachable with Redo.
+    pub fn history(&mut self) -> UndoHistory {
+        self.finalize_undo_group();
+        UndoHistory { content_hash: self.saved_hash, tree: self.undo_tree.moved_to(self.saved_state) }
+    }
+
+    /// Take over the undo tree of an earlier session. Refused (returning false) unless
+    /// it was saved for exactly the current text.
+    pub fn restore_history(&mut self, history: UndoHistory) -> bool {
+        let hash = state::hash_str(&self.rope.to_string());
+        if history.content_hash != hash || !history.tree.is_valid() {
+            return false;
+        }
+        self.current_group = None;
+        self.undo_tree = history.tree;
+        self.saved_state = self.undo_tree.current();
+        self.saved_hash = hash;
+        true
     }
     
     /// Get the entire buffer as a string (for saving)
     pub fn to_string(&self) -> String {
         self.rope.to_string()
     }
-    
-    /// Check if there are undo operations available
-    pub fn can_undo(&self) -> bool {
-        !self.undo_stack.is_empty() || self.current_group.is_some()
-    }
-    
-    /// Check if there are redo operations available
-    pub fn can_redo(&self) -> bool {
-        !self.redo_stack.is_empty()
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_explicit_undo_group() {
+        let mut buffer = Buffer::from_string("a\nb\n".to_string());
+        buffer.begin_undo_group(1);
+        buffer.insert(1, "x", 1, 2);
+        buffer.replace(3, 4, "yy", 3, 5);
+        std::thread::sleep(Duration::from_millis(350));
+        buffer.insert(0, "z", 0, 1);
+        buffer.end_undo_group();
+        assert_eq!(buffer.rope().to_string(), "zax\nyy\n");
+
+        // One undo reverts every edit in the group, however far apart they were
+        assert_eq!(buffer.undo(), Some(1));
+        assert_eq!(buffer.rope().to_string(), "a\nb\n");
+        assert_eq!(buffer.redo(), Some(1));
+        assert_eq!(buffer.rope().to_string(), "zax\nyy\n");
     }
-}
\ No newline at end of file
+}
@@ -1,5 +1,7 @@
-use crate::kernel::ExecutionResult;
+use crate::kernel::{ExecutionResult, Kernel};
 use ropey::Rope;
+use std::error::Error;
+use std::time::Instant;
 
 /// Represents a cell in the notebook
 #[derive(Debug, Clone)]
@@ -14,6 +16,29 @@ pub struct Cell {
     pub output: Option<ExecutionResult>,
     /// Execution count
     pub execution_count: Option<usize>,
+    /// Execution state shown in the gutter
+    pub status: CellStatus,
+    /// Hash of the cell's code, used to notice edits after it was run
+    pub code_hash: u64,
+    /// Whether the inline output below the cell is folded away
+    pub output_collapsed: bool,
+    /// When the cell last finished running and how long it took (seconds)
+    pub last_run: Option<(Instant, f64)>,
+}
+
+/// Execution state of a cell
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CellStatus {
+    /// Never executed
+    Idle,
+    /// Currently executing
+    Running,
+    /// Last run succeeded
+    Ok,
+    /// Last run raised an error
+    Error,
+    /// Code was edited since it last ran
+    Stale,
 }
 
 /// Type of cell
@@ -23,6 +48,13 @@ pub enum CellType {
     Code,
     /// Markdown/text cell
     Markdown,
+    /// SQL run by the kernel (`##$$ sql [conn=name] [-> variable]`)
+    Sql {
+        /// Connection in the namespace to run on (the only one there is if None)
+        connection: Option<String>,
+        /// Python variable the result is bound to
+        variable: Option<String>,
+    },
 }
 
 /// Cell delimiter marker
@@ -52,6 +84,10 @@ pub fn parse_cells(buffer: &Rope) -> Vec<Cell> {
             cell_type: CellType::Code,
             output: None,
             execution_count: None,
+            status: CellStatus::Idle,
+            code_hash: 0,
+            output_collapsed: false,
+            last_run: None,
         });
         return cells;
     }
@@ -64,6 +100,10 @@ pub fn parse_cells(buffer: &Rope) -> Vec<Cell> {
             cell_type: CellType::Code,
             output: None,
             execution_count: None,
+            status: CellStatus::Idle,
+            code_hash: 0,
+            output_collapsed: false,
+            last_run: None,
         });
     }
 
@@ -86,11 +126,7 @@ pub fn parse_cells(buffer: &Rope) -> Vec<Cell> {
         };
 
         let line_text = buffer.slice(line_start..line_end).to_string();
-        let cell_type = if line_text.to_lowercase().contains("markdown") {
-            CellType::Markdown
-        } else {
-            CellType::Code
-        };
+        let cell_type = cell_type_from_header(&line_text);
 
         // Cell starts after the delimiter line
         let cell_start = if delimiter_line_idx + 1 < buffer.len_lines() {
@@ -105,12 +141,95 @@ pub fn parse_cells(buffer: &Rope) -> Vec<Cell> {
             cell_type,
             output: None,
             execution_count: None,
+            status: CellStatus::Idle,
+            code_hash: 0,
+            output_collapsed: false,
+            last_run: None,
         });
     }
 
+    hash_cells(buffer, &mut cells);
     cells
 }
 
+/// Type of the cell a `##$$` delimiter line starts: `##$$ sql conn=db -> df` is
+/// SQL run on `db` with the result bound to `df`, a title mentioning markdown is Markdown
+pub fn cell_type_from_header(line: &str) -> CellType {
+    let title = line.trim().strip_prefix(CELL_DELIMITER).unwrap_or("").replace("->", " -> ");
+    let mut words = title.split_whitespace();
+    if !words.next().is_some_and(|word| word.eq_ignore_ascii_case("sql")) {
+        return if line.to_lowercase().contains("markdown") { CellType::Markdown } else { CellType::Code };
+    }
+
+    let is_name = |word: &str| !word.starts_with(|c: char| c.is_ascii_digit())
+        && word.chars().all(|c| c.is_alphanumeric() || c == '_');
+    let mut connection = None;
+    let mut variable = None;
+    while let Some(word) = words.next() {
+        if let Some(name) = word.strip_prefix("conn=") {
+            connection = Some(name.to_string()).filter(|name| !name.is_empty());
+        } else if word == "->" {
+            variable = words.next().filter(|name| is_name(name)).map(str::to_string);
+        }
+    }
+    CellType::Sql { connection, variable }
+}
+
+/// Run a cell's code on `kernel`: Python as it is, SQL against the cell's connection
+pub fn execute_cell(kernel: &mut dyn Kernel, cell_type: &CellType, code: &str) -> Result<ExecutionResult, Box<dyn Error>> {
+    match cell_type {
+        CellType::Sql { connection, variable } => kernel.execute_sql(code, connection.as_deref(), variable.as_deref()),
+        _ => kernel.execute(code),
+    }
+}
+
+fn hash_cells(buffer: &Rope, cells: &mut [Cell]) {
+    for cell in cells.iter_mut() {
+        cell.code_hash = crate::state::hash_str(&get_cell_content(buffer, cell));
+    }
+}
+
+/// Carry execution state over from the previous parse of the buffer.
+/// A cell whose code is unchanged keeps its state; otherwise the cell in the
+/// same place (counting from the front before the first edited cell and from
+/// the back after it) keeps its output but is marked stale.
+pub fn carry_over_state(old: &[Cell], new: &mut [Cell]) {
+    let first_changed = old.iter()
+        .zip(new.iter())
+        .take_while(|(o, n)| o.code_hash == n.code_hash)
+        .count();
+    let mut used = vec![false; old.len()];
+
+    for i in 0..new.len() {
+        // Same code somewhere in the old cells (e.g. a cell was inserted above)
+        let unchanged = (0..old.len()).find(|&j| {
+            !used[j] && old[j].status != CellStatus::Idle && old[j].code_hash == new[i].code_hash
+        });
+        // Otherwise the cell at the corresponding position
+        let counterpart = unchanged.or_else(|| {
+            let j = if i < first_changed {
+                Some(i)
+            } else {
+                (i + old.len()).checked_sub(new.len()).filter(|&j| j >= first_changed)
+            };
+            j.filter(|&j| j < old.len() && !used[j] && old[j].status != CellStatus::Idle)
+        });
+
+        if let Some(j) = counterpart {
+            used[j] = true;
+            new[i].output = old[j].output.clone();
+            new[i].execution_count = old[j].execution_count;
+            new[i].output_collapsed = old[j].output_collapsed;
+            new[i].last_run = old[j].last_run;
+            new[i].status = match old[j].status {
+                status if old[j].code_hash == new[i].code_hash => status,
+                CellStatus::Running => CellStatus::Running,
+                _ => CellStatus::Stale,
+            };
+        }
+    }
+}
+
 /// Get the cell at a given byte position
 pub fn get_cell_at_position(cells: &[Cell], position: usize) -> Option<usize> {
     cells
@@ -146,10 +265,41 @@ pub fn get_cell_content(buffer: &Rope, cell: &Cell) -> String {
     buffer.slice(content_start..cell.end).to_string()
 }
 
+/// Short title for a cell: the text after `##$$` on its delimiter line,
+/// or its first non-empty line when the delimiter has no title
+pub fn cell_title(buffer: &Rope, cell: &Cell) -> String {
+    let start_line = buffer.byte_to_line(cell.start);
+    let first_line = buffer.line(start_line).to_string();
+    if let Some(title) = first_line.trim().strip_prefix(CELL_DELIMITER) {
+        let title = title.trim();
+        if !title.is_empty() {
+            return title.to_string();
+        }
+    }
+
+    get_cell_content(buffer, cell)
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .unwrap_or("")
+        .to_string()
+}
+
 /// Format output for display (Jupyter-style)
 pub fn format_output(result: &ExecutionResult) -> String {
+    let formatted = format_output_text(result);
+
+    // If no output and execution was successful, show a message
+    if formatted.is_empty() && result.success {
+        return "\x1b[90m(executed successfully)\x1b[0m".to_string();
+    }
+
+    formatted
+}
+
+/// Format the outputs of a result, empty if the cell printed nothing
+pub fn format_output_text(result: &ExecutionResult) -> String {
     let mut output = String::new();
-    let exec_count = result.execution_count.unwrap_or(0);
 
     for exec_output in &result.outputs {
         match exec_output {
@@ -224,12 +374,65 @@ pub fn format_output(result: &ExecutionResult) -> String {
     }
 
     // Remove trailing newline for cleaner display
-    let formatted = output.trim_end().to_string();
+    output.trim_end().to_string()
+}
 
-    // If no output and execution was successful, show a message
-    if formatted.is_empty() && result.success {
-        return "\x1b[90m(executed successfully)\x1b[0m".to_string();
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(cells: &mut [Cell], idx: usize, count: usize) {
+        cells[idx].status = CellStatus::Ok;
+        cells[idx].execution_count = Some(count);
     }
 
-    formatted
+    #[test]
+    fn test_cell_title() {
+        let buffer = Rope::from_str("import os\n##$$ Cell 2: Load data\ndf = 1\n##$$\n\nprint(df)\n");
+        let cells = parse_cells(&buffer);
+        let titles: Vec<String> = cells.iter().map(|c| cell_title(&buffer, c)).collect();
+        assert_eq!(titles, vec!["import os", "Cell 2: Load data", "print(df)"]);
+    }
+
+    #[test]
+    fn test_cell_type_from_header() {
+        let sql = |connection: Option<&str>, variable: Option<&str>| CellType::Sql {
+            connection: connection.map(str::to_string),
+            variable: variable.map(str::to_string),
+        };
+        assert_eq!(cell_type_from_header("##$$ sql\n"), sql(None, None));
+        assert_eq!(cell_type_from_header("##$$ SQL conn=db -> orders\n"), sql(Some("db"), Some("orders")));
+        assert_eq!(cell_type_from_header("##$$ sql ->totals"), sql(None, Some("totals")));
+        assert_eq!(cell_type_from_header("##$$ sql -> 1x"), sql(None, None));
+        assert_eq!(cell_type_from_header("##$$ Notes (markdown)"), CellType::Markdown);
+        assert_eq!(cell_type_from_header("##$$ sqlite setup"), CellType::Code);
+    }
+
+    #[test]
+    fn test_carry_over_state() {
+        let old_text = "##$$ a\nx = 1\n##$$ b\ny = 2\n##$$ c\nz = 3\n";
+        let mut old = parse_cells(&Rope::from_str(old_text));
+        run(&mut old, 0, 1);
+        run(&mut old, 2, 2);
+
+        // Editing the middle cell leaves the others untouched
+        let mut new = parse_cells(&Rope::from_str("##$$ a\nx = 1\n##$$ b\ny = 5\n##$$ c\nz = 3\n"));
+        carry_over_state(&old, &mut new);
+        assert_eq!(new[0].status, CellStatus::Ok);
+        assert_eq!(new[1].status, CellStatus::Idle);
+        assert_eq!(new[2].execution_count, Some(2));
+
+        // Editing an executed cell marks it stale but keeps its count
+        let mut new = parse_cells(&Rope::from_str("##$$ a\nx = 10\n##$$ b\ny = 2\n##$$ c\nz = 3\n"));
+        carry_over_state(&old, &mut new);
+        assert_eq!(new[0].status, CellStatus::Stale);
+        assert_eq!(new[0].execution_count, Some(1));
+
+        // Inserting a cell above keeps state attached to the right cells
+        let mut new = parse_cells(&Rope::from_str("##$$ new\n##$$ a\nx = 1\n##$$ b\ny = 2\n##$$ c\nz = 3\n"));
+        carry_over_state(&old, &mut new);
+        assert_eq!(new[0].status, CellStatus::Idle);
+        assert_eq!(new[1].status, CellStatus::Ok);
+        assert_eq!(new[3].execution_count, Some(2));
+    }
 }
@@ -0,0 +1,273 @@
+use crate::cell::{self, CellStatus, CellType};
+use crate::editor::Editor;
+use crate::fuzzy::fuzzy_match;
+use crossterm::{
+    cursor::{Hide, MoveTo},
+    event::{KeyCode, KeyModifiers},
+    execute,
+    style::{Attribute, Color, Print, ResetColor, SetAttribute, SetBackgroundColor, SetForegroundColor},
+    terminal,
+};
+use std::io::{self, Write};
+use std::time::Instant;
+use unicode_width::UnicodeWidthChar;
+
+/// What the event loop should do after a key press in the outline
+pub enum OutlineAction {
+    Continue,
+    Close,
+    Jump(usize), // Cell index to jump to
+}
+
+/// One cell as listed in the outline
+struct OutlineEntry {
+    cell: usize,
+    title: String,
+    line: usize,
+    cell_type: CellType,
+    status: CellStatus,
+    execution_count: Option<usize>,
+    last_run: Option<(Instant, f64)>,
+    matched: Vec<usize>, // Char indices of the title that matched the filter
+}
+
+/// Filterable list of the notebook's cells, drawn over the editor.
+/// Entries are rebuilt from the editor's cells on every draw, so titles and
+/// execution status stay current while the outline is open.
+pub struct CellOutline {
+    query: String,
+    selected: usize,
+}
+
+impl CellOutline {
+    /// Open the outline with the cell under the cursor selected
+    pub fn new(editor: &mut Editor) -> Self {
+        editor.refresh_cells();
+        let (line, _) = editor.cursor_position();
+        Self { query: String::new(), selected: editor.cell_index_at_line(line).unwrap_or(0) }
+    }
+
+    /// Cells matching the filter, best match first (buffer order when unfiltered)
+    fn entries(&self, editor: &Editor) -> Vec<OutlineEntry> {
+        let buffer = editor.buffer().rope();
+        let mut entries: Vec<(i64, OutlineEntry)> = editor.get_cells().iter().enumerate()
+            .filter_map(|(idx, c)| {
+                let title = cell::cell_title(buffer, c);
+                let (score, matched) = fuzzy_match(&self.query, &title)?;
+                Some((score, OutlineEntry {
+                    cell: idx,
+                    title,
+                    line: buffer.byte_to_line(c.start.min(buffer.len_bytes())) + 1,
+                    cell_type: c.cell_type.clone(),
+                    status: c.status,
+                    execution_count: c.execution_count,
+                    last_run: c.last_run,
+                    matched,
+                }))
+            })
+            .collect();
+        if !self.query.is_empty() {
+            entries.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        }
+        entries.into_iter().map(|(_, entry)| entry).collect()
+    }
+
+    pub fn handle_key(&mut self, code: KeyCode, modifiers: KeyModifiers, editor: &Editor) -> OutlineAction {
+        let count = self.entries(editor).len();
+        match code {
+            KeyCode::Esc => return OutlineAction::Close,
+            KeyCode::Char('p') | KeyCode::Char('P') if modifiers.contains(KeyModifiers::CONTROL) => {
+                return OutlineAction::Close;
+            }
+            KeyCode::Enter => {
+                return match self.entries(editor).get(self.selected) {
+                    Some(entry) => OutlineAction::Jump(entry.cell),
+                    None => OutlineAction::Continue,
+                };
+            }
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down => self.selected = (self.selected + 1).min(count.saturating_sub(1)),
+            KeyCode::PageUp => self.selected = self.selected.saturating_sub(10),
+            KeyCode::PageDown => self.selected = (self.selected + 10).min(count.saturating_sub(1)),
+            KeyCode::Home => self.selected = 0,
+            KeyCode::End => self.selected = count.saturating_sub(1),
+            KeyCode::Backspace => {
+                self.query.pop();
+                self.selected = 0;
+            }
+            KeyCode::Char(c) if !modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) => {
+                self.query.push(c);
+                self.selected = 0;
+            }
+            _ => {}
+        }
+        OutlineAction::Continue
+    }
+
+    /// Draw the outline centered over the editor area (above the status bar and any bottom pane)
+    pub fn draw<W: Write>(&mut self, writer: &mut W, editor: &Editor, bottom_height: usize) -> io::Result<()> {
+        let entries = self.entries(editor);
+        self.selected = self.selected.min(entries.len().saturating_sub(1));
+
+        let (width, height) = terminal::size()?;
+        let area_height = (height as usize).saturating_sub(1 + bottom_height);
+        let box_width = (width as usize).min(100);
+        let inner_width = box_width.saturating_sub(2);
+        let list_height = entries.len().clamp(1, area_height.saturating_sub(6).max(1));
+        let box_height = list_height + 4;
+        let start_x = (width as usize).saturating_sub(box_width) / 2;
+        let start_y = area_height.saturating_sub(box_height) / 2;
+        let scroll = (self.selected + 1).saturating_sub(list_height);
+
+        let background = Color::Rgb { r: 40, g: 40, b: 45 };
+        let border = Color::Rgb { r: 200, g: 200, b: 205 };
+
+        // Header: filter text and match count
+        let counter = format!("{}/{} ", entries.len(), editor.get_cells().len());
+        let prompt = format!("Cells: {}▏", self.query);
+        execute!(
+            writer,
+            MoveTo(start_x as u16, start_y as u16),
+            SetBackgroundColor(background),
+            SetForegroundColor(border),
+            Print(format!("╭{}╮", "─".repeat(inner_width))),
+            MoveTo(start_x as u16, (start_y + 1) as u16),
+            Print("│ "),
+            SetAttribute(Attribute::Bold),
+            SetForegroundColor(Color::White),
+            Print(fit(&prompt, inner_width.saturating_sub(1 + counter.chars().count()))),
+            SetAttribute(Attribute::Reset),
+            SetBackgroundColor(background),
+            SetForegroundColor(Color::Rgb { r: 120, g: 120, b: 125 }),
+            Print(&counter),
+            SetForegroundColor(border),
+            Print("│"),
+            MoveTo(start_x as u16, (start_y + 2) as u16),
+            Print(format!("├{}┤", "─".repeat(inner_width))),
+        )?;
+
+        for row in 0..list_height {
+            let y = (start_y + 3 + row) as u16;
+            execute!(writer, MoveTo(start_x as u16, y), SetBackgroundColor(background), SetForegroundColor(border), Print("│"))?;
+
+            match entries.get(scroll + row) {
+                Some(entry) => self.draw_entry(writer, entry, scroll + row == self.selected, inner_width)?,
+                None if entries.is_empty() && row == 0 => {
+                    execute!(
+                        writer,
+                        SetForegroundColor(Color::Rgb { r: 120, g: 120, b: 125 }),
+                        Print(fit(" No matching cells", inner_width)),
+                    )?;
+                }
+                None => execute!(writer, Print(" ".repeat(inner_width)))?,
+            }
+            execute!(writer, SetBackgroundColor(background), SetForegroundColor(border), Print("│"))?;
+        }
+
+        let footer = " Type to filter  ↑↓: Select  Enter: Jump  Esc: Close ";
+        execute!(
+            writer,
+            MoveTo(start_x as u16, (start_y + 3 + list_height) as u16),
+            Print(format!("╰{}╯", fit_with(footer, inner_width, '─'))),
+            ResetColor,
+            Hide
+        )?;
+        writer.flush()
+    }
+
+    /// One list row: status, execution count, type, title (matches highlighted), run time and line
+    fn draw_entry<W: Write>(&self, writer: &mut W, entry: &OutlineEntry, selected: bool, width: usize) -> io::Result<()> {
+        let background = if selected { Color::Rgb { r: 60, g: 90, b: 130 } } else { Color::Rgb { r: 40, g: 40, b: 45 } };
+        let text = if selected { Color::White } else { Color::Rgb { r: 180, g: 180, b: 185 } };
+        let dim = Color::Rgb { r: 120, g: 120, b: 125 };
+
+        let (glyph, glyph_color) = match entry.status {
+            CellStatus::Idle => ('○', Color::Rgb { r: 90, g: 90, b: 90 }),
+            CellStatus::Running => ('◐', Color::Rgb { r: 220, g: 190, b: 90 }),
+            CellStatus::Ok => ('●', Color::Rgb { r: 110, g: 170, b: 110 }),
+            CellStatus::Error => ('●', Color::Rgb { r: 210, g: 100, b: 100 }),
+            CellStatus::Stale => ('●', Color::Rgb { r: 140, g: 125, b: 95 }),
+        };
+        let count = match (entry.status, entry.execution_count) {
+            (CellStatus::Running, _) => "[*]".to_string(),
+            (_, Some(n)) => format!("[{}]", n),
+            (_, None) => "[ ]".to_string(),
+        };
+        let kind = match entry.cell_type {
+            CellType::Code => "py",
+            CellType::Markdown => "md",
+            CellType::Sql { .. } => "sql",
+        };
+        let right = match entry.last_run {
+            Some((at, secs)) => format!("{} · {}  L{} ", format_secs(secs), format_ago(at), entry.line),
+            None => format!("L{} ", entry.line),
+        };
+        let left = format!("{:>5} {}  ", count, kind);
+        let title_width = width.saturating_sub(3 + left.chars().count() + right.chars().count() + 1);
+
+        execute!(
+            writer,
+            SetBackgroundColor(background),
+            SetForegroundColor(glyph_color),
+            Print(format!(" {} ", glyph)),
+            SetForegroundColor(dim),
+            Print(&left),
+        )?;
+
+        // Title with the characters matched by the filter highlighted
+        let mut used = 0;
+        for (i, ch) in entry.title.chars().enumerate() {
+            let char_width = ch.width().unwrap_or(1);
+            if used + char_width > title_width {
+                break;
+            }
+            let color = if entry.matched.contains(&i) { Color::Rgb { r: 230, g: 190, b: 110 } } else { text };
+            execute!(writer, SetForegroundColor(color), Print(ch))?;
+            used += char_width;
+        }
+        execute!(
+            writer,
+            Print(" ".repeat(title_width - used + 1)),
+            SetForegroundColor(dim),
+            Print(&right),
+        )?;
+        Ok(())
+    }
+}
+
+/// Run time in a compact form (0.42s, 12.3s, 2m05s)
+fn format_secs(secs: f64) -> String {
+    if secs < 10.0 {
+        format!("{:.2}s", secs)
+    } else if secs < 60.0 {
+        format!("{:.1}s", secs)
+    } else {
+        format!("{}m{:02}s", secs as u64 / 60, secs as u64 % 60)
+    }
+}
+
+/// How long ago an execution finished (just now, 5m ago, 2h ago)
+fn format_ago(at: Instant) -> String {
+    let secs = at.elapsed().as_secs();
+    if secs < 10 {
+        "just now".to_string()
+    } else if secs < 60 {
+        format!("{}s ago", secs)
+    } else if secs < 3600 {
+        format!("{}m ago", secs / 60)
+    } else {
+        format!("{}h ago", secs / 3600)
+    }
+}
+
+fn fit(text: &str, width: usize) -> String {
+    fit_with(text, width, ' ')
+}
+
+/// Truncate or pad text to exactly `width` characters
+fn fit_with(text: &str, width: usize, pad: char) -> String {
+    let mut result: String = text.chars().take(width).collect();
+    let used = result.chars().count();
+    result.extend(std::iter::repeat_n(pad, width - used));
+    result
+}
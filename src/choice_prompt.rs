@@ -0,0 +1,178 @@
+use crossterm::{
+    cursor::{Hide, MoveTo},
+    event::{self, Event, KeyCode},
+    execute,
+    style::{Attribute, Color, Print, ResetColor, SetAttribute, SetBackgroundColor, SetForegroundColor},
+    terminal,
+};
+use std::io::{self, Write};
+
+/// Floating dialog with a title, a few lines of text and a row of buttons
+/// (same look as the exit prompt, but with caller-supplied options)
+pub struct ChoicePrompt {
+    title: String,
+    message: Vec<String>,
+    options: Vec<String>,
+    selected: usize,
+}
+
+impl ChoicePrompt {
+    pub fn new(title: &str, message: &[&str], options: &[&str]) -> Self {
+        Self {
+            title: title.to_string(),
+            message: message.iter().map(|s| s.to_string()).collect(),
+            options: options.iter().map(|s| s.to_string()).collect(),
+            selected: 0,
+        }
+    }
+
+    /// Show the dialog and return the chosen option index (None if cancelled with Esc)
+    /// The first letter of each option works as a shortcut
+    pub fn run(&mut self, stdout: &mut io::Stdout) -> io::Result<Option<usize>> {
+        loop {
+            self.draw(stdout)?;
+
+            if let Event::Key(key) = event::read()? {
+                if key.kind == event::KeyEventKind::Release {
+                    continue;
+                }
+
+                match key.code {
+                    KeyCode::Enter => return Ok(Some(self.selected)),
+                    KeyCode::Esc => return Ok(None),
+                    KeyCode::Left | KeyCode::BackTab => {
+                        self.selected = (self.selected + self.options.len() - 1) % self.options.len();
+                    }
+                    KeyCode::Right | KeyCode::Tab => {
+                        self.selected = (self.selected + 1) % self.options.len();
+                    }
+                    KeyCode::Char(c) => {
+                        let c = c.to_ascii_lowercase();
+                        if let Some(idx) = self.options.iter().position(|o| {
+                            o.chars().next().map(|f| f.to_ascii_lowercase()) == Some(c)
+                        }) {
+                            return Ok(Some(idx));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    fn draw(&self, stdout: &mut io::Stdout) -> io::Result<()> {
+        let (width, height) = terminal::size()?;
+
+        let buttons_width: usize = self.options.iter().map(|o| o.chars().count() + 2).sum::<usize>()
+            + 2 * self.options.len().saturating_sub(1);
+        let longest_message = self.message.iter().map(|m| m.chars().count()).max().unwrap_or(0);
+        let prompt_width = (buttons_width + 4)
+            .max(longest_message + 4)
+            .max(40)
+            .min(width as usize);
+        let prompt_height = self.message.len() + 6;
+        let prompt_x = (width as usize).saturating_sub(prompt_width) / 2;
+        let prompt_y = (height as usize).saturating_sub(prompt_height) / 2;
+        let inner_width = prompt_width.saturating_sub(2);
+
+        let background = Color::Rgb { r: 40, g: 40, b: 45 };
+        let text_color = Color::Rgb { r: 200, g: 200, b: 205 };
+
+        // Shadow
+        for y in 1..prompt_height {
+            execute!(
+                stdout,
+                MoveTo((prompt_x + 2).min(width as usize - 1) as u16, (prompt_y + y) as u16),
+                SetBackgroundColor(Color::Black),
+                Print(" ".repeat(prompt_width.min((width as usize).saturating_sub(prompt_x + 2))))
+            )?;
+        }
+
+        for y in 0..prompt_height {
+            execute!(
+                stdout,
+                MoveTo(prompt_x as u16, (prompt_y + y) as u16),
+                SetBackgroundColor(background),
+                SetForegroundColor(text_color),
+            )?;
+
+            if y == 0 {
+                write!(stdout, "╭{}╮", "─".repeat(inner_width))?;
+            } else if y == prompt_height - 1 {
+                write!(stdout, "╰{}╯", "─".repeat(inner_width))?;
+            } else if y == 1 {
+                // Title line
+                write!(stdout, "│ ")?;
+                let title = Self::truncate(&self.title, inner_width.saturating_sub(2));
+                execute!(
+                    stdout,
+                    SetAttribute(Attribute::Bold),
+                    SetForegroundColor(Color::White),
+                    Print(&title),
+                    SetAttribute(Attribute::Reset),
+                    SetBackgroundColor(background),
+                    SetForegroundColor(text_color),
+                )?;
+                let padding = inner_width.saturating_sub(1 + title.chars().count());
+                write!(stdout, "{}│", " ".repeat(padding))?;
+            } else if y >= 3 && y < 3 + self.message.len() {
+                // Message lines
+                let line = Self::truncate(&self.message[y - 3], inner_width.saturating_sub(2));
+                execute!(
+                    stdout,
+                    Print("│ "),
+                    SetForegroundColor(Color::Rgb { r: 180, g: 180, b: 185 }),
+                    Print(&line),
+                    SetForegroundColor(text_color),
+                )?;
+                let padding = inner_width.saturating_sub(1 + line.chars().count());
+                write!(stdout, "{}│", " ".repeat(padding))?;
+            } else if y == prompt_height - 2 {
+                // Buttons line
+                write!(stdout, "│")?;
+                let padding_left = inner_width.saturating_sub(buttons_width) / 2;
+                write!(stdout, "{}", " ".repeat(padding_left))?;
+                for (idx, option) in self.options.iter().enumerate() {
+                    if idx > 0 {
+                        write!(stdout, "  ")?;
+                    }
+                    let selected = idx == self.selected;
+                    execute!(
+                        stdout,
+                        SetBackgroundColor(if selected {
+                            Color::Rgb { r: 60, g: 90, b: 130 }
+                        } else {
+                            background
+                        }),
+                        SetForegroundColor(if selected {
+                            Color::White
+                        } else {
+                            Color::Rgb { r: 150, g: 150, b: 155 }
+                        }),
+                        Print(format!(" {} ", option)),
+                        SetBackgroundColor(background),
+                        SetForegroundColor(text_color),
+                    )?;
+                }
+                let remaining = inner_width.saturating_sub(padding_left + buttons_width);
+                write!(stdout, "{}│", " ".repeat(remaining))?;
+            } else {
+                write!(stdout, "│{}│", " ".repeat(inner_width))?;
+            }
+        }
+
+        execute!(stdout, ResetColor, Hide)?;
+        stdout.flush()?;
+        Ok(())
+    }
+
+    fn truncate(text: &str, max: usize) -> String {
+        if text.chars().count() > max {
+            let mut truncated: String = text.chars().take(max.saturating_sub(3)).collect();
+            truncated.push_str("...");
+            truncated
+        } else {
+            text.to_string()
+        }
+    }
+}
@@ -36,7 +36,20 @@ pub enum Command {
     Dedent,    // Dedent line(s)
     Backspace,
     Delete,
-    
+
+    // Line operations (each is a single undo step)
+    ToggleComment,     // Comment or uncomment the selected lines
+    ToggleBlockComment, // Wrap the selection in a block comment, or unwrap it
+    DuplicateLines,    // Copy the selection, or the current line(s), below itself
+    DeleteLines,
+    JoinLines,
+    MoveLinesUp,
+    MoveLinesDown,
+    SortLines,
+
+    // Multiple cursors
+    AddNextOccurrence, // Add a cursor at the next occurrence of the selection (Ctrl+D)
+
     // Clipboard operations
     Copy,
     Cut,
@@ -46,6 +59,40 @@ pub enum Command {
     Save,
     SaveAs,
     
+    // File format conversion
+    ToggleLineEnding,  // Switch between LF and CRLF
+    ToggleIndentStyle, // Switch between tab and space indentation
+    ToggleBom,         // Add or remove the UTF-8 byte order mark
+    ToggleAutoPair,    // Close brackets and quotes automatically
+
+    // View
+    ToggleGutter,      // Show or hide line numbers and cell status
+    ToggleInlineOutputs, // Show cell outputs below each cell
+    ToggleOutputCollapsed, // Fold the inline output of the current cell
+    ToggleSoftWrap,    // Wrap long lines at the window width
+    ToggleFold,        // Fold or unfold the cell, block or bracket at the cursor
+    ToggleFoldAllCells, // Fold every cell to its header, or unfold everything
+
+    // Cell structure (each is a single undo step)
+    InsertCellAbove,
+    InsertCellBelow,
+    SplitCell,         // Start a new cell at the cursor line
+    MergeCellBelow,    // Join the next cell onto the current one
+    MoveCellUp,
+    MoveCellDown,
+    DuplicateCell,
+    DeleteCell,
+
+    // Navigation
+    GoToLine,          // Prompt for a line (or line:col) and jump there
+    JumpToMatchingBracket,
+    NavigateBack,      // Return to where the cursor was before the last jump
+    NavigateForward,
+
+    // Cell navigation
+    NextCell,
+    PrevCell,
+    
     // Find and Replace
     FindReplace,
     FindNext,
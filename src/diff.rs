@@ -0,0 +1,92 @@
+/// One line of a line-based diff
+#[derive(Debug, Clone, PartialEq)]
+pub enum DiffLine {
+    Same(String),
+    Removed(String),
+    Added(String),
+}
+
+/// Above this many (old lines x new lines) the LCS table gets too big,
+/// and the changed region is shown as a plain remove/add block instead
+const MAX_LCS_CELLS: usize = 4_000_000;
+
+/// Compute a line diff from `old` to `new` using the longest common subsequence
+pub fn line_diff(old: &str, new: &str) -> Vec<DiffLine> {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+
+    // Skip the common prefix and suffix - usually most of the file
+    let prefix = old_lines.iter()
+        .zip(new_lines.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let suffix = old_lines[prefix..].iter().rev()
+        .zip(new_lines[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let old_mid = &old_lines[prefix..old_lines.len() - suffix];
+    let new_mid = &new_lines[prefix..new_lines.len() - suffix];
+
+    let mut result: Vec<DiffLine> = old_lines[..prefix].iter()
+        .map(|l| DiffLine::Same(l.to_string()))
+        .collect();
+
+    if old_mid.len().saturating_mul(new_mid.len()) > MAX_LCS_CELLS {
+        result.extend(old_mid.iter().map(|l| DiffLine::Removed(l.to_string())));
+        result.extend(new_mid.iter().map(|l| DiffLine::Added(l.to_string())));
+    } else {
+        // lcs[i][j] = length of the LCS of old_mid[i..] and new_mid[j..]
+        let (n, m) = (old_mid.len(), new_mid.len());
+        let mut lcs = vec![vec![0u32; m + 1]; n + 1];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                lcs[i][j] = if old_mid[i] == new_mid[j] {
+                    lcs[i + 1][j + 1] + 1
+                } else {
+                    lcs[i + 1][j].max(lcs[i][j + 1])
+                };
+            }
+        }
+
+        let (mut i, mut j) = (0, 0);
+        while i < n && j < m {
+            if old_mid[i] == new_mid[j] {
+                result.push(DiffLine::Same(old_mid[i].to_string()));
+                i += 1;
+                j += 1;
+            } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+                result.push(DiffLine::Removed(old_mid[i].to_string()));
+                i += 1;
+            } else {
+                result.push(DiffLine::Added(new_mid[j].to_string()));
+                j += 1;
+            }
+        }
+        result.extend(old_mid[i..].iter().map(|l| DiffLine::Removed(l.to_string())));
+        result.extend(new_mid[j..].iter().map(|l| DiffLine::Added(l.to_string())));
+    }
+
+    result.extend(old_lines[old_lines.len() - suffix..].iter().map(|l| DiffLine::Same(l.to_string())));
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_diff() {
+        let old = "a\nb\nc\nd\n";
+        let new = "a\nc\nx\nd\n";
+        assert_eq!(line_diff(old, new), vec![
+            DiffLine::Same("a".to_string()),
+            DiffLine::Removed("b".to_string()),
+            DiffLine::Same("c".to_string()),
+            DiffLine::Added("x".to_string()),
+            DiffLine::Same("d".to_string()),
+        ]);
+
+        assert!(line_diff(old, old).iter().all(|l| matches!(l, DiffLine::Same(_))));
+    }
+}
@@ -0,0 +1,175 @@
+use crate::diff::DiffLine;
+use crossterm::{
+    cursor::{Hide, MoveTo},
+    event::{self, Event, KeyCode},
+    execute,
+    style::{Attribute, Color, Print, ResetColor, SetAttribute, SetBackgroundColor, SetForegroundColor},
+    terminal::{self, Clear, ClearType},
+};
+use std::io::{self, Write};
+use unicode_width::UnicodeWidthChar;
+
+/// Read-only floating window that shows a line diff
+pub struct DiffView {
+    title: String,
+    lines: Vec<DiffLine>,
+    scroll: usize,
+}
+
+impl DiffView {
+    pub fn new(title: &str, lines: Vec<DiffLine>) -> Self {
+        // Start at the first change rather than the top of the file
+        let first_change = lines.iter()
+            .position(|l| !matches!(l, DiffLine::Same(_)))
+            .unwrap_or(0);
+        Self {
+            title: title.to_string(),
+            lines,
+            scroll: first_change.saturating_sub(3),
+        }
+    }
+
+    /// Show the diff until the user closes it with Esc, q or Enter
+    pub fn run(&mut self, stdout: &mut io::Stdout) -> io::Result<()> {
+        loop {
+            self.draw(stdout)?;
+
+            if let Event::Key(key) = event::read()? {
+                if key.kind == event::KeyEventKind::Release {
+                    continue;
+                }
+
+                let (_, height) = terminal::size()?;
+                let page = Self::list_height(height).max(1);
+                let max_scroll = self.lines.len().saturating_sub(page);
+
+                match key.code {
+                    KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q') => break,
+                    KeyCode::Up | KeyCode::Char('k') => self.scroll = self.scroll.saturating_sub(1),
+                    KeyCode::Down | KeyCode::Char('j') => self.scroll = (self.scroll + 1).min(max_scroll),
+                    KeyCode::PageUp => self.scroll = self.scroll.saturating_sub(page),
+                    KeyCode::PageDown => self.scroll = (self.scroll + page).min(max_scroll),
+                    KeyCode::Home => self.scroll = 0,
+                    KeyCode::End => self.scroll = max_scroll,
+                    KeyCode::Char('n') => {
+                        // Jump to the next block of changes
+                        if let Some(next) = self.next_change(self.scroll + 1) {
+                            self.scroll = next.min(max_scroll);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        execute!(stdout, Clear(ClearType::All))?;
+        Ok(())
+    }
+
+    /// Find the start of the next change block at or after `from`
+    fn next_change(&self, from: usize) -> Option<usize> {
+        let mut i = from;
+        // Skip the rest of the current change block
+        while i < self.lines.len() && !matches!(self.lines[i], DiffLine::Same(_)) {
+            i += 1;
+        }
+        (i..self.lines.len()).find(|&idx| !matches!(self.lines[idx], DiffLine::Same(_)))
+    }
+
+    fn list_height(height: u16) -> usize {
+        (height as usize).saturating_sub(6)
+    }
+
+    fn draw(&self, stdout: &mut io::Stdout) -> io::Result<()> {
+        let (width, height) = terminal::size()?;
+        let box_width = (width as usize).saturating_sub(4).max(20);
+        let list_height = Self::list_height(height);
+        let start_x = (width as usize).saturating_sub(box_width) / 2;
+        let start_y = 1;
+        let inner_width = box_width.saturating_sub(2);
+
+        let background = Color::Rgb { r: 40, g: 40, b: 45 };
+        let border = Color::Rgb { r: 200, g: 200, b: 205 };
+
+        // Top border and title
+        execute!(
+            stdout,
+            MoveTo(start_x as u16, start_y as u16),
+            SetBackgroundColor(background),
+            SetForegroundColor(border),
+            Print(format!("╭{}╮", "─".repeat(inner_width))),
+            MoveTo(start_x as u16, (start_y + 1) as u16),
+            Print("│ "),
+            SetAttribute(Attribute::Bold),
+            SetForegroundColor(Color::White),
+            Print(Self::fit(&self.title, inner_width.saturating_sub(1))),
+            SetAttribute(Attribute::Reset),
+            SetBackgroundColor(background),
+            SetForegroundColor(border),
+            Print("│"),
+            MoveTo(start_x as u16, (start_y + 2) as u16),
+            Print(format!("├{}┤", "─".repeat(inner_width))),
+        )?;
+
+        // Diff lines
+        for row in 0..list_height {
+            let y = (start_y + 3 + row) as u16;
+            execute!(stdout, MoveTo(start_x as u16, y), SetBackgroundColor(background), SetForegroundColor(border), Print("│"))?;
+
+            let (marker, text, fg, bg) = match self.lines.get(self.scroll + row) {
+                Some(DiffLine::Same(text)) => (' ', text.as_str(), Color::Rgb { r: 150, g: 150, b: 155 }, background),
+                Some(DiffLine::Removed(text)) => ('-', text.as_str(), Color::Rgb { r: 230, g: 150, b: 150 }, Color::Rgb { r: 70, g: 35, b: 35 }),
+                Some(DiffLine::Added(text)) => ('+', text.as_str(), Color::Rgb { r: 150, g: 220, b: 150 }, Color::Rgb { r: 35, g: 65, b: 35 }),
+                None => (' ', "", border, background),
+            };
+
+            execute!(
+                stdout,
+                SetBackgroundColor(bg),
+                SetForegroundColor(fg),
+                Print(Self::fit(&format!("{} {}", marker, text), inner_width)),
+                SetBackgroundColor(background),
+                SetForegroundColor(border),
+                Print("│"),
+            )?;
+        }
+
+        // Bottom border with position and help
+        let footer = format!(" {}/{}  ↑↓ PgUp/PgDn: Scroll  n: Next change  Esc: Close ",
+            (self.scroll + 1).min(self.lines.len()), self.lines.len());
+        execute!(
+            stdout,
+            MoveTo(start_x as u16, (start_y + 3 + list_height) as u16),
+            Print(format!("╰{}╯", Self::fit_with(&footer, inner_width, '─'))),
+            ResetColor,
+            Hide
+        )?;
+
+        stdout.flush()?;
+        Ok(())
+    }
+
+    /// Truncate or pad text to exactly `width` display columns
+    fn fit(text: &str, width: usize) -> String {
+        Self::fit_with(text, width, ' ')
+    }
+
+    fn fit_with(text: &str, width: usize, pad: char) -> String {
+        let mut result = String::new();
+        let mut used = 0;
+        for ch in text.chars() {
+            let ch = if ch == '\t' { ' ' } else { ch };
+            let w = ch.width().unwrap_or(1);
+            if used + w > width {
+                break;
+            }
+            result.push(ch);
+            used += w;
+        }
+        while used < width {
+            result.push(pad);
+            used += 1;
+        }
+        result
+    }
+}
@@ -1,14 +1,24 @@
-use crate::kernel::{ExecutionOutput, ExecutionResult, Kernel, KernelInfo, KernelType};
+use crate::kernel::{CompletionItem, Documentation, ExecutionOutput, ExecutionResult, Kernel, KernelInfo, KernelType, Signature};
 use std::error::Error;
 use std::io::{BufRead, BufReader, Write};
-use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+/// How long the editor waits for the answer to a request (completions, docs, ...) before
+/// going on without it, so a slow or stuck kernel doesn't freeze typing
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Listing a package the session hasn't imported imports it in a separate process
+const MODULES_TIMEOUT: Duration = Duration::from_secs(10);
 
 /// Direct Python kernel using subprocess communication
 pub struct DirectKernel {
     info: KernelInfo,
     process: Option<Child>,
     stdin: Option<ChildStdin>,
-    stdout: Option<BufReader<ChildStdout>>,
+    output: Option<Receiver<String>>, // Lines the kernel prints, read on a separate thread
+    unanswered_requests: usize, // Requests given up on whose answers are still to come
     execution_count: usize,
 }
 
@@ -23,7 +33,8 @@ impl DirectKernel {
             },
             process: None,
             stdin: None,
-            stdout: None,
+            output: None,
+            unanswered_requests: 0,
             execution_count: 0,
         }
     }
@@ -52,12 +63,251 @@ except (AttributeError, OSError):
 # Ensure TERM is set to dumb to avoid escape codes
 os.environ['TERM'] = 'dumb'
 
+def _sage_resolve(name):
+    # Look up a dotted name in the user's namespace (or builtins). Attributes are found
+    # statically so property getters don't run; a property resolves to itself.
+    import builtins
+    import inspect
+    parts = name.split('.')
+    if parts[0] in globals():
+        obj = globals()[parts[0]]
+    else:
+        obj = getattr(builtins, parts[0])
+    for part in parts[1:]:
+        if inspect.ismodule(obj):
+            # Modules may import submodules lazily in their __getattr__
+            obj = getattr(obj, part)
+            continue
+        attr = inspect.getattr_static(obj, part)
+        # Methods are bound to the object, which leaves out `self` from their signature
+        obj = getattr(obj, part) if inspect.isfunction(attr) or inspect.ismethoddescriptor(attr) else attr
+    return obj
+
+def _sage_inspect(name):
+    import inspect
+    obj = _sage_resolve(name)
+    if inspect.ismodule(obj):
+        kind = "module"
+    elif inspect.isclass(obj):
+        kind = "class"
+    elif callable(obj):
+        kind = "function"
+    else:
+        kind = type(obj).__name__
+    signature = None
+    if callable(obj):
+        try:
+            signature = str(inspect.signature(obj))
+        except (TypeError, ValueError):
+            pass
+    doc = inspect.getdoc(obj) or ""
+    first_paragraph = " ".join(doc.strip().split("\n\n")[0].split())
+    return {"kind": kind, "signature": signature, "doc": first_paragraph}
+
+def _sage_signature(name, base):
+    import inspect
+    if base:
+        # A method of what `base` returns: a class constructs itself, a function says in its annotation
+        owner = _sage_resolve(base)
+        if not inspect.isclass(owner):
+            owner = inspect.signature(owner).return_annotation
+            if not inspect.isclass(owner):
+                return None
+        obj = getattr(owner, name)
+        # Methods looked up on the class still have `self`
+        unbound = not inspect.ismethod(obj) and not isinstance(inspect.getattr_static(owner, name, None), staticmethod)
+    else:
+        obj = _sage_resolve(name)
+        unbound = False
+    parameters = [
+        {"name": p.name, "text": str(p), "kind": p.kind.name.lower()}
+        for p in inspect.signature(obj).parameters.values()
+    ]
+    if unbound and parameters:
+        parameters = parameters[1:]
+    return {"label": name.split('.')[-1], "parameters": parameters}
+
+def _sage_complete(code, line, column):
+    # Static analysis with jedi (if installed), which also sees the live namespace
+    try:
+        import jedi
+    except ImportError:
+        return []
+    kinds = {"module": "module", "class": "type", "function": "function"}
+    completions = jedi.Interpreter(code, [globals()]).complete(line + 1, column)
+    # The editor already knows the keywords, and the builtins unless completing an attribute
+    attribute = code.split("\n")[line][:column].endswith(".")
+    return [
+        {"name": c.name, "type": kinds.get(c.type, "")}
+        for c in completions[:1000]
+        if c.type != "keyword" and (attribute or c.module_name != "builtins")
+        and not c.name.startswith(("__", "_sage"))
+    ]
+
+_SAGE_MODULE_NAMES = """
+def _sage_module_names(module):
+    # A module's submodules and exported names, with the type name of each
+    import pkgutil
+    items = {}
+    for submodule in pkgutil.iter_modules(getattr(module, "__path__", [])):
+        items[submodule.name] = "module"
+    exported = getattr(module, "__all__", None)
+    if exported is None:
+        exported = [name for name in dir(module) if not name.startswith("_")]
+    for name in exported:
+        if isinstance(name, str) and name not in items:
+            items[name] = type(getattr(module, name, None)).__name__
+    return items
+"""
+exec(_SAGE_MODULE_NAMES)
+
+def _sage_modules(package):
+    # The interpreter's top-level modules, or a package's submodules and exported names.
+    # A package the session hasn't imported is imported by a short-lived process, so
+    # listing it doesn't run its import code here or add it to sys.modules.
+    import pkgutil
+    items = {}
+    if package is None:
+        for name in sys.builtin_module_names:
+            items[name] = "module"
+        for module in pkgutil.iter_modules():
+            items[module.name] = "module"
+    elif package in sys.modules:
+        items = _sage_module_names(sys.modules[package])
+    else:
+        import subprocess
+        code = (_SAGE_MODULE_NAMES + "import importlib, json, sys\n"
+                + "print(); print(json.dumps(_sage_module_names(importlib.import_module(sys.argv[1]))))")
+        # The same search path as the session (including its working directory)
+        env = dict(os.environ, PYTHONPATH=os.pathsep.join(path or os.getcwd() for path in sys.path))
+        result = subprocess.run([sys.executable, "-c", code, package], stdin=subprocess.DEVNULL,
+                                capture_output=True, text=True, timeout=10, env=env)
+        if result.returncode != 0:
+            return None
+        # Importing the package may print things; the names come last
+        items = json.loads(result.stdout.splitlines()[-1])
+    return [{"name": name, "type": kind} for name, kind in sorted(items.items()) if name.isidentifier()]
+
+class _SageTable:
+    # Rows shown as an aligned text table
+    def __init__(self, columns, rows, more=False):
+        self.columns, self.rows, self.more = columns, rows, more
+
+    def __repr__(self):
+        cells = [[str(c) for c in self.columns]] + [["NULL" if v is None else str(v) for v in row] for row in self.rows]
+        widths = [max(len(row[i]) for row in cells) for i in range(len(self.columns))]
+        lines = [" | ".join(v.ljust(w) for v, w in zip(row, widths)) for row in cells]
+        lines.insert(1, "-+-".join("-" * w for w in widths))
+        lines.append("..." if self.more else f"({len(self.rows)} row{'' if len(self.rows) == 1 else 's'})")
+        return "\n".join(lines)
+
+def _sage_sql_connection(obj):
+    # "duckdb", "sqlite" or "spark" if obj can run SQL
+    kind = type(obj).__name__
+    if kind == "module":
+        return "duckdb" if obj.__name__ == "duckdb" else None
+    if kind == "DuckDBPyConnection":
+        return "duckdb"
+    if kind == "Connection" and type(obj).__module__ == "sqlite3":
+        return "sqlite"
+    if kind == "SparkSession":
+        return "spark"
+    return None
+
+def _sage_sql_cell(query, connection=None, variable=None):
+    # Run a SQL cell on a connection of the namespace, binding the result to variable
+    namespace = globals()
+    if connection is not None:
+        if connection not in namespace:
+            raise NameError(f"No connection named {connection!r}")
+        con = namespace[connection]
+        kind = _sage_sql_connection(con)
+        if kind is None:
+            raise TypeError(f"{connection} is a {type(con).__name__}, not a DuckDB, SQLite or Spark connection")
+    else:
+        found = [(name, value) for name, value in namespace.items()
+                 if not name.startswith("_") and type(value).__name__ != "module" and _sage_sql_connection(value)]
+        if len(found) > 1:
+            names = ", ".join(name for name, _ in found)
+            raise NameError(f"Several connections ({names}): pick one with ##$$ sql conn=name")
+        if found:
+            con = found[0][1]
+        else:
+            import duckdb
+            con = duckdb
+        kind = _sage_sql_connection(con)
+
+    try:
+        import pandas
+    except ImportError:
+        pandas = None
+    result = None
+    if kind == "duckdb":
+        relation = con.sql(query)
+        if relation is not None:
+            result = relation.df() if variable and pandas else relation
+    elif kind == "sqlite":
+        try:
+            cursor = con.execute(query)
+        except Exception as e:
+            if "one statement at a time" not in str(e):
+                raise
+            cursor = con.executescript(query)
+        if cursor.description:
+            columns = [d[0] for d in cursor.description]
+            rows = cursor.fetchall()
+            if pandas:
+                result = pandas.DataFrame(rows, columns=columns)
+            else:
+                # Bind the rows, show them as a table
+                if variable:
+                    namespace[variable] = rows
+                return _SageTable(columns, rows)
+    else:
+        frame = con.sql(query)
+        if variable:
+            namespace[variable] = frame
+        rows = frame.limit(21).collect()
+        return _SageTable(frame.columns, [tuple(row) for row in rows[:20]], len(rows) > 20)
+
+    if variable:
+        namespace[variable] = result
+    return result
+
+def _sage_handle_request(request):
+    # Answer an editor request (documentation, ...) without executing user code
+    kind = request.get("kind")
+    data = None
+    try:
+        with contextlib.redirect_stdout(io.StringIO()):
+            if kind == "inspect":
+                data = _sage_inspect(request["name"])
+            elif kind == "signature":
+                data = _sage_signature(request["name"], request.get("base"))
+            elif kind == "complete":
+                data = _sage_complete(request["code"], request["line"], request["column"])
+            elif kind == "modules":
+                data = _sage_modules(request.get("package"))
+            elif kind == "cwd":
+                data = os.getcwd()
+    except Exception:
+        data = None
+    print("SAGE_OUTPUT_START", flush=True)
+    print(json.dumps({"type": kind, "request": True, "data": data}), flush=True)
+    print("SAGE_OUTPUT_END", flush=True)
+
 print("SAGE_KERNEL_READY", flush=True)
 
 while True:
     try:
         # Read delimiter
         line = input()
+        if line == "SAGE_REQUEST_START":
+            # One JSON request line, then the end delimiter
+            _sage_request = json.loads(input())
+            input()
+            _sage_handle_request(_sage_request)
+            continue
         if line != "SAGE_EXEC_START":
             continue
 
@@ -113,7 +363,21 @@ while True:
             type_methods = {}  # Maps type names to their methods
             sql_tables = []    # SQL table names
             sql_columns = []   # SQL column names (format: "table.column")
+            sql_column_types = {}  # Column types by "table.column"
             sql_functions = [] # SQL function names
+            frame_columns = {} # Column names of DataFrames and relations by variable name
+
+            def _sage_add_sql_table(table_name, columns):
+                # Record a table and its (name, type) columns
+                if table_name not in sql_tables:
+                    sql_tables.append(table_name)
+                for col_name, col_type in columns:
+                    full_name = f"{table_name}.{col_name}"
+                    sql_column_types[full_name] = str(col_type)
+                    if full_name not in sql_columns:
+                        sql_columns.append(full_name)
+                    if col_name not in sql_columns:
+                        sql_columns.append(col_name)
 
             # Debug: Check what's in globals
             with open('/tmp/sage_python_debug.txt', 'a') as debug_f:
@@ -291,6 +555,26 @@ while True:
                         debug_f.write(f'DB items: {db_items[:10]}\n')
                 debug_f.write(f'Type methods keys: {list(type_methods.keys())[:5]}\n')
 
+            # Column names of pandas/polars DataFrames and DuckDB relations, for df["...
+            for name in globals_snapshot:
+                if name.startswith('_'):
+                    continue
+                obj = globals_snapshot[name]
+                obj_type = type(obj).__name__
+                obj_module = type(obj).__module__
+                try:
+                    if obj_type == 'DataFrame' and obj_module.startswith(('pandas', 'polars')):
+                        columns = obj.columns
+                    elif obj_type == 'LazyFrame' and obj_module.startswith('polars'):
+                        columns = obj.collect_schema().names() if hasattr(obj, 'collect_schema') else obj.columns
+                    elif obj_type == 'DuckDBPyRelation':
+                        columns = obj.columns
+                    else:
+                        continue
+                    frame_columns[name] = [str(column) for column in list(columns)[:1000]]
+                except Exception:
+                    pass
+
             # Harvest SQL metadata from DuckDB and Spark connections
             for name in globals_snapshot:
                 if name.startswith('_') or name.startswith('SAGE_'):
@@ -323,6 +607,7 @@ while True:
                                             col_name = col_row[0]
                                             # Add fully qualified name (table.column)
                                             full_name = f"{table_name}.{col_name}"
+                                            sql_column_types[full_name] = str(col_row[1])
                                             if full_name not in sql_columns:
                                                 sql_columns.append(full_name)
                                             # Also add unqualified name (just column)
@@ -378,6 +663,7 @@ while True:
                                             col_name = col_row[0]  # First column is column name
                                             # Add fully qualified name (table.column)
                                             full_name = f"{table_name}.{col_name}"
+                                            sql_column_types[full_name] = str(col_row[1])
                                             if full_name not in sql_columns:
                                                 sql_columns.append(full_name)
                                             # Also add unqualified name (just column)
@@ -425,6 +711,7 @@ while True:
                                     for col in columns:
                                         # Add fully qualified name (table.column)
                                         full_name = f"{table_name}.{col.name}"
+                                        sql_column_types[full_name] = str(col.dataType)
                                         if full_name not in sql_columns:
                                             sql_columns.append(full_name)
                                         # Also add unqualified name (just column)
@@ -446,6 +733,70 @@ while True:
                         except Exception as e:
                             with open('/tmp/sage_python_debug.txt', 'a') as debug_f:
                                 debug_f.write(f'Error harvesting Spark metadata: {str(e)}\n')
+
+                    # Check for SQLite connection
+                    elif obj_type == 'Connection' and type(obj).__module__ == 'sqlite3':
+                        try:
+                            tables_result = obj.execute("SELECT name FROM sqlite_master WHERE type IN ('table', 'view') AND name NOT LIKE 'sqlite_%'").fetchall()
+                            for (table_name,) in tables_result:
+                                # PRAGMA table_info rows: (cid, name, type, notnull, default, pk)
+                                columns_result = obj.execute(f'PRAGMA table_info("{table_name}")').fetchall()
+                                _sage_add_sql_table(table_name, [(col_row[1], col_row[2]) for col_row in columns_result])
+                        except Exception as e:
+                            with open('/tmp/sage_python_debug.txt', 'a') as debug_f:
+                                debug_f.write(f'Error harvesting SQLite metadata: {str(e)}\n')
+
+                    # Check for SQLAlchemy engine or connection
+                    elif obj_type in ('Engine', 'Connection') and type(obj).__module__.startswith('sqlalchemy'):
+                        try:
+                            import sqlalchemy
+                            inspector = sqlalchemy.inspect(obj)
+                            for table_name in inspector.get_table_names() + inspector.get_view_names():
+                                columns = inspector.get_columns(table_name)
+                                _sage_add_sql_table(table_name, [(col['name'], col['type']) for col in columns])
+                        except Exception as e:
+                            with open('/tmp/sage_python_debug.txt', 'a') as debug_f:
+                                debug_f.write(f'Error harvesting SQLAlchemy metadata: {str(e)}\n')
+
+                    # Check for psycopg (3) or psycopg2 connection
+                    elif obj_type in ('Connection', 'connection') and type(obj).__module__.startswith('psycopg') and not obj.closed:
+                        try:
+                            # Only query an idle connection, and end the transaction the query
+                            # starts, so the user's own transaction is never touched
+                            if hasattr(obj, 'info'):
+                                idle = obj.info.transaction_status == 0
+                            else:
+                                idle = obj.get_transaction_status() == 0
+                            if idle:
+                                try:
+                                    cursor = obj.cursor()
+                                    cursor.execute(
+                                        "SELECT table_name, column_name, data_type FROM information_schema.columns "
+                                        "WHERE table_schema NOT IN ('pg_catalog', 'information_schema') "
+                                        "ORDER BY table_name, ordinal_position"
+                                    )
+                                    columns_by_table = {}
+                                    for table_name, col_name, col_type in cursor.fetchall():
+                                        columns_by_table.setdefault(table_name, []).append((col_name, col_type))
+                                    cursor.close()
+                                    for table_name, columns in columns_by_table.items():
+                                        _sage_add_sql_table(table_name, columns)
+                                finally:
+                                    if not obj.autocommit:
+                                        obj.rollback()
+                        except Exception as e:
+                            with open('/tmp/sage_python_debug.txt', 'a') as debug_f:
+                                debug_f.write(f'Error harvesting Postgres metadata: {str(e)}\n')
+
+                    # Check for Polars SQL context
+                    elif obj_type == 'SQLContext' and type(obj).__module__.startswith('polars'):
+                        try:
+                            for table_name in obj.tables():
+                                schema = obj.execute(f'SELECT * FROM "{table_name}" LIMIT 0', eager=True).schema
+                                _sage_add_sql_table(table_name, list(schema.items()))
+                        except Exception as e:
+                            with open('/tmp/sage_python_debug.txt', 'a') as debug_f:
+                                debug_f.write(f'Error harvesting Polars SQL metadata: {str(e)}\n')
                 except:
                     pass
 
@@ -461,7 +812,8 @@ while True:
             print("SAGE_OUTPUT_START", flush=True)
             print(json.dumps({"type": "type_relationships", "data": {
                 "return_types": return_types,
-                "type_methods": type_methods
+                "type_methods": type_methods,
+                "frame_columns": frame_columns
             }}), flush=True)
             print("SAGE_OUTPUT_END", flush=True)
 
@@ -470,6 +822,7 @@ while True:
             print(json.dumps({"type": "sql_metadata", "data": {
                 "tables": sql_tables,
                 "columns": sql_columns,
+                "column_types": sql_column_types,
                 "functions": sql_functions
             }}), flush=True)
             print("SAGE_OUTPUT_END", flush=True)
@@ -540,6 +893,55 @@ while True:
     }
 }
 
+impl DirectKernel {
+    /// Next line the kernel printed, waiting at most `timeout` for it if one is given
+    fn read_line(&self, timeout: Option<Duration>) -> Result<String, Box<dyn Error>> {
+        let output = self.output.as_ref().ok_or("No stdout available")?;
+        let line = match timeout {
+            Some(timeout) => output.recv_timeout(timeout).map_err(|e| match e {
+                RecvTimeoutError::Timeout => "Kernel did not answer in time",
+                RecvTimeoutError::Disconnected => "Kernel closed its output",
+            })?,
+            None => output.recv().map_err(|_| "Kernel closed its output")?,
+        };
+        Ok(line)
+    }
+
+    /// Next JSON block the kernel printed between output markers. `deadline` only limits
+    /// the wait for a block to start: the rest of it is printed right away.
+    fn read_output(&self, deadline: Option<Instant>) -> Result<serde_json::Value, Box<dyn Error>> {
+        let timeout = || deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+        while self.read_line(timeout())?.trim() != "SAGE_OUTPUT_START" {}
+        let output = serde_json::from_str(self.read_line(None)?.trim())?;
+        // Output end marker
+        self.read_line(None)?;
+        Ok(output)
+    }
+
+    /// Send a request that the REPL answers without executing user code, returning its data.
+    /// Gives up after `timeout`; until the late answer arrives, further requests fail at once.
+    fn request(&mut self, request: serde_json::Value, timeout: Duration) -> Result<serde_json::Value, Box<dyn Error>> {
+        while self.unanswered_requests > 0 {
+            self.read_output(Some(Instant::now()))?;
+            self.unanswered_requests -= 1;
+        }
+
+        let stdin = self.stdin.as_mut().ok_or("No stdin available")?;
+        writeln!(stdin, "SAGE_REQUEST_START")?;
+        writeln!(stdin, "{}", request)?;
+        writeln!(stdin, "SAGE_REQUEST_END")?;
+        stdin.flush()?;
+
+        match self.read_output(Some(Instant::now() + timeout)) {
+            Ok(mut response) => Ok(response["data"].take()),
+            Err(e) => {
+                self.unanswered_requests += 1;
+                Err(e)
+            }
+        }
+    }
+}
+
 impl Kernel for DirectKernel {
     fn connect(&mut self) -> Result<(), Box<dyn Error>> {
         if self.is_connected() {
@@ -563,17 +965,23 @@ impl Kernel for DirectKernel {
         let stdin = child.stdin.take().ok_or("Failed to get stdin")?;
         let stdout = child.stdout.take().ok_or("Failed to get stdout")?;
 
-        // Wait for ready signal with timeout
-        let mut reader = BufReader::new(stdout);
-        let mut line = String::new();
+        // Read the output on a thread of its own, so requests can stop waiting for it
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                if tx.send(line).is_err() {
+                    break;
+                }
+            }
+        });
 
         // Try to read the ready signal
-        match reader.read_line(&mut line) {
-            Ok(0) => {
+        match rx.recv() {
+            Err(_) => {
                 // EOF - process probably died
                 return Err("Python process died immediately".into());
             }
-            Ok(_) => {
+            Ok(line) => {
                 if !line.trim().starts_with("SAGE_KERNEL_READY") {
                     // Got unexpected output
                     return Err(format!(
@@ -582,14 +990,12 @@ impl Kernel for DirectKernel {
                     ).into());
                 }
             }
-            Err(e) => {
-                return Err(format!("Failed to read from Python: {}", e).into());
-            }
         }
 
-        // Store process handle, stdin, and stdout reader
+        // Store process handle, stdin, and output lines
         self.stdin = Some(stdin);
-        self.stdout = Some(reader);
+        self.output = Some(rx);
+        self.unanswered_requests = 0;
         self.process = Some(child);
 
         Ok(())
@@ -603,7 +1009,6 @@ impl Kernel for DirectKernel {
         self.execution_count += 1;
 
         let stdin = self.stdin.as_mut().ok_or("No stdin available")?;
-        let reader = self.stdout.as_mut().ok_or("No stdout available")?;
 
         // Send execution delimiters and code
         writeln!(stdin, "SAGE_EXEC_START")?;
@@ -620,24 +1025,15 @@ impl Kernel for DirectKernel {
         let mut sql_metadata = crate::kernel::SqlMetadata::default();
         let mut success = false;
         let mut finished = false;
-        let mut line = String::new();
 
         while !finished {
-            // Wait for output start marker
-            loop {
-                line.clear();
-                reader.read_line(&mut line)?;
-                if line.trim() == "SAGE_OUTPUT_START" {
-                    break;
-                }
+            let output_data = self.read_output(None)?;
+            if output_data.get("request").is_some() {
+                // The late answer to a request that was given up on
+                self.unanswered_requests = self.unanswered_requests.saturating_sub(1);
+                continue;
             }
 
-            // Read JSON output
-            line.clear();
-            reader.read_line(&mut line)?;
-
-            let output_data: serde_json::Value = serde_json::from_str(line.trim())?;
-
             match output_data["type"].as_str() {
                 Some("stdout") => {
                     if let Some(data) = output_data["data"].as_str() {
@@ -708,10 +1104,6 @@ impl Kernel for DirectKernel {
                     finished = true;
                 }
             }
-
-            // Wait for output end marker
-            line.clear();
-            reader.read_line(&mut line)?;
         }
 
         Ok(ExecutionResult {
@@ -727,7 +1119,7 @@ impl Kernel for DirectKernel {
     fn disconnect(&mut self) -> Result<(), Box<dyn Error>> {
         // Drop stdin first to send EOF to the Python process
         self.stdin = None;
-        self.stdout = None;
+        self.output = None;
 
         if let Some(mut process) = self.process.take() {
             // Try a quick check if it exited
@@ -749,6 +1141,36 @@ impl Kernel for DirectKernel {
     fn info(&self) -> KernelInfo {
         self.info.clone()
     }
+
+    fn inspect(&mut self, name: &str) -> Result<Option<Documentation>, Box<dyn Error>> {
+        let data = self.request(serde_json::json!({ "kind": "inspect", "name": name }), REQUEST_TIMEOUT)?;
+        Ok(serde_json::from_value(data).ok())
+    }
+
+    fn signature(&mut self, name: &str, base: Option<&str>) -> Result<Option<Signature>, Box<dyn Error>> {
+        let data = self.request(serde_json::json!({ "kind": "signature", "name": name, "base": base }), REQUEST_TIMEOUT)?;
+        Ok(serde_json::from_value(data).ok())
+    }
+
+    fn complete(&mut self, code: &str, line: usize, column: usize) -> Result<Vec<CompletionItem>, Box<dyn Error>> {
+        let data = self.request(serde_json::json!({ "kind": "complete", "code": code, "line": line, "column": column }), REQUEST_TIMEOUT)?;
+        Ok(serde_json::from_value(data).unwrap_or_default())
+    }
+
+    fn execute_sql(&mut self, sql: &str, connection: Option<&str>, variable: Option<&str>) -> Result<ExecutionResult, Box<dyn Error>> {
+        let literal = |value: Option<&str>| value.map_or("None".to_string(), |v| serde_json::Value::from(v).to_string());
+        self.execute(&format!("_sage_sql_cell({}, {}, {})", literal(Some(sql)), literal(connection), literal(variable)))
+    }
+
+    fn modules(&mut self, package: Option<&str>) -> Result<Vec<CompletionItem>, Box<dyn Error>> {
+        let data = self.request(serde_json::json!({ "kind": "modules", "package": package }), MODULES_TIMEOUT)?;
+        Ok(serde_json::from_value(data).unwrap_or_default())
+    }
+
+    fn working_directory(&mut self) -> Result<Option<std::path::PathBuf>, Box<dyn Error>> {
+        let data = self.request(serde_json::json!({ "kind": "cwd" }), REQUEST_TIMEOUT)?;
+        Ok(data.as_str().map(std::path::PathBuf::from))
+    }
 }
 
 impl Drop for DirectKernel {
@@ -756,3 +1178,146 @@ impl Drop for DirectKernel {
         let _ = self.disconnect();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A kernel on the system Python, or None where there is none
+    fn python_kernel() -> Option<DirectKernel> {
+        let mut kernel = DirectKernel::new("python3".to_string(), "python3".to_string(), "Python 3".to_string());
+        kernel.connect().ok()?;
+        Some(kernel)
+    }
+
+    #[test]
+    fn test_sqlite_metadata() {
+        let Some(mut kernel) = python_kernel() else { return };
+        let code = "import sqlite3\ncon = sqlite3.connect(':memory:')\ncon.execute('CREATE TABLE users (id INTEGER, name TEXT)')";
+        let metadata = kernel.execute(code).unwrap().sql_metadata;
+        assert_eq!(metadata.tables, vec!["users".to_string()]);
+        assert!(metadata.columns.contains(&"users.name".to_string()));
+        assert_eq!(metadata.column_types.get("users.name").map(String::as_str), Some("TEXT"));
+    }
+
+    #[test]
+    fn test_duckdb_metadata() {
+        let Some(mut kernel) = python_kernel() else { return };
+        if !kernel.execute("import duckdb").unwrap().success {
+            return; // DuckDB isn't installed
+        }
+        let code = "db = duckdb.connect(':memory:')\ndb.execute('CREATE TABLE orders (id INTEGER, total DOUBLE)')";
+        let metadata = kernel.execute(code).unwrap().sql_metadata;
+        assert!(metadata.tables.contains(&"orders".to_string()));
+        assert_eq!(metadata.column_types.get("orders.total").map(String::as_str), Some("DOUBLE"));
+    }
+
+    #[test]
+    fn test_sql_cell() {
+        let Some(mut kernel) = python_kernel() else { return };
+        let code = "import sqlite3\ncon = sqlite3.connect(':memory:')\ncon.execute('CREATE TABLE t (id INTEGER)')\ncon.execute('INSERT INTO t VALUES (1), (2)')";
+        assert!(kernel.execute(code).unwrap().success);
+        let result = kernel.execute_sql("SELECT count(*) AS n\nFROM t", None, Some("counts")).unwrap();
+        assert!(result.success);
+        assert!(matches!(&result.outputs[..], [ExecutionOutput::Result(table)] if table.contains('2')));
+        assert!(kernel.execute("assert len(counts) == 1").unwrap().success);
+        assert!(!kernel.execute_sql("SELECT 1", Some("missing"), None).unwrap().success);
+    }
+
+    #[test]
+    fn test_inspect_and_signature() {
+        let Some(mut kernel) = python_kernel() else { return };
+        let code = "class Point:\n    '''A point in the plane.'''\n    def __init__(self, x, y=0):\n        self.x = x\n    @property\n    def norm(self):\n        '''Distance from the origin.'''\n        raise RuntimeError('evaluated')\n    def moved(self, dx, *, dy=0):\n        return Point(self.x + dx)\np = Point(1)";
+        assert!(kernel.execute(code).unwrap().success);
+
+        let doc = kernel.inspect("Point").unwrap().unwrap();
+        assert_eq!((doc.kind.as_str(), doc.signature.as_deref(), doc.doc.as_str()), ("class", Some("(x, y=0)"), "A point in the plane."));
+        let doc = kernel.inspect("p.moved").unwrap().unwrap();
+        assert_eq!((doc.kind.as_str(), doc.signature.as_deref()), ("function", Some("(dx, *, dy=0)")));
+        // Properties are described without running their getter
+        let doc = kernel.inspect("p.norm").unwrap().unwrap();
+        assert_eq!((doc.kind.as_str(), doc.doc.as_str()), ("property", "Distance from the origin."));
+        assert!(kernel.inspect("p.missing").unwrap().is_none());
+
+        let signature = kernel.signature("p.moved", None).unwrap().unwrap();
+        assert_eq!(signature.label, "moved");
+        let parameters: Vec<(&str, &str)> = signature.parameters.iter().map(|p| (p.text.as_str(), p.kind.as_str())).collect();
+        assert_eq!(parameters, vec![("dx", "positional_or_keyword"), ("dy=0", "keyword_only")]);
+        // A method of what a class constructs leaves out `self` too
+        let signature = kernel.signature("moved", Some("Point")).unwrap().unwrap();
+        assert_eq!(signature.parameters.iter().map(|p| p.name.as_str()).collect::<Vec<_>>(), vec!["dx", "dy"]);
+        assert!(kernel.signature("norm", Some("Point")).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_complete() {
+        let Some(mut kernel) = python_kernel() else { return };
+        if !kernel.execute("import jedi").unwrap().success {
+            return; // Jedi isn't installed
+        }
+        let code = "import json\nclass Point:\n    def moved(self, dx):\n        pass\np = Point()";
+        assert!(kernel.execute(code).unwrap().success);
+        let names = |items: Vec<CompletionItem>| items.into_iter().map(|c| (c.name, c.item_type)).collect::<Vec<_>>();
+
+        // Live objects and code that hasn't run yet
+        let completions = names(kernel.complete("p.mo", 0, 4).unwrap());
+        assert_eq!(completions, vec![("moved".to_string(), "function".to_string())]);
+        let completions = names(kernel.complete("def helper():\n    pass\nhel", 2, 3).unwrap());
+        assert!(completions.contains(&("helper".to_string(), "function".to_string())));
+        let completions = names(kernel.complete("json.du", 0, 7).unwrap());
+        assert!(completions.contains(&("dumps".to_string(), "function".to_string())));
+
+        // Keywords, builtins and sage's own helpers are left to the editor
+        let completions = names(kernel.complete("pr", 0, 2).unwrap());
+        assert!(completions.iter().all(|(name, _)| name != "print"));
+        let completions = names(kernel.complete("_sa", 0, 3).unwrap());
+        assert!(completions.iter().all(|(name, _)| !name.starts_with("_sage")));
+    }
+
+    #[test]
+    fn test_complete_without_jedi() {
+        let Some(mut kernel) = python_kernel() else { return };
+        assert!(kernel.execute("import sys\nsys.modules['jedi'] = None").unwrap().success);
+        assert!(kernel.complete("sys.pa", 0, 6).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_request_timeout() {
+        let Some(mut kernel) = python_kernel() else { return };
+        // Looking up `slow.x` takes longer than the editor waits
+        let code = "import sys, time, types\nslow = types.ModuleType('slow')\nslow.__getattr__ = lambda name: time.sleep(1.5)\nsys.modules['slow'] = slow";
+        assert!(kernel.execute(code).unwrap().success);
+
+        let start = Instant::now();
+        assert!(kernel.inspect("slow.x").is_err());
+        assert!(start.elapsed() < REQUEST_TIMEOUT + Duration::from_millis(300));
+        // Other requests don't queue up behind the slow one
+        assert!(kernel.inspect("len").is_err());
+        std::thread::sleep(Duration::from_millis(800));
+        assert_eq!(kernel.inspect("len").unwrap().unwrap().kind, "function");
+
+        // Code runs once the late answer is out of the way
+        assert!(kernel.inspect("slow.x").is_err());
+        let result = kernel.execute("1 + 1").unwrap();
+        assert!(matches!(&result.outputs[..], [ExecutionOutput::Result(value)] if value == "2"));
+        assert_eq!(kernel.inspect("len").unwrap().unwrap().kind, "function");
+    }
+
+    #[test]
+    fn test_modules() {
+        // Listed without running any code first
+        let Some(mut kernel) = python_kernel() else { return };
+        let modules = kernel.modules(None).unwrap();
+        assert!(modules.iter().any(|m| m.name == "json" && m.item_type == "module"));
+        let names = kernel.modules(Some("json")).unwrap();
+        assert!(names.iter().any(|m| m.name == "decoder" && m.item_type == "module"));
+        assert!(names.iter().any(|m| m.name == "dumps" && m.item_type == "function"));
+
+        // Packages the session hasn't imported are listed without importing them into it
+        let names = kernel.modules(Some("email")).unwrap();
+        assert!(names.iter().any(|m| m.name == "message" && m.item_type == "module"));
+        assert!(names.iter().any(|m| m.name == "message_from_string" && m.item_type == "function"));
+        assert!(kernel.execute("import sys\nassert 'email' not in sys.modules").unwrap().success);
+        assert!(kernel.modules(Some("no_such_package")).unwrap().is_empty());
+    }
+}
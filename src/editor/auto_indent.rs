@@ -0,0 +1,249 @@
+use crate::cell::CELL_DELIMITER;
+use crate::syntax::Language;
+use super::code_scan::{strip_comment, CodeScanner};
+use super::folding::indent_width;
+use super::Editor;
+
+/// Width of one indentation level
+const INDENT: usize = 4;
+
+/// How far back to look for open brackets and strings when there is no cell delimiter
+const MAX_SCAN_LINES: usize = 500;
+
+/// Statements after which the next line leaves the block
+const BLOCK_ENDERS: &[&str] = &["return", "pass", "break", "continue", "raise"];
+
+/// Brackets and strings still open at the end of a piece of Python
+#[derive(Debug, Default, PartialEq)]
+pub(super) struct CodeContext {
+    /// Byte positions and characters of the unclosed brackets, innermost last
+    pub open_brackets: Vec<(usize, char)>,
+    /// Byte positions of the commas directly inside the open brackets
+    pub commas: Vec<usize>,
+    /// Quote of the unterminated string (three characters for a triple-quoted one)
+    pub string_quote: Option<String>,
+    pub in_comment: bool,
+}
+
+impl Editor {
+    /// Where to start scanning for the code structure around a line: its cell's delimiter,
+    /// or a bounded number of lines back
+    pub(super) fn scan_start_line(&self, line: usize) -> usize {
+        let limit = line.saturating_sub(MAX_SCAN_LINES);
+        (limit..=line).rev()
+            .find(|&l| self.buffer.line(l).trim_start().starts_with(CELL_DELIMITER))
+            .unwrap_or(limit)
+    }
+
+    /// Where to stop scanning forward from a line: the end of its cell, or a bounded
+    /// number of lines on
+    pub(super) fn scan_end_line(&self, line: usize) -> usize {
+        let limit = (line + MAX_SCAN_LINES).min(self.buffer.len_lines().saturating_sub(1));
+        (line + 1..=limit)
+            .find(|&l| self.buffer.line(l).trim_start().starts_with(CELL_DELIMITER))
+            .map_or(limit, |l| l - 1)
+    }
+
+    /// Brackets and strings open at a byte position
+    pub(super) fn code_context_at(&self, pos: usize) -> CodeContext {
+        let start = self.buffer.line_to_byte(self.scan_start_line(self.buffer.byte_to_line(pos)));
+        let mut context = scan_code(&self.buffer.rope().byte_slice(start..pos).to_string());
+        for (offset, _) in &mut context.open_brackets {
+            *offset += start;
+        }
+        for offset in &mut context.commas {
+            *offset += start;
+        }
+        context
+    }
+
+    /// Text that Enter inserts at the cursor and where in it the cursor ends up.
+    /// Python gets an extra level after `:`, one less after `return`/`pass`/...,
+    /// and continuation lines inside brackets line up with the bracket.
+    pub(super) fn newline_text(&self) -> (String, usize) {
+        let line = self.buffer.byte_to_line(self.cursor);
+        let line_start = self.buffer.line_to_byte(line);
+        if self.cursor == line_start {
+            return ("\n".to_string(), 1);
+        }
+        let line_text = self.buffer.line(line);
+        let line_indent = line_text.chars().take_while(|&c| c == ' ').count();
+        let plain = |indent: usize| (format!("\n{}", " ".repeat(indent)), indent + 1);
+
+        if self.syntax.language() != Language::Python {
+            return plain(line_indent);
+        }
+
+        let context = self.code_context_at(self.cursor);
+        if context.string_quote.is_some() {
+            return plain(line_indent);
+        }
+
+        if let Some(&(open_pos, open)) = context.open_brackets.last() {
+            let open_line = self.buffer.byte_to_line(open_pos);
+            let open_line_text = self.buffer.line(open_line);
+            let after_end = if open_line == line {
+                self.cursor
+            } else {
+                self.buffer.line_to_byte(open_line) + open_line_text.trim_end_matches('\n').len()
+            };
+            let after_open = self.buffer.rope().byte_slice(open_pos + 1..after_end).to_string();
+
+            if strip_comment(&after_open).trim().is_empty() {
+                // Nothing after the bracket: hanging indent, with the closing bracket
+                // moved to its own line when the cursor is right before it
+                let base = indent_width(&open_line_text);
+                let hang = base + INDENT;
+                let rest = &line_text[(self.cursor - line_start).min(line_text.len())..];
+                if open_line == line && rest.trim_start().starts_with(closing_bracket(open)) {
+                    return (format!("\n{}\n{}", " ".repeat(hang), " ".repeat(base)), hang + 1);
+                }
+                return plain(hang);
+            }
+
+            // Line up with the first item after the bracket
+            let column = self.byte_position_to_display(open_pos).1 + 1;
+            return plain(column);
+        }
+
+        // A statement spread over several lines is indented from its first line
+        let statement_line = self.code_context_at(line_start).open_brackets.first()
+            .map_or(line, |&(pos, _)| self.buffer.byte_to_line(pos));
+        let statement_indent = indent_width(&self.buffer.line(statement_line));
+        let before = &line_text[..(self.cursor - line_start).min(line_text.len())];
+        let statement = if statement_line == line { before.to_string() } else { self.buffer.line(statement_line) };
+        plain(python_indent_after(before, &statement, statement_indent))
+    }
+
+    /// After the colon of `else:`, `elif ...:`, `except ...:` or `finally:` is typed,
+    /// move the line back to the indentation of the block it continues
+    pub(super) fn auto_dedent_line(&mut self) {
+        if self.syntax.language() != Language::Python {
+            return;
+        }
+        let line = self.buffer.byte_to_line(self.cursor);
+        let line_start = self.buffer.line_to_byte(line);
+        let text = self.buffer.line(line);
+        if !text[(self.cursor - line_start).min(text.len())..].trim().is_empty() {
+            return;
+        }
+
+        let Some(openers) = block_openers(first_word(&text)) else { return };
+        let indent = indent_width(&text);
+        let Some(target) = self.continued_block_indent(line, indent, openers) else { return };
+        if target >= indent || text.len() < indent || !text[..indent].bytes().all(|b| b == b' ') {
+            return;
+        }
+
+        let remove = indent - target;
+        let cursor_before = self.cursor;
+        self.buffer.delete(line_start, line_start + remove, cursor_before, cursor_before - remove);
+        self.cursor -= remove;
+        self.syntax.line_modified(line);
+    }
+
+    /// Indentation of the nearest block header above `line` that one of `openers` starts
+    fn continued_block_indent(&self, line: usize, indent: usize, openers: &[&str]) -> Option<usize> {
+        for l in (line.saturating_sub(MAX_SCAN_LINES)..line).rev() {
+            let text = self.buffer.line(l);
+            let code = strip_comment(&text);
+            if code.trim().is_empty() {
+                continue;
+            }
+            let l_indent = indent_width(&text);
+            if l_indent > indent {
+                continue;
+            }
+            if openers.contains(&first_word(&text)) && code.trim_end().ends_with(':') {
+                return Some(l_indent);
+            }
+            if l_indent < indent {
+                return None;
+            }
+        }
+        None
+    }
+}
+
+/// Scan Python for brackets and strings left open at its end
+pub(super) fn scan_code(text: &str) -> CodeContext {
+    let mut context = CodeContext::default();
+    let mut scanner = CodeScanner::default();
+    scanner.scan(text, |i, b| match b {
+        b'(' | b'[' | b'{' => context.open_brackets.push((i, b as char)),
+        b')' | b']' | b'}' if context.open_brackets.last().is_some_and(|&(_, open)| closing_bracket(open) == b as char) => {
+            if let Some((open, _)) = context.open_brackets.pop() {
+                context.commas.retain(|&comma| comma < open);
+            }
+        }
+        b',' if !context.open_brackets.is_empty() => context.commas.push(i),
+        _ => {}
+    });
+    context.string_quote = scanner.string_quote;
+    context.in_comment = scanner.in_comment;
+    context
+}
+
+pub(super) fn closing_bracket(open: char) -> char {
+    match open {
+        '(' => ')',
+        '[' => ']',
+        _ => '}',
+    }
+}
+
+/// First word of a line of code (`else` for `    else:`)
+fn first_word(line: &str) -> &str {
+    line.trim_start()
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .next()
+        .unwrap_or("")
+}
+
+/// Headers that a continuation keyword can follow
+fn block_openers(keyword: &str) -> Option<&'static [&'static str]> {
+    match keyword {
+        "else" => Some(&["if", "elif", "for", "while", "try", "except"]),
+        "elif" => Some(&["if", "elif"]),
+        "except" => Some(&["try", "except"]),
+        "finally" => Some(&["try", "except", "else"]),
+        _ => None,
+    }
+}
+
+/// Indentation after a line of Python ending in `before`, given the first line of its
+/// statement and that line's indentation
+fn python_indent_after(before: &str, statement: &str, indent: usize) -> usize {
+    if strip_comment(before).trim_end().ends_with(':') {
+        indent + INDENT
+    } else if BLOCK_ENDERS.contains(&first_word(statement)) {
+        indent.saturating_sub(INDENT)
+    } else {
+        indent
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan_code() {
+        let context = scan_code("x = foo(a, [1, 2], ");
+        assert_eq!(context.open_brackets, vec![(7, '(')]);
+        assert_eq!(context.commas, vec![9, 17]);
+        // Brackets in strings and comments don't count
+        let context = scan_code("s = '(' # [\nt = \"\"\"doc (");
+        assert!(context.open_brackets.is_empty());
+        assert_eq!(context.string_quote.as_deref(), Some("\"\"\""));
+    }
+
+    #[test]
+    fn test_python_indent_after() {
+        assert_eq!(python_indent_after("    if x:  # check", "    if x:  # check", 4), 8);
+        assert_eq!(python_indent_after("        return x", "        return x", 8), 4);
+        assert_eq!(python_indent_after("    y = 1", "    y = 1", 4), 4);
+        // The keyword of a multi-line statement is on its first line
+        assert_eq!(python_indent_after("        b)", "    return f(a,", 4), 0);
+    }
+}
@@ -0,0 +1,148 @@
+use crate::syntax::Language;
+use super::auto_indent::closing_bracket;
+use super::Editor;
+
+/// Prefixes that may come right before the opening quote of a Python string
+const STRING_PREFIXES: &[&str] = &["f", "r", "b", "u", "rb", "br", "fr", "rf"];
+
+impl Editor {
+    /// Turn automatic closing of brackets and quotes on or off
+    pub fn toggle_auto_pair(&mut self) {
+        self.auto_pair = !self.auto_pair;
+        self.status_message = Some((
+            if self.auto_pair { "Auto-pairing on" } else { "Auto-pairing off" }.to_string(),
+            false
+        ));
+    }
+
+    fn auto_pair_active(&self) -> bool {
+        self.auto_pair && !matches!(self.syntax.language(), Language::PlainText | Language::Markdown)
+    }
+
+    fn char_before_cursor(&self) -> Option<char> {
+        let char_pos = self.buffer.byte_to_char(self.cursor);
+        (char_pos > 0).then(|| self.buffer.rope().char(char_pos - 1))
+    }
+
+    /// Character after the cursor on the same line
+    pub fn char_after_cursor(&self) -> Option<char> {
+        let char_pos = self.buffer.byte_to_char(self.cursor);
+        (char_pos < self.buffer.rope().len_chars())
+            .then(|| self.buffer.rope().char(char_pos))
+            .filter(|&c| c != '\n')
+    }
+
+    /// Handle a typed bracket or quote: wrap the selection in it, type over the
+    /// closing character after the cursor, or insert both halves of the pair.
+    /// Returns false when the character should just be inserted.
+    pub(super) fn insert_paired(&mut self, c: char) -> bool {
+        if !self.auto_pair_active() {
+            return false;
+        }
+        let is_quote = c == '"' || c == '\'';
+        let close = match c {
+            '(' | '[' | '{' => closing_bracket(c),
+            '"' | '\'' => c,
+            ')' | ']' | '}' => return self.type_over(c),
+            _ => return false,
+        };
+
+        if let Some((start, end)) = self.get_selection() {
+            let cursor_before = self.cursor;
+            self.buffer.insert(end, &close.to_string(), cursor_before, cursor_before);
+            self.buffer.insert(start, &c.to_string(), cursor_before, end + 1);
+            self.selection_start = Some(start + 1);
+            self.cursor = end + 1;
+            self.modified = true;
+            self.syntax.mark_range_dirty(self.buffer.byte_to_line(start), self.buffer.byte_to_line(end + 2));
+            return true;
+        }
+
+        let context = self.code_context_at(self.cursor);
+        if context.in_comment {
+            return false;
+        }
+        let next = self.char_after_cursor();
+        let next_allows_pair = next.is_none_or(|n| n.is_whitespace() || ")]},:;".contains(n));
+
+        if !is_quote {
+            if context.string_quote.is_some() || !next_allows_pair {
+                return false;
+            }
+            self.insert_pair_text(&format!("{}{}", c, close), 1);
+            return true;
+        }
+
+        if let Some(quote) = context.string_quote {
+            // Inside a string only its closing quote is typed over
+            return quote.starts_with(c) && self.type_over(c);
+        }
+        if next == Some(c) {
+            return self.type_over(c);
+        }
+
+        // The third quote of `"""` opens a triple-quoted string
+        let line_start = self.buffer.line_to_byte(self.buffer.byte_to_line(self.cursor));
+        let before = self.buffer.rope().byte_slice(line_start..self.cursor).to_string();
+        let doubled = format!("{}{}", c, c);
+        if before.ends_with(&doubled) && !before.ends_with(&format!("{}{}", doubled, c)) {
+            self.insert_pair_text(&doubled.repeat(2), 1);
+            return true;
+        }
+
+        // Apostrophes inside words stay single, but string prefixes like f"..." pair
+        let word_before: String = before.chars().rev().take_while(|ch| ch.is_alphanumeric() || *ch == '_').collect();
+        let word_before: String = word_before.chars().rev().collect();
+        if !word_before.is_empty() && !STRING_PREFIXES.contains(&word_before.to_lowercase().as_str()) {
+            return false;
+        }
+        if !next_allows_pair {
+            return false;
+        }
+        self.insert_pair_text(&doubled, 1);
+        true
+    }
+
+    /// Step over a closing bracket or quote that is already after the cursor
+    fn type_over(&mut self, c: char) -> bool {
+        if self.get_selection().is_some() || self.char_after_cursor() != Some(c) {
+            return false;
+        }
+        self.cursor += c.len_utf8();
+        true
+    }
+
+    fn insert_pair_text(&mut self, text: &str, cursor_offset: usize) {
+        let cursor_before = self.cursor;
+        self.buffer.insert(self.cursor, text, cursor_before, self.cursor + cursor_offset);
+        self.cursor += cursor_offset;
+        self.modified = true;
+        self.syntax.line_modified(self.buffer.byte_to_line(self.cursor));
+    }
+
+    /// Backspace between the halves of an empty pair deletes both
+    pub(super) fn delete_empty_pair(&mut self) -> bool {
+        if !self.auto_pair_active() || self.get_selection().is_some() {
+            return false;
+        }
+        let (Some(before), Some(after)) = (self.char_before_cursor(), self.char_after_cursor()) else {
+            return false;
+        };
+        let pairs = match before {
+            '(' | '[' | '{' => closing_bracket(before) == after,
+            '"' | '\'' => before == after,
+            _ => false,
+        };
+        if !pairs {
+            return false;
+        }
+
+        let cursor_before = self.cursor;
+        let start = self.cursor - before.len_utf8();
+        self.buffer.delete(start, self.cursor + after.len_utf8(), cursor_before, start);
+        self.cursor = start;
+        self.modified = true;
+        self.syntax.line_modified(self.buffer.byte_to_line(start));
+        true
+    }
+}
@@ -0,0 +1,223 @@
+use crate::cell::CELL_DELIMITER;
+use super::Editor;
+
+impl Editor {
+    /// Index of the cell under the cursor, with cells re-parsed if needed
+    fn current_cell_index(&mut self) -> Option<usize> {
+        self.refresh_cells();
+        let (line, _) = self.cursor_position();
+        self.cell_index_at_line(line)
+    }
+
+    /// Text of a cell ending with a newline, with a delimiter line added in front if
+    /// `with_header` is set and the cell has none (only the first cell can lack one).
+    /// Also returns the number of bytes added in front.
+    fn cell_block(&self, idx: usize, with_header: bool) -> (String, usize) {
+        let cell = &self.cells[idx];
+        let end = cell.end.min(self.buffer.len_bytes());
+        let mut text = self.buffer.rope().byte_slice(cell.start.min(end)..end).to_string();
+
+        let has_header = text.lines().next().is_some_and(|l| l.trim_start().starts_with(CELL_DELIMITER));
+        let added = if with_header && !has_header {
+            text.insert_str(0, &format!("{}\n", CELL_DELIMITER));
+            CELL_DELIMITER.len() + 1
+        } else {
+            0
+        };
+        if !text.ends_with('\n') {
+            text.push('\n');
+        }
+        (text, added)
+    }
+
+    /// Replace a range covering whole cells as one undo step and move the cursor
+    fn replace_cells(&mut self, start: usize, end: usize, mut text: String, cursor_after: usize, message: &str) {
+        // Don't add a trailing newline the file didn't have
+        let len = self.buffer.len_bytes();
+        let ends_with_newline = len > 0 && self.buffer.rope().byte(len - 1) == b'\n';
+        if end == len && !ends_with_newline && text.ends_with('\n') {
+            text.pop();
+        }
+
+        let cursor_after = cursor_after.min(start + text.len());
+        self.buffer.replace(start, end, &text, self.cursor, cursor_after);
+        self.cursor = self.ensure_char_boundary(cursor_after);
+        self.selection_start = None;
+        self.preferred_column = None;
+        self.modified = true;
+        self.reinit_syntax_highlighting();
+        self.refresh_cells();
+        self.status_message = Some((message.to_string(), false));
+    }
+
+    /// Add an empty cell above or below the current one and put the cursor in it
+    pub fn insert_cell(&mut self, below: bool) {
+        let Some(idx) = self.current_cell_index() else { return };
+        let new_cell = format!("{}\n\n", CELL_DELIMITER);
+        let (start, end) = (self.cells[idx].start, self.cells[idx].end);
+
+        if below {
+            let (block, _) = self.cell_block(idx, false);
+            let cursor = start + block.len() + CELL_DELIMITER.len() + 1;
+            self.replace_cells(start, end, block + &new_cell, cursor, "Inserted cell below");
+        } else {
+            let (block, _) = self.cell_block(idx, true);
+            self.replace_cells(start, end, new_cell + &block, start + CELL_DELIMITER.len() + 1, "Inserted cell above");
+        }
+    }
+
+    /// Split the current cell at the cursor line
+    pub fn split_cell(&mut self) {
+        if self.current_cell_index().is_none() {
+            return;
+        }
+        let line = self.buffer.byte_to_line(self.cursor);
+        if self.buffer.line(line).trim_start().starts_with(CELL_DELIMITER) {
+            self.status_message = Some(("Move the cursor below the cell header to split".to_string(), true));
+            return;
+        }
+
+        let line_start = self.buffer.line_to_byte(line);
+        let header = format!("{}\n", CELL_DELIMITER);
+        let cursor = self.cursor + header.len();
+        self.replace_cells(line_start, line_start, header, cursor, "Split cell");
+    }
+
+    /// Join the next cell onto the current one by removing its delimiter line
+    pub fn merge_cell_below(&mut self) {
+        let Some(idx) = self.current_cell_index() else { return };
+        let Some(next) = self.cells.get(idx + 1) else {
+            self.status_message = Some(("No cell below to merge".to_string(), true));
+            return;
+        };
+
+        let header_start = next.start;
+        let header_end = self.buffer.line_to_byte(self.buffer.byte_to_line(header_start) + 1);
+        // The cursor is above the removed line, so it keeps its position
+        self.replace_cells(header_start, header_end, String::new(), self.cursor, "Merged cells");
+    }
+
+    /// Swap the current cell with the one above or below it
+    pub fn move_cell(&mut self, up: bool) {
+        let Some(idx) = self.current_cell_index() else { return };
+        let other = if up { idx.checked_sub(1) } else { Some(idx + 1).filter(|&i| i < self.cells.len()) };
+        let Some(other) = other else {
+            let message = if up { "Already the first cell" } else { "Already the last cell" };
+            self.status_message = Some((message.to_string(), true));
+            return;
+        };
+
+        let (upper, lower) = (idx.min(other), idx.max(other));
+        let (upper_block, upper_added) = self.cell_block(upper, true);
+        let (lower_block, lower_added) = self.cell_block(lower, true);
+        let (start, end) = (self.cells[upper].start, self.cells[lower].end);
+        let offset_in_cell = self.cursor - self.cells[idx].start;
+
+        // Keep the cursor at the same spot inside the moved cell
+        let cursor = if up {
+            start + lower_added + offset_in_cell
+        } else {
+            start + lower_block.len() + upper_added + offset_in_cell
+        };
+        let message = if up { "Moved cell up" } else { "Moved cell down" };
+        self.replace_cells(start, end, lower_block + &upper_block, cursor, message);
+    }
+
+    /// Insert a copy of the current cell below it and move the cursor into the copy
+    pub fn duplicate_cell(&mut self) {
+        let Some(idx) = self.current_cell_index() else { return };
+        let (original, _) = self.cell_block(idx, false);
+        let (copy, added) = self.cell_block(idx, true);
+        let (start, end) = (self.cells[idx].start, self.cells[idx].end);
+        let cursor = self.cursor + original.len() + added;
+        self.replace_cells(start, end, original + &copy, cursor, "Duplicated cell");
+    }
+
+    /// Remove the current cell, delimiter line included
+    pub fn delete_cell(&mut self) {
+        let Some(idx) = self.current_cell_index() else { return };
+        let (start, end) = (self.cells[idx].start, self.cells[idx].end);
+        self.replace_cells(start, end, String::new(), start, "Deleted cell (Ctrl+Z to undo)");
+
+        // Deleting the last cell leaves the cursor in the one above
+        if self.cursor >= self.buffer.len_bytes() && idx > 0 {
+            self.cursor = self.cells[idx - 1].start;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::Command;
+
+    /// An editor holding `text` with the cursor at the `|` in it
+    fn editor_with(text: &str) -> Editor {
+        let cursor = text.find('|').unwrap();
+        let mut editor = Editor::new();
+        editor.paste_text(text.replacen('|', "", 1));
+        editor.move_cursor_to(cursor);
+        editor
+    }
+
+    /// The editor's text with a `|` at the cursor
+    fn text_with_cursor(editor: &Editor) -> String {
+        let mut text = editor.buffer.to_string();
+        text.insert(editor.cursor, '|');
+        text
+    }
+
+    /// Apply `edit` to `before`, check the result, and check that one undo restores `before`
+    fn check(before: &str, edit: impl FnOnce(&mut Editor), after: &str) {
+        let mut editor = editor_with(before);
+        edit(&mut editor);
+        assert_eq!(text_with_cursor(&editor), after);
+        editor.execute(Command::Undo).unwrap();
+        assert_eq!(editor.buffer.to_string(), before.replacen('|', "", 1));
+    }
+
+    #[test]
+    fn test_split_and_merge() {
+        check("##$$\na = 1\n|b = 2\n", Editor::split_cell, "##$$\na = 1\n##$$\n|b = 2\n");
+        check("a = 1\n|b = 2", Editor::split_cell, "a = 1\n##$$\n|b = 2");
+        check("##$$ A\na = |1\n##$$ B\nb = 2\n", Editor::merge_cell_below, "##$$ A\na = |1\nb = 2\n");
+
+        // Nothing to do on a header or in the last cell
+        let mut editor = editor_with("##|$$\na = 1\n");
+        editor.split_cell();
+        assert_eq!(text_with_cursor(&editor), "##|$$\na = 1\n");
+        editor.merge_cell_below();
+        assert_eq!(text_with_cursor(&editor), "##|$$\na = 1\n");
+    }
+
+    #[test]
+    fn test_move_cell() {
+        let text = "##$$ A\na = 1\n##$$ B\nb = |2\n##$$ C\nc = 3";
+        check(text, |e| e.move_cell(true), "##$$ B\nb = |2\n##$$ A\na = 1\n##$$ C\nc = 3");
+        // The last cell of a file without a final newline stays without one
+        check(text, |e| e.move_cell(false), "##$$ A\na = 1\n##$$ C\nc = 3\n##$$ B\nb = |2");
+        // A first cell without a header gets one when it moves
+        check("import os\n##$$\nx = |1\n", |e| e.move_cell(true), "##$$\nx = |1\n##$$\nimport os\n");
+        check("import |os\n##$$\nx = 1\n", |e| e.move_cell(false), "##$$\nx = 1\n##$$\nimport |os\n");
+
+        let mut editor = editor_with("##$$ A\n|a = 1\n");
+        editor.move_cell(true);
+        editor.move_cell(false);
+        assert_eq!(text_with_cursor(&editor), "##$$ A\n|a = 1\n");
+    }
+
+    #[test]
+    fn test_duplicate_and_delete_cell() {
+        check("##$$ A\na = |1\n##$$ B\nb = 2\n", Editor::duplicate_cell, "##$$ A\na = 1\n##$$ A\na = |1\n##$$ B\nb = 2\n");
+        check("import |os\n", Editor::duplicate_cell, "import os\n##$$\nimport |os\n");
+        check("##$$ A\na = 1\n##$$ B\nb = |2\n##$$ C\nc = 3\n", Editor::delete_cell, "##$$ A\na = 1\n|##$$ C\nc = 3\n");
+        // Deleting the last cell leaves the cursor in the one above
+        check("##$$ A\na = 1\n##$$ B\nb = |2\n", Editor::delete_cell, "|##$$ A\na = 1\n");
+    }
+
+    #[test]
+    fn test_insert_cell() {
+        check("##$$ A\na = |1\n", |e| e.insert_cell(true), "##$$ A\na = 1\n##$$\n|\n");
+        check("a = |1\n", |e| e.insert_cell(false), "##$$\n|\n##$$\na = 1\n");
+    }
+}
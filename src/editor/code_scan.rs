@@ -0,0 +1,91 @@
+/// Walks Python source, telling strings and comments apart from code. The state carries
+/// over between calls, so text can be fed a line at a time.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub(super) struct CodeScanner {
+    /// Quote of the string being scanned (three characters for a triple-quoted one)
+    pub string_quote: Option<String>,
+    pub in_comment: bool,
+}
+
+impl CodeScanner {
+    /// Call `code` with the offset of each byte of `text` outside strings and comments.
+    /// The `#` that opens a comment is passed too, so callers can find where it starts.
+    pub fn scan(&mut self, text: &str, mut code: impl FnMut(usize, u8)) {
+        let bytes = text.as_bytes();
+        let mut i = 0;
+        while i < bytes.len() {
+            let b = bytes[i];
+            if self.in_comment {
+                self.in_comment = b != b'\n';
+                i += 1;
+                continue;
+            }
+            if let Some(quote) = &self.string_quote {
+                if b == b'\\' {
+                    i += 2;
+                    continue;
+                }
+                if bytes[i..].starts_with(quote.as_bytes()) {
+                    i += quote.len();
+                    self.string_quote = None;
+                    continue;
+                }
+                if b == b'\n' && quote.len() == 1 {
+                    // Unterminated single-line string
+                    self.string_quote = None;
+                }
+                i += 1;
+                continue;
+            }
+            match b {
+                b'#' => {
+                    self.in_comment = true;
+                    code(i, b);
+                }
+                b'"' | b'\'' => {
+                    let triple = bytes.get(i + 1) == Some(&b) && bytes.get(i + 2) == Some(&b);
+                    let len = if triple { 3 } else { 1 };
+                    self.string_quote = Some((b as char).to_string().repeat(len));
+                    i += len;
+                    continue;
+                }
+                _ => code(i, b),
+            }
+            i += 1;
+        }
+    }
+}
+
+/// Line text without a trailing comment (quotes are tracked so `"#"` is kept)
+pub(super) fn strip_comment(line: &str) -> &str {
+    let mut end = None;
+    CodeScanner::default().scan(line, |i, b| {
+        if b == b'#' && end.is_none() {
+            end = Some(i);
+        }
+    });
+    &line[..end.unwrap_or(line.len())]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan() {
+        let mut code = String::new();
+        let mut scanner = CodeScanner::default();
+        scanner.scan("f('a)', \"b\\\"#\") # c(\n", |_, b| code.push(b as char));
+        assert_eq!(code, "f(, ) #");
+        assert!(!scanner.in_comment);
+
+        // Triple-quoted strings carry over to the next piece of text
+        scanner.scan("x = \"\"\"(\n", |_, _| {});
+        assert_eq!(scanner.string_quote.as_deref(), Some("\"\"\""));
+        code.clear();
+        scanner.scan(")\"\"\" + [1]", |_, b| code.push(b as char));
+        assert_eq!(code, " + [1]");
+
+        assert_eq!(strip_comment("if x == '#':  # check"), "if x == '#':  ");
+    }
+}
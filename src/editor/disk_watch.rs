@@ -0,0 +1,177 @@
+use crate::file_format::FileFormat;
+use crate::state;
+use std::fs;
+use std::io;
+use std::time::{Duration, Instant, SystemTime};
+
+use super::Editor;
+
+/// Minimum time between two checks of the file on disk while idle
+const DISK_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Result of comparing the buffer's file with what is on disk
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DiskChange {
+    Unchanged,
+    Reloaded, // File changed and the buffer was clean, so it was reloaded
+    Conflict, // File changed while the buffer has unsaved edits
+}
+
+impl Editor {
+    /// Remember the mtime and content hash of the file as we last read or wrote it
+    pub(super) fn record_disk_state(&mut self, content_hash: u64) {
+        self.disk_hash = content_hash;
+        self.disk_mtime = self.file_path.as_ref()
+            .and_then(|p| fs::metadata(p).ok())
+            .and_then(|m| m.modified().ok());
+        self.disk_changed = false;
+    }
+
+    /// Read the raw file content and its mtime, if the file exists
+    fn read_disk(&self) -> Option<(String, Option<SystemTime>)> {
+        let path = self.file_path.as_ref()?;
+        let mtime = fs::metadata(path).ok()?.modified().ok();
+        let content = fs::read_to_string(path).ok()?;
+        Some((content, mtime))
+    }
+
+    /// Check whether the file was changed by another program (git checkout, formatter, ...)
+    /// A clean buffer is reloaded in place; a modified one is flagged so save can warn first
+    pub fn check_disk_changes(&mut self) -> DiskChange {
+        self.last_disk_check = Some(Instant::now());
+
+        let path = match self.file_path.as_ref() {
+            Some(path) => path.clone(),
+            None => return DiskChange::Unchanged,
+        };
+
+        // Cheap test first: nothing to do if the mtime hasn't moved
+        let mtime = match fs::metadata(&path).and_then(|m| m.modified()) {
+            Ok(mtime) => mtime,
+            Err(_) => return DiskChange::Unchanged, // Deleted or unreadable - keep the buffer as is
+        };
+        if Some(mtime) == self.disk_mtime {
+            return if self.disk_changed && self.modified {
+                DiskChange::Conflict
+            } else {
+                DiskChange::Unchanged
+            };
+        }
+
+        // The mtime changed - compare content, since touching a file isn't a change
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(_) => return DiskChange::Unchanged,
+        };
+        self.disk_mtime = Some(mtime);
+        if state::hash_str(&content) == self.disk_hash {
+            return DiskChange::Unchanged;
+        }
+
+        if !self.modified {
+            self.apply_disk_content(&content, Some(mtime));
+            self.status_message = Some((format!("Reloaded {} (changed on disk)", self.file_name()), false));
+            DiskChange::Reloaded
+        } else {
+            self.disk_changed = true;
+            self.status_message = Some((
+                format!("{} changed on disk - saving will ask before overwriting", self.file_name()),
+                true
+            ));
+            DiskChange::Conflict
+        }
+    }
+
+    /// Rate-limited version of `check_disk_changes` for the idle loop
+    pub fn poll_disk_changes(&mut self) -> DiskChange {
+        if self.last_disk_check.is_some_and(|t| t.elapsed() < DISK_CHECK_INTERVAL) {
+            return DiskChange::Unchanged;
+        }
+        self.check_disk_changes()
+    }
+
+    /// Whether saving now would overwrite changes someone else made to the file
+    pub fn has_disk_conflict(&mut self) -> bool {
+        self.check_disk_changes();
+        self.disk_changed
+    }
+
+    /// Replace the buffer with the file's current content (undoable)
+    pub fn reload_from_disk(&mut self) -> io::Result<()> {
+        let (content, mtime) = self.read_disk()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "File not found on disk"))?;
+        self.apply_disk_content(&content, mtime);
+        self.status_message = Some((format!("Reloaded {} from disk (Ctrl+Z to undo)", self.file_name()), false));
+        Ok(())
+    }
+
+    /// The file's current content, normalized the same way as the buffer (for diffs)
+    pub fn disk_text(&self) -> Option<String> {
+        self.read_disk().map(|(content, _)| FileFormat::buffer_text(&content))
+    }
+
+    fn apply_disk_content(&mut self, content: &str, mtime: Option<SystemTime>) {
+        self.file_format = FileFormat::detect(content);
+        self.replace_buffer_text(content);
+        self.set_line_formats(self.file_format.line_formats(content));
+        self.buffer.mark_saved();
+        self.modified = false;
+        self.disk_hash = state::hash_str(content);
+        self.disk_mtime = mtime;
+        self.disk_changed = false;
+        self.remove_swap_file();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::Command;
+    use std::path::Path;
+
+    /// Write the file with an mtime `secs` ahead, so the change shows even on coarse clocks
+    fn write_file(path: &Path, content: &str, secs: u64) {
+        fs::write(path, content).unwrap();
+        let file = fs::File::options().write(true).open(path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(secs)).unwrap();
+    }
+
+    #[test]
+    fn test_disk_changes() {
+        let dir = std::env::temp_dir().join(format!("sage-disk-watch-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("notebook.py");
+        fs::write(&path, "x = 1\n").unwrap();
+
+        let mut editor = Editor::new();
+        editor.load_file(path.to_str().unwrap()).unwrap();
+        assert_eq!(editor.check_disk_changes(), DiskChange::Unchanged);
+
+        // A clean buffer follows the file
+        write_file(&path, "x = 2\n", 10);
+        assert_eq!(editor.check_disk_changes(), DiskChange::Reloaded);
+        assert_eq!(editor.buffer.to_string(), "x = 2\n");
+        assert!(!editor.is_modified());
+
+        // Touching the file without changing it is not a change
+        write_file(&path, "x = 2\n", 20);
+        assert_eq!(editor.check_disk_changes(), DiskChange::Unchanged);
+
+        // Unsaved edits are kept and flagged until reloaded explicitly
+        editor.paste_text("# ".to_string());
+        write_file(&path, "x = 3\n", 30);
+        assert_eq!(editor.check_disk_changes(), DiskChange::Conflict);
+        assert_eq!(editor.check_disk_changes(), DiskChange::Conflict);
+        assert!(editor.has_disk_conflict());
+        assert_eq!(editor.buffer.to_string(), "# x = 2\n");
+
+        editor.reload_from_disk().unwrap();
+        assert_eq!(editor.buffer.to_string(), "x = 3\n");
+        assert!(!editor.is_modified());
+        assert!(!editor.has_disk_conflict());
+        editor.execute(Command::Undo).unwrap();
+        assert_eq!(editor.buffer.to_string(), "# x = 2\n");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
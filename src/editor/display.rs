@@ -0,0 +1,310 @@
+use crate::cell::CellStatus;
+use std::borrow::Cow;
+use super::Editor;
+
+/// Maximum number of output lines shown inline below a cell
+const MAX_INLINE_OUTPUT_LINES: usize = 10;
+
+/// Number of "~" filler rows drawn before the first buffer line
+const LEADING_FILLER_ROWS: usize = 2;
+
+/// What one row of the editor view shows. With inline outputs enabled the view is
+/// no longer one row per buffer line, so viewport, cursor and mouse math go through this.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DisplayRow {
+    /// "~" line before or after the buffer
+    Filler,
+    /// A buffer line (its first row when soft-wrapped)
+    Line(usize),
+    /// A further row of a soft-wrapped line, starting at byte `start` of the line
+    Wrap { line: usize, start: usize },
+    /// Non-editable output of a cell, drawn below the cell's last line
+    Output { cell: usize, line: usize, text: String, style: OutputStyle },
+}
+
+/// How an inline output row is drawn
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputStyle {
+    Text,
+    Error,
+    /// Summary row for collapsed or truncated output
+    Fold,
+}
+
+/// Output rows of one cell and the buffer line they follow
+#[derive(Clone)]
+pub(super) struct OutputBlock {
+    cell: usize,
+    after_line: usize,
+    rows: Vec<(String, OutputStyle)>,
+}
+
+/// Output blocks laid out for a buffer revision, fold state and version of the cell outputs
+pub(super) struct OutputLayout {
+    revision: u64,
+    outputs_version: u64,
+    folds: Vec<super::folding::Fold>,
+    blocks: Vec<OutputBlock>,
+}
+
+impl Editor {
+    /// Show or hide cell outputs inline below each cell
+    pub fn toggle_inline_outputs(&mut self) {
+        self.inline_outputs = !self.inline_outputs;
+        self.refresh_cells();
+        self.status_message = Some((
+            if self.inline_outputs { "Inline outputs on" } else { "Inline outputs off" }.to_string(),
+            false
+        ));
+        self.update_viewport_for_cursor();
+    }
+
+    pub fn inline_outputs_enabled(&self) -> bool {
+        self.inline_outputs
+    }
+
+    /// Fold or unfold the inline output of the cell under the cursor
+    pub fn toggle_output_collapsed(&mut self) {
+        self.refresh_cells();
+        let (line, _) = self.cursor_position();
+        match self.cell_index_at_line(line) {
+            Some(idx) if self.cells[idx].output.is_some() => self.toggle_cell_output(idx),
+            _ => self.status_message = Some(("No output for this cell".to_string(), false)),
+        }
+    }
+
+    /// Fold or unfold the inline output of a cell
+    pub fn toggle_cell_output(&mut self, cell: usize) {
+        if let Some(cell) = self.cells.get_mut(cell) {
+            cell.output_collapsed = !cell.output_collapsed;
+        }
+        self.outputs_version += 1;
+        self.update_viewport_for_cursor();
+    }
+
+    /// Drop the outputs kept in cells (the gutter still shows which cells ran)
+    pub fn clear_cell_outputs(&mut self) {
+        for cell in &mut self.cells {
+            cell.output = None;
+            cell.output_collapsed = false;
+        }
+        self.outputs_version += 1;
+    }
+
+    /// Lay out the inline outputs again if the buffer, folds or outputs changed since
+    pub fn refresh_output_layout(&mut self) {
+        if !self.inline_outputs {
+            self.output_layout = None;
+        } else if !self.output_layout.as_ref().is_some_and(|layout| self.is_current(layout)) {
+            self.output_layout = Some(OutputLayout {
+                revision: self.buffer.revision(),
+                outputs_version: self.outputs_version,
+                folds: self.folds.clone(),
+                blocks: self.layout_output_blocks(),
+            });
+        }
+    }
+
+    fn is_current(&self, layout: &OutputLayout) -> bool {
+        layout.revision == self.buffer.revision()
+            && layout.outputs_version == self.outputs_version
+            && layout.folds == self.folds
+    }
+
+    /// Inline output rows for every cell that has output, in buffer order
+    fn output_blocks(&self) -> Cow<'_, [OutputBlock]> {
+        if !self.inline_outputs {
+            return Cow::Borrowed(&[]);
+        }
+        match self.output_layout.as_ref().filter(|layout| self.is_current(layout)) {
+            Some(layout) => Cow::Borrowed(&layout.blocks),
+            None => Cow::Owned(self.layout_output_blocks()),
+        }
+    }
+
+    fn layout_output_blocks(&self) -> Vec<OutputBlock> {
+
+        let len_bytes = self.buffer.len_bytes();
+        let hidden = self.hidden_ranges();
+        let cell_folds = self.cell_fold_lines();
+        let mut blocks = Vec::new();
+        for (idx, cell) in self.cells.iter().enumerate() {
+            let Some(result) = cell.output.as_ref() else { continue };
+            let text = strip_ansi(&crate::cell::format_output_text(result));
+            if text.is_empty() {
+                continue;
+            }
+
+            // Cells may be a parse behind the buffer while typing; keep offsets in range
+            let last_byte = cell.end.min(len_bytes).saturating_sub(1).max(cell.start.min(len_bytes));
+            let after_line = self.buffer.byte_to_line(last_byte.min(len_bytes));
+
+            // A folded cell hides its output; inside other folds it follows the fold's header
+            if cell_folds.iter().any(|&(first, last)| (first..=last).contains(&after_line)) {
+                continue;
+            }
+            let after_line = hidden.iter()
+                .find(|r| (r.first..=r.last).contains(&after_line))
+                .map_or(after_line, |r| r.first - 1);
+            let count = cell.execution_count.map_or(String::new(), |n| format!("[{}] ", n));
+            let lines: Vec<&str> = text.lines().collect();
+            let style = if result.success { OutputStyle::Text } else { OutputStyle::Error };
+
+            let rows = if cell.output_collapsed {
+                vec![(format!("▸ {}output hidden ({} lines)", count, lines.len()), OutputStyle::Fold)]
+            } else if lines.len() > MAX_INLINE_OUTPUT_LINES {
+                let shown = MAX_INLINE_OUTPUT_LINES - 1;
+                let mut rows: Vec<_> = lines[..shown].iter().map(|l| (l.replace('\t', "    "), style)).collect();
+                rows.push((format!("… {} more lines", lines.len() - shown), OutputStyle::Fold));
+                rows
+            } else {
+                lines.iter().map(|l| (l.replace('\t', "    "), style)).collect()
+            };
+
+            // A running or stale cell still shows its previous output, dimmed
+            let rows = if matches!(cell.status, CellStatus::Running | CellStatus::Stale) {
+                rows.into_iter()
+                    .map(|(text, style)| (text, if style == OutputStyle::Error { style } else { OutputStyle::Fold }))
+                    .collect()
+            } else {
+                rows
+            };
+            blocks.push(OutputBlock { cell: idx, after_line, rows });
+        }
+        blocks
+    }
+
+    /// Whether every buffer line is drawn on exactly one row: no wrapping, folds or outputs
+    fn is_one_row_per_line(&self) -> bool {
+        !self.soft_wrap && self.folds.is_empty() && self.output_blocks().is_empty()
+    }
+
+    /// Display row on which a buffer line is drawn (a folded line is drawn on its fold's header)
+    pub fn line_to_display_row(&self, line: usize) -> usize {
+        if self.is_one_row_per_line() {
+            return line + LEADING_FILLER_ROWS;
+        }
+        let hidden = self.hidden_ranges();
+        let line = hidden.iter()
+            .find(|r| (r.first..=r.last).contains(&line))
+            .map_or(line, |r| r.first - 1);
+        let hidden_rows: usize = hidden.iter()
+            .filter(|r| r.last < line)
+            .map(|r| r.last + 1 - r.first)
+            .sum();
+        let output_rows: usize = self.output_blocks().iter()
+            .filter(|block| block.after_line < line)
+            .map(|block| block.rows.len())
+            .sum();
+        line + LEADING_FILLER_ROWS + output_rows + self.wrap_rows_in(0..line) - hidden_rows
+    }
+
+    /// Total number of display rows used by the buffer and its inline outputs
+    pub fn display_row_count(&self) -> usize {
+        if self.is_one_row_per_line() {
+            return self.buffer.len_lines() + LEADING_FILLER_ROWS;
+        }
+        let hidden_rows: usize = self.hidden_ranges().iter().map(|r| r.last + 1 - r.first).sum();
+        let output_rows: usize = self.output_blocks().iter().map(|block| block.rows.len()).sum();
+        let wrap_rows = self.wrap_rows_in(0..self.buffer.len_lines());
+        self.buffer.len_lines() + LEADING_FILLER_ROWS + output_rows + wrap_rows - hidden_rows
+    }
+
+    /// The `count` display rows starting at display row `start`
+    pub fn display_rows(&self, start: usize, count: usize) -> Vec<DisplayRow> {
+        let hidden = self.hidden_ranges();
+        let (mut line, skip) = if start < LEADING_FILLER_ROWS {
+            (0, start)
+        } else {
+            // The last line drawn at or above `start` (the fold's header for a folded line)
+            let (mut low, mut high) = (0, self.buffer.len_lines());
+            while low + 1 < high {
+                let mid = (low + high) / 2;
+                if self.line_to_display_row(mid) <= start {
+                    low = mid;
+                } else {
+                    high = mid;
+                }
+            }
+            let line = hidden.iter().find(|r| (r.first..=r.last).contains(&low)).map_or(low, |r| r.first - 1);
+            (line, start - self.line_to_display_row(line))
+        };
+
+        let first_line = line;
+        let blocks = self.output_blocks();
+        let mut blocks = blocks.iter().skip_while(|block| block.after_line < first_line).peekable();
+        let mut hidden = hidden.iter().skip_while(|r| r.first <= first_line).peekable();
+        let mut rows = Vec::with_capacity(skip + count);
+        if start < LEADING_FILLER_ROWS {
+            rows.extend(std::iter::repeat_n(DisplayRow::Filler, LEADING_FILLER_ROWS));
+        }
+
+        while rows.len() < skip + count && line < self.buffer.len_lines() {
+            rows.push(DisplayRow::Line(line));
+            if self.soft_wrap {
+                rows.extend(self.wrap_segments(line).into_iter().skip(1).map(|(start, _)| DisplayRow::Wrap { line, start }));
+            }
+            while let Some(block) = blocks.next_if(|block| block.after_line <= line) {
+                rows.extend(block.rows.iter().map(|(text, style)| DisplayRow::Output {
+                    cell: block.cell,
+                    line,
+                    text: text.clone(),
+                    style: *style,
+                }));
+            }
+            // Skip the lines folded below this one
+            line = match hidden.next_if(|r| r.first == line + 1) {
+                Some(range) => range.last + 1,
+                None => line + 1,
+            };
+        }
+
+        rows.resize(skip + count, DisplayRow::Filler);
+        rows.split_off(skip)
+    }
+
+    /// Display row shown at a screen row of the editor view
+    pub fn display_row_at_screen(&self, screen_row: usize) -> DisplayRow {
+        self.display_rows(self.viewport_offset.0 + screen_row, 1).remove(0)
+    }
+
+    /// Cell whose inline output is drawn on a screen row, if any
+    pub fn output_cell_at_screen_row(&self, screen_row: usize) -> Option<usize> {
+        match self.display_row_at_screen(screen_row) {
+            DisplayRow::Output { cell, .. } => Some(cell),
+            _ => None,
+        }
+    }
+}
+
+/// Remove terminal escape sequences (colored tracebacks) from output text
+fn strip_ansi(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(ch) = chars.next() {
+        if ch == '\x1b' {
+            // CSI sequences end with a letter; anything else is a single escaped char
+            if chars.next() == Some('[') {
+                for c in chars.by_ref() {
+                    if c.is_ascii_alphabetic() {
+                        break;
+                    }
+                }
+            }
+        } else {
+            result.push(ch);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::strip_ansi;
+
+    #[test]
+    fn test_strip_ansi() {
+        assert_eq!(strip_ansi("\x1b[31mValueError\x1b[0m: bad"), "ValueError: bad");
+        assert_eq!(strip_ansi("plain"), "plain");
+    }
+}
@@ -2,12 +2,19 @@ use crate::buffer::Buffer;
 use crate::syntax::SyntaxHighlighter;
 use crate::kernel::{self, Kernel};
 use crate::direct_kernel::DirectKernel;
+use crate::file_format::{FileFormat, LineFormat};
+use crate::{state, swap};
 use std::fs;
 use std::io;
 use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
+use super::multi_cursor::remap_position;
 use super::Editor;
 
+/// Write the swap file at least this often while the user keeps typing
+const SWAP_INTERVAL: Duration = Duration::from_secs(30);
+
 impl Editor {
     /// Normalize text by removing invisible characters and converting line endings/tabs
     pub(super) fn normalize_text(text: String) -> String {
@@ -69,15 +76,23 @@ impl Editor {
 
     pub fn load_file(&mut self, path: &str) -> io::Result<()> {
         let content = fs::read_to_string(path)?;
-        // Normalize: CRLF → LF, tabs → spaces, remove invisible characters
-        let content = Self::normalize_text(content);
-        self.buffer = Buffer::from_string(content);
+        // Remember line endings, indentation and BOM so save can restore them
+        self.file_format = FileFormat::detect(&content);
+        let line_formats = self.file_format.line_formats(&content);
+        let disk_hash = state::hash_str(&content);
+        // Normalize: CRLF → LF, indentation tabs → spaces
+        self.buffer = Buffer::from_string(FileFormat::buffer_text(&content));
+        self.set_line_formats(line_formats);
+        self.clear_folds();
+        self.clear_extra_cursors();
+        self.cells.clear();
+        self.cells_revision = None;
         self.file_path = Some(PathBuf::from(path));
+        self.record_disk_state(disk_hash);
         self.cursor = 0;
         self.selection_start = None;
         self.modified = false;
         self.viewport_offset = (0, 0);
-        self.last_saved_undo_len = 0;
         self.mouse_selecting = false;
         self.preferred_column = None;
 
@@ -105,10 +120,39 @@ impl Editor {
         }
         // Large files will initialize viewport on first render
 
+        Ok(())
+    }
+
+    /// Connect to the interpreter used for this file last time, falling back to
+    /// the shebang if there is none (or it is no longer installed)
+    pub fn select_initial_kernel(&mut self, remembered: Option<&kernel::KernelInfo>) {
+        if let Some(kernel_info) = remembered {
+            // A bare command name is looked up on PATH when the kernel starts
+            let python_path = Path::new(&kernel_info.python_path);
+            let installed = python_path.components().count() <= 1 || python_path.exists();
+            if installed {
+                self.set_kernel(self.create_kernel_from_info(kernel_info));
+                self.enable_repl_mode();
+                match self.connect_kernel() {
+                    Ok(_) => {
+                        self.status_message = Some((
+                            format!("Reconnected to: {}", kernel_info.display_name),
+                            false
+                        ));
+                        return;
+                    }
+                    Err(e) => {
+                        self.status_message = Some((
+                            format!("Failed to reconnect to {}: {}", kernel_info.display_name, e),
+                            true
+                        ));
+                    }
+                }
+            }
+        }
+
         // Check for shebang and auto-select kernel if appropriate
         self.detect_and_set_kernel_from_shebang();
-
-        Ok(())
     }
 
     /// Detect shebang in the first few lines and automatically select an appropriate kernel
@@ -307,15 +351,19 @@ impl Editor {
             return Err(io::Error::new(io::ErrorKind::PermissionDenied, "File is read-only"));
         }
 
+        self.sync_line_formats();
         if let Some(ref path) = self.file_path {
             // Create parent directories if they don't exist
             if let Some(parent) = path.parent() {
                 fs::create_dir_all(parent)?;
             }
-            match fs::write(path, self.buffer.to_string()) {
+            let content = self.file_content();
+            match state::write_atomic(path, content.as_bytes()) {
                 Ok(_) => {
                     self.modified = false;
-                    self.last_saved_undo_len = 0; // Reset save point
+                    self.remove_swap_file();
+                    self.record_disk_state(state::hash_str(&content));
+                    self.buffer.mark_saved();
                     self.status_message = None; // Clear any error messages
                     Ok(())
                 }
@@ -342,11 +390,16 @@ impl Editor {
             fs::create_dir_all(parent)?;
         }
 
-        match fs::write(&path, self.buffer.to_string()) {
+        self.sync_line_formats();
+        let content = self.file_content();
+        match state::write_atomic(&path, content.as_bytes()) {
             Ok(_) => {
+                // The buffer now lives under the new name
+                self.remove_swap_file();
                 self.file_path = Some(path.clone());
+                self.record_disk_state(state::hash_str(&content));
                 self.modified = false;
-                self.last_saved_undo_len = 0; // Reset save point
+                self.buffer.mark_saved();
                 self.read_only = new_read_only;
                 self.status_message = None; // Clear any error messages
                 Ok(())
@@ -370,8 +423,150 @@ impl Editor {
             .unwrap_or("[No Name]")
     }
 
+    /// On-disk formatting that will be used when saving
+    pub fn file_format(&self) -> FileFormat {
+        self.file_format
+    }
+
+    /// Switch between LF and CRLF line endings for the next save
+    pub fn toggle_line_ending(&mut self) {
+        self.file_format.line_ending = self.file_format.line_ending.toggled();
+        for (_, format) in &mut self.line_formats {
+            format.line_ending = None;
+        }
+        self.modified = true;
+        self.status_message = Some((
+            format!("Line endings: {}", self.file_format.line_ending.label()),
+            false
+        ));
+    }
+
+    /// Switch between tab and space indentation for the next save
+    pub fn toggle_indent_style(&mut self) {
+        self.file_format.indent_style = self.file_format.indent_style.toggled();
+        for (_, format) in &mut self.line_formats {
+            format.indent = None;
+        }
+        self.modified = true;
+        self.status_message = Some((
+            format!("Indentation: {}", self.file_format.indent_style.label()),
+            false
+        ));
+    }
+
+    /// Add or remove the UTF-8 byte order mark for the next save
+    pub fn toggle_bom(&mut self) {
+        self.file_format.has_bom = !self.file_format.has_bom;
+        self.modified = true;
+        let state = if self.file_format.has_bom { "on" } else { "off" };
+        self.status_message = Some((format!("Byte order mark: {}", state), false));
+    }
+
     pub fn set_file_path(&mut self, path: &str) {
         self.file_path = Some(PathBuf::from(path));
         self.syntax.set_language_from_path(path);
     }
+
+    /// Snapshot the buffer to its swap file if it has unsaved changes
+    /// `idle` forces a write (the user paused); otherwise only write every SWAP_INTERVAL
+    pub fn update_swap_file(&mut self, idle: bool) {
+        if !self.modified {
+            // Back at the saved state (e.g. after undo) - nothing to recover
+            if self.last_swap_hash != 0 {
+                self.remove_swap_file();
+            }
+            return;
+        }
+
+        let due = idle || self.last_swap_time.is_none_or(|t| t.elapsed() >= SWAP_INTERVAL);
+        if !due {
+            return;
+        }
+
+        let content = self.buffer.to_string();
+        let hash = state::hash_str(&content);
+        if hash == self.last_swap_hash {
+            return;
+        }
+
+        if swap::write(self.file_path.as_deref(), &content).is_ok() {
+            self.last_swap_hash = hash;
+        }
+        self.last_swap_time = Some(Instant::now());
+    }
+
+    /// Delete this buffer's swap file (after saving or a clean exit)
+    pub fn remove_swap_file(&mut self) {
+        if self.last_swap_hash != 0 {
+            swap::remove(self.file_path.as_deref());
+        }
+        self.last_swap_hash = 0;
+        self.last_swap_time = None;
+    }
+
+    /// Look for a swap file left by a crashed session whose content differs from the buffer
+    pub fn find_recovery(&self) -> Option<(PathBuf, swap::SwapFile)> {
+        let (swap_path, snapshot) = swap::find_orphaned(self.file_path.as_deref())?;
+        if snapshot.content == self.buffer.to_string() {
+            // Nothing was lost - the snapshot matches what's on disk
+            swap::remove_file(&swap_path);
+            return None;
+        }
+        Some((swap_path, snapshot))
+    }
+
+    /// Remember the lines written differently from the file's format, by where they start
+    pub(super) fn set_line_formats(&mut self, line_formats: Vec<(usize, LineFormat)>) {
+        self.line_formats = line_formats;
+        self.line_formats_revision = self.buffer.revision();
+    }
+
+    /// Move the per-line format starts along with edits made since they were last synced
+    pub(super) fn sync_line_formats(&mut self) {
+        let revision = self.buffer.revision();
+        if revision == self.line_formats_revision {
+            return;
+        }
+        match self.buffer.changes_since(self.line_formats_revision) {
+            Some(changes) => {
+                for (pos, _) in &mut self.line_formats {
+                    *pos = changes.iter().fold(*pos, remap_position);
+                }
+            }
+            None => self.line_formats.clear(),
+        }
+        self.line_formats_revision = revision;
+    }
+
+    /// The buffer text in the file's on-disk format (per-line formats must be synced)
+    fn file_content(&self) -> String {
+        let rope = self.buffer.rope();
+        let mut line_formats: Vec<(usize, LineFormat)> = self.line_formats.iter()
+            .filter(|(pos, _)| *pos == 0 || (*pos < rope.len_bytes() && rope.byte(pos - 1) == b'\n'))
+            .cloned()
+            .collect();
+        // Lines joined by an edit can end up at the same start; keep the first
+        line_formats.sort_by_key(|(pos, _)| *pos);
+        line_formats.dedup_by_key(|(pos, _)| *pos);
+        self.file_format.apply(&self.buffer.to_string(), &line_formats)
+    }
+
+    /// Replace the whole buffer with `text` as a single undoable edit
+    pub fn replace_buffer_text(&mut self, text: &str) {
+        let text = FileFormat::buffer_text(text);
+        let cursor_before = self.cursor;
+        let new_cursor = self.cursor.min(text.len());
+
+        self.buffer.finalize_undo_group();
+        self.buffer.delete(0, self.buffer.len_bytes(), cursor_before, 0);
+        self.buffer.insert(0, &text, 0, new_cursor);
+        self.buffer.finalize_undo_group();
+
+        self.cursor = self.ensure_char_boundary(new_cursor);
+        self.selection_start = None;
+        self.preferred_column = None;
+        self.modified = true;
+        self.reinit_syntax_highlighting();
+        self.update_viewport_for_cursor();
+    }
 }
@@ -0,0 +1,399 @@
+use crate::buffer::TextChange;
+use crate::cell::CELL_DELIMITER;
+use ropey::Rope;
+use super::Editor;
+use super::code_scan::{strip_comment, CodeScanner};
+
+/// Lines scanned when looking for the end of a bracketed expression
+const MAX_BRACKET_SCAN_LINES: usize = 2000;
+
+/// What a fold collapses
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum FoldKind {
+    /// A whole cell down to its `##$$` header (its inline output is hidden too)
+    Cell,
+    /// An indented block or a multi-line bracketed expression
+    Block,
+}
+
+/// Whole lines hidden below a visible header line. Stored as byte offsets
+/// (start of the first hidden line, end of the last one) so that edits
+/// elsewhere only shift the fold.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) struct Fold {
+    start: usize,
+    end: usize,
+    kind: FoldKind,
+}
+
+/// Hidden lines of one or more nested folds: `first..=last`, shown as one row for `first - 1`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) struct HiddenRange {
+    pub first: usize,
+    pub last: usize,
+}
+
+impl Editor {
+    /// Fold the block at the cursor, or unfold it if it is folded. Without a foldable
+    /// block starting on the cursor line, the innermost block around it is folded.
+    pub fn toggle_fold(&mut self) {
+        self.sync_folds();
+        self.refresh_cells();
+        let (line, _) = self.cursor_position();
+
+        if self.unfold_line(line) {
+            self.status_message = Some(("Unfolded".to_string(), false));
+            return;
+        }
+
+        let header = (0..=line).rev()
+            .take(MAX_BRACKET_SCAN_LINES)
+            .find(|&header| self.fold_range_at(header).is_some_and(|(last, _)| last >= line));
+        match header {
+            Some(header) => {
+                self.fold_line(header);
+                // Park the cursor on the header so it stays visible
+                if header != line {
+                    self.cursor = self.line_end_byte(header);
+                    self.selection_start = None;
+                    self.preferred_column = None;
+                }
+                self.update_viewport_for_cursor();
+            }
+            None => self.status_message = Some(("Nothing to fold here".to_string(), false)),
+        }
+    }
+
+    /// Fold or unfold the block starting on a line (gutter clicks)
+    pub fn toggle_fold_at_line(&mut self, line: usize) -> bool {
+        self.sync_folds();
+        self.refresh_cells();
+        if self.unfold_line(line) {
+            return true;
+        }
+        if self.fold_range_at(line).is_none() {
+            return false;
+        }
+        self.fold_line(line);
+        // Keep the cursor out of the hidden lines
+        let (cursor_line, _) = self.cursor_position();
+        if self.is_line_hidden(cursor_line) {
+            self.cursor = self.line_end_byte(line);
+            self.selection_start = None;
+        }
+        true
+    }
+
+    /// Fold every cell down to its header, or unfold everything if anything is folded
+    pub fn toggle_fold_all_cells(&mut self) {
+        self.sync_folds();
+        if !self.folds.is_empty() {
+            self.folds.clear();
+            self.status_message = Some(("Unfolded all".to_string(), false));
+            self.update_viewport_for_cursor();
+            return;
+        }
+
+        self.refresh_cells();
+        let headers: Vec<usize> = self.cells.iter()
+            .map(|c| self.buffer.byte_to_line(c.start))
+            .filter(|&line| self.buffer.line(line).trim_start().starts_with(CELL_DELIMITER))
+            .collect();
+        for &header in &headers {
+            self.fold_line(header);
+        }
+
+        // Put the cursor on the header of its cell
+        let (line, _) = self.cursor_position();
+        if let Some(range) = self.hidden_ranges().into_iter().find(|r| (r.first..=r.last).contains(&line)) {
+            self.cursor = self.line_end_byte(range.first - 1);
+            self.selection_start = None;
+            self.preferred_column = None;
+        }
+        self.status_message = Some((format!("Folded {} cells", headers.len()), false));
+        self.update_viewport_for_cursor();
+    }
+
+    /// Fold the block starting on a line (must be foldable)
+    fn fold_line(&mut self, line: usize) {
+        let Some((last, kind)) = self.fold_range_at(line) else { return };
+        let fold = Fold {
+            start: self.buffer.line_to_byte(line + 1),
+            end: self.buffer.line_to_byte(last + 1),
+            kind,
+        };
+        self.folds.retain(|f| f.start != fold.start);
+        self.folds.push(fold);
+        self.folds.sort_by_key(|f| (f.start, std::cmp::Reverse(f.end)));
+    }
+
+    /// Remove the fold whose header is a line; false if there is none
+    fn unfold_line(&mut self, line: usize) -> bool {
+        let start = self.buffer.line_to_byte(line + 1);
+        let before = self.folds.len();
+        self.folds.retain(|f| f.start != start);
+        self.folds.len() != before
+    }
+
+    /// Last line and kind of the fold that would start on a line: the rest of the cell
+    /// on a `##$$` line, else a multi-line bracket or an indented block opened on it
+    fn fold_range_at(&self, line: usize) -> Option<(usize, FoldKind)> {
+        let rope = self.buffer.rope();
+        if line + 1 >= self.buffer.len_lines() {
+            return None;
+        }
+
+        if self.buffer.line(line).trim_start().starts_with(CELL_DELIMITER) {
+            let cell = self.cell_index_at_line(line).map(|idx| &self.cells[idx])?;
+            let last = self.buffer.byte_to_line(cell.end.min(self.buffer.len_bytes()).saturating_sub(1));
+            return (last > line).then_some((last, FoldKind::Cell));
+        }
+
+        bracket_block_end(rope, line)
+            .or_else(|| indent_block_end(rope, line))
+            .map(|last| (last, FoldKind::Block))
+    }
+
+    /// Bring folds up to date with edits made since they were last checked.
+    /// A fold is dropped when an edit touches its hidden lines or it no longer covers whole lines.
+    pub fn sync_folds(&mut self) {
+        let revision = self.buffer.revision();
+        if self.folds_revision == revision {
+            return;
+        }
+        if self.folds.is_empty() {
+            self.folds_revision = revision;
+            return;
+        }
+
+        let changes = self.buffer.changes_since(self.folds_revision);
+        self.folds_revision = revision;
+        let Some(changes) = changes else {
+            self.folds.clear();
+            return;
+        };
+
+        let rope = self.buffer.rope();
+        let len = rope.len_bytes();
+        let is_line_start = |pos: usize| pos == 0 || (pos <= len && rope.byte(pos - 1) == b'\n');
+        self.folds.retain_mut(|fold| {
+            for change in &changes {
+                match remap_fold(fold.start, fold.end, change) {
+                    Some((start, end)) => (fold.start, fold.end) = (start, end),
+                    None => return false,
+                }
+            }
+            fold.start > 0 && fold.start < fold.end && fold.end <= len
+                && is_line_start(fold.start) && (fold.end == len || is_line_start(fold.end))
+        });
+    }
+
+    /// Reset folds (a new file was loaded)
+    pub(super) fn clear_folds(&mut self) {
+        self.folds.clear();
+        self.folds_revision = self.buffer.revision();
+    }
+
+    /// Hidden line ranges in buffer order, nested and overlapping folds merged
+    pub(super) fn hidden_ranges(&self) -> Vec<HiddenRange> {
+        let len = self.buffer.len_bytes();
+        let mut ranges: Vec<HiddenRange> = Vec::new();
+        for fold in &self.folds {
+            if fold.start >= fold.end || fold.end > len {
+                continue; // Not synced with the latest edit yet
+            }
+            let first = self.buffer.byte_to_line(fold.start);
+            let last = self.buffer.byte_to_line(fold.end - 1);
+            match ranges.last_mut() {
+                Some(range) if first <= range.last + 1 => range.last = range.last.max(last),
+                _ => ranges.push(HiddenRange { first, last }),
+            }
+        }
+        ranges
+    }
+
+    /// Hidden ranges that belong to folded cells (their inline outputs are hidden as well)
+    pub(super) fn cell_fold_lines(&self) -> Vec<(usize, usize)> {
+        self.folds.iter()
+            .filter(|f| f.kind == FoldKind::Cell && f.start < f.end && f.end <= self.buffer.len_bytes())
+            .map(|f| (self.buffer.byte_to_line(f.start), self.buffer.byte_to_line(f.end - 1)))
+            .collect()
+    }
+
+    pub fn is_line_hidden(&self, line: usize) -> bool {
+        self.hidden_ranges().iter().any(|r| (r.first..=r.last).contains(&line))
+    }
+
+    /// Number of lines folded away below a line, if it is a fold header
+    pub fn folded_line_count(&self, line: usize) -> Option<usize> {
+        self.hidden_ranges().iter()
+            .find(|r| r.first == line + 1)
+            .map(|r| r.last + 1 - r.first)
+    }
+
+    /// Nearest line above that is not folded away
+    pub(super) fn visible_line_above(&self, line: usize) -> Option<usize> {
+        let above = line.checked_sub(1)?;
+        match self.hidden_ranges().iter().find(|r| (r.first..=r.last).contains(&above)) {
+            Some(range) => Some(range.first - 1),
+            None => Some(above),
+        }
+    }
+
+    /// Nearest line below that is not folded away
+    pub(super) fn visible_line_below(&self, line: usize) -> Option<usize> {
+        let below = match self.hidden_ranges().iter().find(|r| (r.first..=r.last).contains(&(line + 1))) {
+            Some(range) => range.last + 1,
+            None => line + 1,
+        };
+        (below < self.buffer.len_lines()).then_some(below)
+    }
+
+    /// Move the cursor out of folded lines after a movement: forward to the next
+    /// visible line when it moved forward, else back to the end of the fold's header
+    pub(super) fn skip_folded_cursor(&mut self, previous: usize) {
+        let line = self.buffer.byte_to_line(self.cursor);
+        let Some(range) = self.hidden_ranges().into_iter().find(|r| (r.first..=r.last).contains(&line)) else {
+            return;
+        };
+        self.cursor = if self.cursor > previous && range.last + 1 < self.buffer.len_lines() {
+            self.buffer.line_to_byte(range.last + 1)
+        } else {
+            self.line_end_byte(range.first - 1)
+        };
+    }
+
+    /// Unfold whatever hides a byte range (search matches, jumps, undo)
+    pub fn reveal_range(&mut self, start: usize, end: usize) {
+        self.sync_folds();
+        let before = self.folds.len();
+        // A selection may end right where hidden text starts without touching it
+        self.folds.retain(|f| end < f.start || (start < end && end == f.start) || start >= f.end);
+        if self.folds.len() != before {
+            self.status_message = Some(("Unfolded to show the cursor".to_string(), false));
+        }
+    }
+
+    /// Byte offset of the end of a line (before its newline)
+    fn line_end_byte(&self, line: usize) -> usize {
+        let text = self.buffer.line(line);
+        self.buffer.line_to_byte(line) + text.trim_end_matches('\n').len()
+    }
+
+    /// Buffer line whose fold placeholder or gutter fold marker is at a screen position
+    pub fn fold_toggle_at_screen(&self, screen_col: usize, screen_row: usize) -> Option<usize> {
+        let line = self.screen_row_to_line(screen_row)?;
+        let gutter_width = self.gutter_width();
+        if gutter_width > 0 && screen_col + 1 == gutter_width {
+            return Some(line); // Fold marker column of the gutter
+        }
+        self.folded_line_count(line)?;
+        let (_, end_col) = self.position_to_display(self.line_end_byte(line));
+        let col = screen_col.checked_sub(gutter_width)? + self.viewport_offset.1;
+        (col > end_col).then_some(line)
+    }
+}
+
+/// Move a folded byte range through a text change; None if the change touches the hidden text
+fn remap_fold(start: usize, end: usize, change: &TextChange) -> Option<(usize, usize)> {
+    let change_end = change.pos + change.removed;
+    let touches = if change.removed > 0 {
+        change.pos < end && change_end > start
+    } else {
+        change.pos >= start && change.pos < end
+    };
+    if touches {
+        return None;
+    }
+
+    // Text inserted right after the fold stays visible
+    let shift = |pos: usize| pos - change.removed + change.inserted;
+    let start = if start >= change_end { shift(start) } else { start };
+    let end = if end > change.pos { shift(end) } else { end };
+    Some((start, end))
+}
+
+/// Number of leading spaces on a line (tabs count as four)
+pub(super) fn indent_width(line: &str) -> usize {
+    line.chars()
+        .take_while(|c| *c == ' ' || *c == '\t')
+        .map(|c| if c == '\t' { 4 } else { 1 })
+        .sum()
+}
+
+/// Last line of the indented block opened by a line ending in `:` (def, class, if, ...)
+fn indent_block_end(rope: &Rope, line: usize) -> Option<usize> {
+    let header = rope.line(line).to_string();
+    if !strip_comment(&header).trim_end().ends_with(':') {
+        return None;
+    }
+    let indent = indent_width(&header);
+
+    let mut last = None;
+    for next in line + 1..rope.len_lines() {
+        let text = rope.line(next).to_string();
+        if text.trim().is_empty() {
+            continue; // Blank lines don't end a block, but trailing ones aren't folded
+        }
+        if indent_width(&text) <= indent || text.trim_start().starts_with(CELL_DELIMITER) {
+            break;
+        }
+        last = Some(next);
+    }
+    last
+}
+
+/// Line holding the bracket that closes the first bracket left open on a line
+fn bracket_block_end(rope: &Rope, line: usize) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut scanner = CodeScanner::default();
+    let end = rope.len_lines().min(line + MAX_BRACKET_SCAN_LINES);
+
+    for current in line..end {
+        let mut closed = false;
+        scanner.scan(&rope.line(current).to_string(), |_, b| match b {
+            b'(' | b'[' | b'{' => depth += 1,
+            b')' | b']' | b'}' if depth > 0 => {
+                depth -= 1;
+                closed |= depth == 0;
+            }
+            _ => {}
+        });
+        if closed && current > line {
+            return Some(current);
+        }
+        if depth == 0 {
+            return None; // Nothing left open on the first line
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_block_ends() {
+        let rope = Rope::from_str("def f(x):\n    if x:\n        return 1\n\n    return 2\n\nprint(f(1))\n");
+        assert_eq!(indent_block_end(&rope, 0), Some(4));
+        assert_eq!(indent_block_end(&rope, 1), Some(2));
+        assert_eq!(indent_block_end(&rope, 2), None);
+
+        let rope = Rope::from_str("df = pd.DataFrame({\n    \"a\": [1, 2],  # (\n    \"b\": \")\",\n})\nx = (1)\n");
+        assert_eq!(bracket_block_end(&rope, 0), Some(3));
+        assert_eq!(bracket_block_end(&rope, 4), None);
+    }
+
+    #[test]
+    fn test_remap_fold() {
+        let change = |pos, removed, inserted| TextChange { pos, removed, inserted };
+        // Edits before the fold shift it, edits after leave it alone
+        assert_eq!(remap_fold(10, 20, &change(2, 0, 3)), Some((13, 23)));
+        assert_eq!(remap_fold(10, 20, &change(2, 4, 0)), Some((6, 16)));
+        assert_eq!(remap_fold(10, 20, &change(20, 0, 5)), Some((10, 20)));
+        // Edits inside the hidden text drop it
+        assert_eq!(remap_fold(10, 20, &change(12, 0, 1)), None);
+        assert_eq!(remap_fold(10, 20, &change(8, 4, 0)), None);
+    }
+}
@@ -0,0 +1,96 @@
+use crate::kernel::CompletionItem;
+use crate::syntax::Language;
+use super::Editor;
+
+/// The module or name being typed in an import statement
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportContext {
+    pub package: Option<String>, // Package the name is looked up in (None for top-level modules)
+    pub typed: String,           // Text being completed, with its dotted qualifier (`os.pa`)
+    pub modules_only: bool,      // After `import` or `from`: only modules, not exported names
+}
+
+impl Editor {
+    /// The import the cursor is typing a module (or `from ... import` name) of
+    pub fn import_completion_context(&self) -> Option<ImportContext> {
+        if self.syntax.language() != Language::Python {
+            return None;
+        }
+        let line_start = self.buffer.line_to_byte(self.buffer.byte_to_line(self.cursor));
+        let before = self.buffer.rope().byte_slice(line_start..self.cursor).to_string();
+        parse_import(&before)
+    }
+
+    /// Modules the kernel's interpreter can import, or a package's submodules and exported
+    /// names. Cached per interpreter, so they are only listed once; an empty or failed lookup
+    /// isn't cached, so it's retried once the package is installed or the kernel is idle.
+    pub fn importable_modules(&mut self, package: Option<&str>) -> Vec<CompletionItem> {
+        let Some(kernel) = self.kernel.as_mut() else {
+            return Vec::new();
+        };
+        let key = (kernel.info().python_path, package.map(str::to_string));
+        if let Some(modules) = self.module_cache.get(&key) {
+            return modules.clone();
+        }
+        let modules = kernel.modules(package).unwrap_or_default();
+        if !modules.is_empty() {
+            self.module_cache.insert(key, modules.clone());
+        }
+        modules
+    }
+}
+
+/// What the text before the cursor is importing: `import a.b`, `import a, b`, `from a.b`
+/// or `from a import b, c`. Relative imports and aliases aren't completed.
+fn parse_import(line: &str) -> Option<ImportContext> {
+    let line = line.trim_start();
+    if let Some(rest) = line.strip_prefix("import ") {
+        return module_path(rest.rsplit(',').next()?.trim_start());
+    }
+    let rest = line.strip_prefix("from ")?;
+    let Some(import) = rest.find(" import ") else {
+        return module_path(rest);
+    };
+    let package = rest[..import].trim();
+    let names = &rest[import + " import ".len()..];
+    let name = names.rsplit([',', '(']).next()?.trim_start();
+    if package.starts_with('.') || !is_dotted_name(package) || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        return None;
+    }
+    Some(ImportContext { package: Some(package.to_string()), typed: name.to_string(), modules_only: false })
+}
+
+/// A dotted module name being typed: its package is everything before the last dot
+fn module_path(item: &str) -> Option<ImportContext> {
+    if item.starts_with('.') || !(item.is_empty() || is_dotted_name(item.trim_end_matches('.'))) {
+        return None;
+    }
+    let package = item.rsplit_once('.').map(|(package, _)| package.to_string());
+    Some(ImportContext { package, typed: item.to_string(), modules_only: true })
+}
+
+fn is_dotted_name(text: &str) -> bool {
+    !text.is_empty() && text.split('.').all(|part| !part.is_empty() && part.chars().all(|c| c.is_alphanumeric() || c == '_'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context(package: Option<&str>, typed: &str, modules_only: bool) -> Option<ImportContext> {
+        Some(ImportContext { package: package.map(str::to_string), typed: typed.to_string(), modules_only })
+    }
+
+    #[test]
+    fn test_parse_import() {
+        assert_eq!(parse_import("import "), context(None, "", true));
+        assert_eq!(parse_import("    import os, pan"), context(None, "pan", true));
+        assert_eq!(parse_import("import os.pa"), context(Some("os"), "os.pa", true));
+        assert_eq!(parse_import("from xml.etree."), context(Some("xml.etree"), "xml.etree.", true));
+        assert_eq!(parse_import("from os import pa"), context(Some("os"), "pa", false));
+        assert_eq!(parse_import("from json import (dump, lo"), context(Some("json"), "lo", false));
+        assert_eq!(parse_import("import numpy as n"), None);
+        assert_eq!(parse_import("from . import x"), None);
+        assert_eq!(parse_import("x = 1"), None);
+    }
+}
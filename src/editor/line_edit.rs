@@ -0,0 +1,421 @@
+use crate::buffer::TextChange;
+use crate::cell::{cell_type_from_header, CellType, CELL_DELIMITER};
+use ropey::Rope;
+use super::multi_cursor::remap_position;
+use super::Editor;
+
+impl Editor {
+    /// First and last line of the selection, or the cursor line. A selection ending
+    /// at the start of a line doesn't include that line.
+    fn selected_lines(&self) -> (usize, usize) {
+        match self.get_selection() {
+            Some((start, end)) => {
+                let first = self.buffer.byte_to_line(start);
+                let last = self.buffer.byte_to_line(end);
+                if last > first && self.buffer.line_to_byte(last) == end {
+                    (first, last - 1)
+                } else {
+                    (first, last)
+                }
+            }
+            None => {
+                let line = self.buffer.byte_to_line(self.cursor);
+                (line, line)
+            }
+        }
+    }
+
+    /// Replace lines `first..=last` with `lines` as one undo step and select from `anchor` to `cursor`
+    fn replace_lines(&mut self, first: usize, last: usize, lines: &[String], anchor: Option<usize>, cursor: usize) {
+        let (start, end) = line_range(self.buffer.rope(), first, last);
+        let old = self.buffer.rope().byte_slice(start..end).to_string();
+        let mut text = lines.join("\n");
+        if old.ends_with('\n') {
+            text.push('\n');
+        }
+        if text != old {
+            self.buffer.replace(start, end, &text, self.cursor, cursor);
+            self.modified = true;
+            self.reinit_syntax_highlighting();
+        }
+        self.cursor = self.ensure_char_boundary(cursor.min(self.buffer.len_bytes()));
+        self.selection_start = anchor.map(|a| self.ensure_char_boundary(a.min(self.buffer.len_bytes())));
+        self.preferred_column = None;
+    }
+
+    /// Apply non-overlapping edits (position, bytes removed, text inserted) as one undo
+    /// step. The cursor and selection move with the text around them.
+    fn apply_edits(&mut self, mut edits: Vec<(usize, usize, String)>) {
+        if edits.is_empty() {
+            return;
+        }
+        edits.sort_by_key(|&(pos, _, _)| pos);
+        let start = edits[0].0;
+        let end = edits.iter().map(|(pos, removed, _)| pos + removed).max().unwrap_or(start);
+        let mut text = self.buffer.rope().byte_slice(start..end).to_string();
+
+        let mut cursor = self.cursor;
+        let mut anchor = self.selection_start;
+        for (pos, removed, inserted) in edits.iter().rev() {
+            text.replace_range(pos - start..pos - start + removed, inserted);
+            let change = TextChange { pos: *pos, removed: *removed, inserted: inserted.len() };
+            cursor = remap_position(cursor, &change);
+            anchor = anchor.map(|a| remap_position(a, &change));
+        }
+
+        self.buffer.replace(start, end, &text, self.cursor, cursor);
+        self.cursor = cursor;
+        self.selection_start = anchor;
+        self.preferred_column = None;
+        self.modified = true;
+        self.reinit_syntax_highlighting();
+    }
+
+    /// Comment out the selected lines with the language's line comment (`--` in SQL
+    /// cells), or uncomment them if they all are comments already
+    pub fn toggle_comment(&mut self) {
+        let language_prefix = self.syntax.language().line_comment();
+        let (first, last) = self.selected_lines();
+        let edits = comment_edits(self.buffer.rope(), first, last, language_prefix);
+        if edits.is_empty() && language_prefix.is_none() {
+            self.status_message = Some(("No line comments in this file type".to_string(), true));
+            return;
+        }
+        self.apply_edits(edits);
+    }
+
+    /// Wrap the selection (or the current line) in a block comment, or unwrap it.
+    /// Languages without block comments comment out each line instead.
+    pub fn toggle_block_comment(&mut self) {
+        let Some((open, close)) = self.syntax.language().block_comment() else {
+            self.toggle_comment();
+            return;
+        };
+        let (start, end) = match self.get_selection() {
+            Some(range) => range,
+            None => {
+                let line = self.buffer.byte_to_line(self.cursor);
+                let line_start = self.buffer.line_to_byte(line);
+                let text = self.buffer.line(line);
+                let content = text.trim_end();
+                (line_start + (content.len() - content.trim_start().len()), line_start + content.len())
+            }
+        };
+        // A selection of whole lines keeps its last line break outside the comment
+        let mut text = self.buffer.rope().byte_slice(start..end).to_string();
+        let end = start + text.trim_end_matches('\n').len();
+        text.truncate(end - start);
+        let inner = text.trim();
+        if inner.is_empty() {
+            return;
+        }
+
+        if inner.len() >= open.len() + close.len() && inner.starts_with(open) && inner.ends_with(close) {
+            let open_pos = start + (text.len() - text.trim_start().len());
+            let close_end = start + text.trim_end().len();
+            let body = &inner[open.len()..inner.len() - close.len()];
+            let open_len = open.len() + body.starts_with(' ') as usize;
+            let close_len = close.len() + (body.len() > 1 && body.ends_with(' ')) as usize;
+            self.apply_edits(vec![
+                (open_pos, open_len, String::new()),
+                (close_end - close_len, close_len, String::new()),
+            ]);
+        } else {
+            self.apply_edits(vec![
+                (start, 0, format!("{} ", open)),
+                (end, 0, format!(" {}", close)),
+            ]);
+        }
+    }
+
+    /// Insert a copy of the selection after it, or of the current line(s) below them
+    pub fn duplicate_lines(&mut self) {
+        if let Some((start, end)) = self.get_selection() {
+            let text = self.buffer.rope().byte_slice(start..end).to_string();
+            let len = text.len();
+            self.apply_edits(vec![(end, 0, text)]);
+            // Select the copy
+            self.selection_start = Some(end);
+            self.cursor = end + len;
+            return;
+        }
+
+        let (first, last) = self.selected_lines();
+        let (end, text) = duplicate_edit(self.buffer.rope(), first, last);
+        let len = text.len();
+        let cursor = self.cursor;
+        self.apply_edits(vec![(end, 0, text)]);
+        self.cursor = cursor + len;
+    }
+
+    /// Delete the current line, or every line the selection touches
+    pub fn delete_lines(&mut self) {
+        let (first, last) = self.selected_lines();
+        let (start, end) = delete_range(self.buffer.rope(), first, last);
+        let column = self.cursor - self.buffer.line_to_byte(self.buffer.byte_to_line(self.cursor));
+        if start == end {
+            return;
+        }
+
+        self.selection_start = None;
+        self.apply_edits(vec![(start, end - start, String::new())]);
+
+        // Keep the column on the line that took the deleted lines' place
+        let line = first.min(self.buffer.len_lines().saturating_sub(1));
+        let line_start = self.buffer.line_to_byte(line);
+        let line_len = self.buffer.line(line).trim_end_matches('\n').len();
+        self.cursor = self.ensure_char_boundary(line_start + column.min(line_len));
+    }
+
+    /// Join the selected lines (or the current line and the next) with single spaces
+    pub fn join_lines(&mut self) {
+        let (first, mut last) = self.selected_lines();
+        if first == last {
+            if last + 1 >= self.buffer.len_lines() {
+                return;
+            }
+            last += 1;
+        }
+
+        let edits = join_edits(self.buffer.rope(), first, last);
+        let join_point = edits[0].0;
+        let had_selection = self.get_selection().is_some();
+        self.apply_edits(edits);
+        if !had_selection {
+            self.cursor = join_point;
+        }
+    }
+
+    /// Move the selected lines (or the current line) up or down past the next line
+    pub fn move_lines(&mut self, up: bool) {
+        let (first, last) = self.selected_lines();
+        let Some((range_first, range_last, lines)) = moved_lines(self.buffer.rope(), first, last, up) else {
+            return;
+        };
+        let other = if up { first - 1 } else { last + 1 };
+
+        // The cursor and selection move by the length of the line they passed
+        let other_len = self.buffer.line(other).trim_end_matches('\n').len() + 1;
+        let shift = |pos: usize| if up { pos - other_len } else { pos + other_len };
+        let anchor = self.selection_start.map(shift);
+        let cursor = shift(self.cursor);
+        self.replace_lines(range_first, range_last, &lines, anchor, cursor);
+    }
+
+    /// Sort the selected lines
+    pub fn sort_lines(&mut self) {
+        let (first, last) = self.selected_lines();
+        if first == last {
+            self.status_message = Some(("Select the lines to sort".to_string(), false));
+            return;
+        }
+        let lines = sorted_lines(self.buffer.rope(), first, last);
+
+        let start = self.buffer.line_to_byte(first);
+        let block_len = lines.iter().map(|line| line.len() + 1).sum::<usize>() - 1;
+        self.replace_lines(first, last, &lines, Some(start), start + block_len);
+        self.status_message = Some((format!("Sorted {} lines", last - first + 1), false));
+    }
+}
+
+/// Byte range of lines `first..=last`, with the newline after the last one if it has one
+fn line_range(rope: &Rope, first: usize, last: usize) -> (usize, usize) {
+    (rope.line_to_byte(first), rope.line_to_byte((last + 1).min(rope.len_lines())))
+}
+
+/// Lines `first..=last` without their newlines
+fn lines_text(rope: &Rope, first: usize, last: usize) -> Vec<String> {
+    (first..=last).map(|line| rope.line(line).to_string().trim_end_matches('\n').to_string()).collect()
+}
+
+/// Line comment for each of lines `first..=last`: `--` in the body of a `##$$ sql` cell and
+/// the file's own elsewhere. Cell delimiters get none, so they stay cell delimiters.
+fn comment_prefixes<'a>(rope: &Rope, first: usize, last: usize, language_prefix: Option<&'a str>) -> Vec<Option<&'a str>> {
+    let header_is_sql = |line: usize| {
+        let text = rope.line(line).to_string();
+        text.trim_start().starts_with(CELL_DELIMITER)
+            .then(|| matches!(cell_type_from_header(&text), CellType::Sql { .. }))
+    };
+    let mut in_sql = (0..first).rev().find_map(header_is_sql).unwrap_or(false);
+    (first..=last).map(|line| match header_is_sql(line) {
+        Some(sql) => {
+            in_sql = sql;
+            None
+        }
+        None if in_sql => Some("--"),
+        None => language_prefix,
+    }).collect()
+}
+
+/// Edits that comment out lines `first..=last`, or uncomment them if they all are comments
+/// already. Blank lines and lines without a comment syntax are left alone.
+fn comment_edits(rope: &Rope, first: usize, last: usize, language_prefix: Option<&str>) -> Vec<(usize, usize, String)> {
+    let lines = lines_text(rope, first, last);
+    let prefixes = comment_prefixes(rope, first, last, language_prefix);
+    let code: Vec<(usize, &String, &str)> = lines.iter().zip(prefixes).enumerate()
+        .filter_map(|(i, (line, prefix))| Some((i, line, prefix?)))
+        .filter(|(_, line, _)| !line.trim().is_empty())
+        .collect();
+
+    let leading = |line: &str| line.len() - line.trim_start().len();
+    let indent = code.iter().map(|(_, line, _)| leading(line)).min().unwrap_or(0);
+    let commented = code.iter().all(|(_, line, prefix)| line.trim_start().starts_with(prefix));
+
+    code.iter().map(|&(i, line, prefix)| {
+        let line_start = rope.line_to_byte(first + i);
+        if commented {
+            let at = leading(line);
+            let space = line[at + prefix.len()..].starts_with(' ');
+            (line_start + at, prefix.len() + space as usize, String::new())
+        } else {
+            (line_start + indent, 0, format!("{} ", prefix))
+        }
+    }).collect()
+}
+
+/// Where to insert a copy of lines `first..=last`, and the text to insert
+fn duplicate_edit(rope: &Rope, first: usize, last: usize) -> (usize, String) {
+    let (start, end) = line_range(rope, first, last);
+    let mut text = rope.byte_slice(start..end).to_string();
+    if !text.ends_with('\n') {
+        // The last line of the file gets its copy after a new line break
+        text.insert(0, '\n');
+    }
+    (end, text)
+}
+
+/// Byte range that deleting lines `first..=last` removes
+fn delete_range(rope: &Rope, first: usize, last: usize) -> (usize, usize) {
+    let (mut start, end) = line_range(rope, first, last);
+    // Without a newline after the last line, remove the one before the first
+    if end > start && rope.byte(end - 1) != b'\n' && start > 0 {
+        start -= 1;
+    }
+    (start, end)
+}
+
+/// Edits joining lines `first..=last` with single spaces (none before a closing bracket)
+fn join_edits(rope: &Rope, first: usize, last: usize) -> Vec<(usize, usize, String)> {
+    (first..last).map(|line| {
+        let text = rope.line(line).to_string();
+        let content_end = rope.line_to_byte(line) + text.trim_end().len();
+        let next = rope.line(line + 1).to_string();
+        let next = next.trim_end_matches('\n');
+        let next_content = next.trim_start();
+        let next_start = rope.line_to_byte(line + 1) + (next.len() - next_content.len());
+        let separator = if text.trim().is_empty() || next_content.is_empty() || next_content.starts_with([')', ']', '}']) {
+            ""
+        } else {
+            " "
+        };
+        (content_end, next_start - content_end, separator.to_string())
+    }).collect()
+}
+
+/// Lines `first..=last` moved up or down past the next line: the range of lines that
+/// changes and its new text, or None at the start or end of the file
+fn moved_lines(rope: &Rope, first: usize, last: usize, up: bool) -> Option<(usize, usize, Vec<String>)> {
+    // The empty line after a final newline isn't a line to swap with
+    let is_empty = |line: usize| rope.line(line).len_bytes() == 0;
+    let blocked = is_empty(last) || if up {
+        first == 0
+    } else {
+        last + 1 >= rope.len_lines() || is_empty(last + 1)
+    };
+    if blocked {
+        return None;
+    }
+
+    let (range_first, range_last) = if up { (first - 1, last) } else { (first, last + 1) };
+    let mut lines = lines_text(rope, range_first, range_last);
+    if up {
+        lines.rotate_left(1);
+    } else {
+        lines.rotate_right(1);
+    }
+    Some((range_first, range_last, lines))
+}
+
+/// Lines `first..=last` in sorted order
+fn sorted_lines(rope: &Rope, first: usize, last: usize) -> Vec<String> {
+    let mut lines = lines_text(rope, first, last);
+    lines.sort();
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(text: &str, mut edits: Vec<(usize, usize, String)>) -> String {
+        let mut text = text.to_string();
+        edits.sort_by_key(|&(pos, _, _)| std::cmp::Reverse(pos));
+        for (pos, removed, inserted) in edits {
+            text.replace_range(pos..pos + removed, &inserted);
+        }
+        text
+    }
+
+    #[test]
+    fn test_comment_edits() {
+        let text = "def f():\n    x = 1\n\n    return x\n";
+        let rope = Rope::from_str(text);
+        let commented = apply(text, comment_edits(&rope, 0, 3, Some("#")));
+        assert_eq!(commented, "# def f():\n#     x = 1\n\n#     return x\n");
+        let rope = Rope::from_str(&commented);
+        assert_eq!(apply(&commented, comment_edits(&rope, 0, 3, Some("#"))), text);
+
+        // Cell delimiters are never commented, and SQL cell bodies use `--`
+        let text = "x = 1\n##$$ sql duckdb\nSELECT 1\n##$$\ny = 2\n";
+        let rope = Rope::from_str(text);
+        let commented = apply(text, comment_edits(&rope, 0, 4, Some("#")));
+        assert_eq!(commented, "# x = 1\n##$$ sql duckdb\n-- SELECT 1\n##$$\n# y = 2\n");
+        let rope = Rope::from_str(&commented);
+        assert_eq!(apply(&commented, comment_edits(&rope, 0, 4, Some("#"))), text);
+        assert_eq!(comment_prefixes(&rope, 2, 2, Some("#")), vec![Some("--")]);
+        assert!(comment_edits(&Rope::from_str("##$$\n"), 0, 0, Some("#")).is_empty());
+    }
+
+    #[test]
+    fn test_duplicate_and_delete() {
+        let text = "a\nb\nc";
+        let rope = Rope::from_str(text);
+        assert_eq!(duplicate_edit(&rope, 0, 1), (4, "a\nb\n".to_string()));
+        // The last line has no newline, so its copy goes after a new one
+        assert_eq!(duplicate_edit(&rope, 2, 2), (5, "\nc".to_string()));
+
+        assert_eq!(delete_range(&rope, 0, 0), (0, 2));
+        assert_eq!(delete_range(&rope, 1, 2), (1, 5));
+        let rope = Rope::from_str("x = 1\n##$$\ny = 2\n");
+        assert_eq!(delete_range(&rope, 1, 1), (6, 11));
+    }
+
+    #[test]
+    fn test_join_edits() {
+        let text = "f(a,\n    b\n)\n";
+        let rope = Rope::from_str(text);
+        assert_eq!(apply(text, join_edits(&rope, 0, 2)), "f(a, b)\n");
+        let text = "x = 1  \n\n  y\n";
+        let rope = Rope::from_str(text);
+        assert_eq!(apply(text, join_edits(&rope, 0, 2)), "x = 1y\n");
+    }
+
+    #[test]
+    fn test_moved_lines() {
+        let rope = Rope::from_str("a\n##$$\nb\n");
+        let lines = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        // A line moves past a cell delimiter like any other line
+        assert_eq!(moved_lines(&rope, 0, 0, false), Some((0, 1, lines(&["##$$", "a"]))));
+        assert_eq!(moved_lines(&rope, 1, 2, true), Some((0, 2, lines(&["##$$", "b", "a"]))));
+        assert_eq!(moved_lines(&rope, 0, 0, true), None);
+        // The empty line after the final newline isn't swapped with
+        assert_eq!(moved_lines(&rope, 2, 2, false), None);
+    }
+
+    #[test]
+    fn test_sorted_lines() {
+        let rope = Rope::from_str("import sys\nimport os\nimport json\n");
+        assert_eq!(sorted_lines(&rope, 0, 2), vec!["import json", "import os", "import sys"]);
+        assert_eq!(sorted_lines(&rope, 0, 1), vec!["import os", "import sys"]);
+    }
+}
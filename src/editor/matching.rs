@@ -1,95 +1,46 @@
+use crate::syntax::Language;
+use super::auto_indent::closing_bracket;
+use super::code_scan::CodeScanner;
 use super::Editor;
 
 impl Editor {
-    /// Find matching opening bracket scanning backward
-    fn find_matching_open(&self, bytes: &[u8], close_pos: usize, close_char: char) -> Option<usize> {
-        let open_char = match close_char {
-            ')' => '(',
-            ']' => '[',
-            '}' => '{',
-            _ => return None,
-        };
-
-        let mut depth = 1;
-        let mut pos = close_pos;
-
-        while pos > 0 {
-            pos -= 1;
-            let ch = bytes[pos] as char;
-
-            if ch == close_char {
-                depth += 1;
-            } else if ch == open_char {
-                depth -= 1;
-                if depth == 0 {
-                    return Some(pos);
-                }
-            }
-        }
-
-        None
-    }
-
-    /// Find matching closing bracket scanning forward
-    fn find_matching_close(&self, bytes: &[u8], open_pos: usize, open_char: char) -> Option<usize> {
-        let close_char = match open_char {
-            '(' => ')',
-            '[' => ']',
-            '{' => '}',
-            _ => return None,
-        };
-
-        let mut depth = 1;
-        let mut pos = open_pos + 1;
-
-        while pos < bytes.len() {
-            let ch = bytes[pos] as char;
-
-            if ch == open_char {
-                depth += 1;
-            } else if ch == close_char {
-                depth -= 1;
-                if depth == 0 {
-                    return Some(pos);
-                }
-            }
-
-            pos += 1;
-        }
-
-        None
-    }
-
+    /// Find the bracket paired with the one under the cursor. Brackets can't pair across
+    /// cells, so only the cursor's cell is scanned; in Python, strings and comments are skipped.
     fn find_matching_brackets(&mut self) {
         self.matching_brackets = None;
 
-        if self.cursor > self.buffer.len_bytes() {
+        // Only check character at cursor position (when block cursor is covering the bracket)
+        if self.cursor >= self.buffer.len_bytes()
+            || !matches!(self.buffer.rope().byte(self.cursor), b'(' | b'[' | b'{' | b')' | b']' | b'}')
+        {
             return;
         }
 
-        let text = self.buffer.to_string();
-        let bytes = text.as_bytes();
-
-        // Only check character at cursor position (when block cursor is covering the bracket)
-        if self.cursor < bytes.len() {
-            let char_at_cursor = bytes[self.cursor] as char;
-
-            // Check for opening bracket
-            if matches!(char_at_cursor, '(' | '[' | '{') {
-                if let Some(match_pos) = self.find_matching_close(bytes, self.cursor, char_at_cursor) {
-                    self.matching_brackets = Some((self.cursor, match_pos));
-                    return;
-                }
-            }
-
-            // Check for closing bracket
-            if matches!(char_at_cursor, ')' | ']' | '}') {
-                if let Some(match_pos) = self.find_matching_open(bytes, self.cursor, char_at_cursor) {
-                    self.matching_brackets = Some((match_pos, self.cursor));
-                    return;
+        let line = self.buffer.byte_to_line(self.cursor);
+        let start = self.buffer.line_to_byte(self.scan_start_line(line));
+        let end = self.buffer.line_to_byte(self.scan_end_line(line) + 1);
+        let text = self.buffer.rope().byte_slice(start..end).to_string();
+        let cursor = self.cursor - start;
+
+        let mut open: Vec<(usize, u8)> = Vec::new();
+        let mut pair = None;
+        let mut visit = |i: usize, b: u8| match b {
+            b'(' | b'[' | b'{' => open.push((i, b)),
+            b')' | b']' | b'}' if open.last().is_some_and(|&(_, o)| closing_bracket(o as char) == b as char) => {
+                if let Some((o, _)) = open.pop() {
+                    if o == cursor || i == cursor {
+                        pair = Some((start + o, start + i));
+                    }
                 }
             }
+            _ => {}
+        };
+        if self.syntax.language() == Language::Python {
+            CodeScanner::default().scan(&text, &mut visit);
+        } else {
+            text.bytes().enumerate().for_each(|(i, b)| visit(i, b));
         }
+        self.matching_brackets = pair;
     }
 
     /// Find all occurrences of the selected text in the buffer
@@ -2,12 +2,14 @@ use crate::buffer::Buffer;
 use crate::commands::Command;
 use crate::syntax::SyntaxHighlighter;
 use crate::cell::{Cell, parse_cells};
-use crate::kernel::Kernel;
+use crate::kernel::{CompletionItem, Kernel};
+use crate::file_format::{FileFormat, LineFormat};
 use arboard::Clipboard;
+use std::collections::HashMap;
 use std::fs;
 use std::io;
 use std::path::{Path, PathBuf};
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime};
 use unicode_width::UnicodeWidthChar;
 
 // Submodules
@@ -17,6 +19,26 @@ mod mouse;
 mod viewport;
 mod matching;
 mod notebook;
+mod disk_watch;
+mod display;
+mod cell_edit;
+mod folding;
+mod wrap;
+mod multi_cursor;
+mod auto_indent;
+mod auto_pair;
+mod code_scan;
+mod line_edit;
+mod navigation;
+mod undo_history;
+mod signature_help;
+mod import_completion;
+
+pub use disk_watch::DiskChange;
+pub use display::{DisplayRow, OutputStyle};
+pub use signature_help::CallContext;
+pub use import_completion::ImportContext;
+pub use notebook::ColumnContext;
 
 /// Token type for word boundary detection
 #[derive(Debug, PartialEq, Copy, Clone)]
@@ -47,8 +69,7 @@ pub struct Editor {
     file_path: Option<PathBuf>,
     modified: bool,
     viewport_offset: (usize, usize),  // (row, col) offset for scrolling
-    last_saved_undo_len: usize,       // Track save point for modified flag
-    clipboard: Clipboard,             // System clipboard
+    clipboard: Option<Clipboard>,     // System clipboard (None without a display)
     mouse_selecting: bool,            // Track if we're actively selecting with mouse
     last_click_time: Option<Instant>, // Track time of last click for double/triple click
     last_click_position: Option<usize>, // Track position of last click
@@ -56,6 +77,15 @@ pub struct Editor {
     preferred_column: Option<usize>,  // Preferred column for vertical movement
     syntax: SyntaxHighlighter,       // Syntax highlighting state
     read_only: bool,                  // Whether the file is read-only
+    file_format: FileFormat,          // Line endings, indentation and BOM to restore on save
+    line_formats: Vec<(usize, LineFormat)>, // Starts of the lines written differently from file_format on disk
+    line_formats_revision: u64,       // Buffer revision the per-line formats were last synced with
+    last_swap_time: Option<Instant>,  // When the swap file was last written
+    last_swap_hash: u64,              // Hash of the content in the swap file (0 = no swap file)
+    disk_mtime: Option<SystemTime>,   // Modification time of the file when last read or written
+    disk_hash: u64,                   // Hash of the file content when last read or written
+    disk_changed: bool,               // File changed on disk while the buffer had unsaved edits
+    last_disk_check: Option<Instant>, // When the file on disk was last checked for changes
     pub status_message: Option<(String, bool)>, // Status bar message (text, is_error)
     status_message_persistent: bool, // Whether status message should persist until cleared
     matching_brackets: Option<(usize, usize)>, // Positions of matching brackets
@@ -64,8 +94,26 @@ pub struct Editor {
     current_find_match: Option<usize>, // Index of the current find match
     // REPL/Notebook fields
     cells: Vec<Cell>,                  // Parsed cells for notebook mode
+    cells_revision: Option<u64>,       // Buffer revision the cells were parsed from
+    show_gutter: bool,                 // Show line numbers and cell status in a gutter
+    inline_outputs: bool,              // Draw cell outputs below each cell in the editor
+    outputs_version: u64,              // Bumped when cell outputs or their state change
+    output_layout: Option<display::OutputLayout>, // Inline outputs laid out for the last draw
+    soft_wrap: bool,                   // Wrap long lines at the window width instead of scrolling
+    auto_pair: bool,                   // Close brackets and quotes as they are typed
+    wrap_width: usize,                 // Columns available to wrapped text
+    wrap_rows: Option<wrap::WrapRows>, // Rows each line wraps into, while soft wrap is on
+    folds: Vec<folding::Fold>,         // Folded cells and blocks, sorted by position
+    folds_revision: u64,               // Buffer revision the folds were last synced with
+    extra_cursors: Vec<multi_cursor::Caret>, // Cursors besides the primary one
+    extra_cursors_revision: u64,       // Buffer revision the extra cursors were last placed at
+    block_anchor: Option<(usize, usize)>, // (line, column) where a block selection started
+    nav_back: Vec<usize>,              // Positions before recent jumps, newest last (Alt+Left)
+    nav_forward: Vec<usize>,           // Positions left by going back (Alt+Right)
+    nav_revision: u64,                 // Buffer revision the remembered positions are valid for
     kernel: Option<Box<dyn Kernel>>,   // Active Python kernel
     repl_mode: bool,                   // Whether we're in REPL mode
+    module_cache: HashMap<(String, Option<String>), Vec<CompletionItem>>, // Importable modules by (interpreter, package)
 }
 
 impl Editor {
@@ -78,8 +126,7 @@ impl Editor {
             file_path: None,
             modified: false,
             viewport_offset: (0, 0),
-            last_saved_undo_len: 0,
-            clipboard: Clipboard::new().expect("Failed to access clipboard"),
+            clipboard: Clipboard::new().ok(),
             mouse_selecting: false,
             last_click_time: None,
             last_click_position: None,
@@ -87,19 +134,47 @@ impl Editor {
             preferred_column: None,
             syntax: SyntaxHighlighter::new(),
             read_only: false,
+            file_format: FileFormat::default(),
+            line_formats: Vec::new(),
+            line_formats_revision: 0,
+            last_swap_time: None,
+            last_swap_hash: 0,
+            disk_mtime: None,
+            disk_hash: 0,
+            disk_changed: false,
+            last_disk_check: None,
             status_message: None,
             matching_brackets: None,
             matching_text_positions: Vec::new(),
             find_matches: Vec::new(),
             current_find_match: None,
             cells: Vec::new(),
+            cells_revision: None,
+            show_gutter: false,
+            inline_outputs: false,
+            outputs_version: 0,
+            output_layout: None,
+            soft_wrap: false,
+            auto_pair: false,
+            wrap_width: 80,
+            wrap_rows: None,
+            folds: Vec::new(),
+            folds_revision: 0,
+            extra_cursors: Vec::new(),
+            extra_cursors_revision: 0,
+            block_anchor: None,
+            nav_back: Vec::new(),
+            nav_forward: Vec::new(),
+            nav_revision: 0,
             kernel: None,
             repl_mode: false,
+            module_cache: HashMap::new(),
             status_message_persistent: false,
         }
     }
 
-    pub fn execute(&mut self, cmd: Command) -> io::Result<()> {
+    /// Run a command at the primary cursor
+    fn execute_at_cursor(&mut self, cmd: Command) -> io::Result<()> {
         // Clear non-persistent status messages on user action
         if !self.status_message_persistent {
             if matches!(cmd,
@@ -125,6 +200,9 @@ impl Editor {
         
         // Track if cursor moved to update viewport
         let mut cursor_moved = false;
+        let cursor_before_command = self.cursor;
+        self.sync_folds();
+        self.sync_line_formats();
         
         // For non-selection movement commands, clear selection
         // Note: MoveLeft, MoveRight, MoveUp, and MoveDown handle their own selection clearing
@@ -139,6 +217,12 @@ impl Editor {
         }
         
         match cmd {
+            // Brackets and quotes are closed as they are typed, typed over when the closing
+            // half is already there, or wrapped around the selection
+            Command::InsertChar(c) if self.insert_paired(c) => {
+                self.preferred_column = None;
+            }
+
             Command::InsertChar(c) => {
                 // Delete selection first if any
                 self.delete_selection();
@@ -168,43 +252,33 @@ impl Editor {
                 // Update syntax highlighting for the modified line
                 let line = self.buffer.byte_to_line(self.cursor);
                 self.syntax.line_modified(line);
+
+                // `else:`, `except:` and friends go back to the block they continue
+                if c == ':' {
+                    self.auto_dedent_line();
+                }
             }
             
             Command::InsertNewline => {
                 // Delete selection first if any
                 self.delete_selection();
                 
-                // Get the current line to check for indentation
                 let current_line = self.buffer.byte_to_line(self.cursor);
-                let line_start = self.buffer.line_to_byte(current_line);
-                let is_at_line_start = self.cursor == line_start;
-                
-                let mut new_text = String::from("\n");
-                
-                // Only add indentation if cursor is NOT at the start of the line
-                if !is_at_line_start {
-                    let line_text = self.buffer.line(current_line);
-                    
-                    // Count leading spaces
-                    let indent_count = line_text.chars()
-                        .take_while(|&c| c == ' ')
-                        .count();
-                    
-                    // Add indentation spaces
-                    for _ in 0..indent_count {
-                        new_text.push(' ');
-                    }
-                }
+
+                // Keep the indentation of the line (none at the start of a line), adjusted
+                // for Python blocks and brackets
+                let (new_text, cursor_offset) = self.newline_text();
+                let new_lines = new_text.matches('\n').count();
                 
                 let cursor_before = self.cursor;
-                self.buffer.insert(self.cursor, &new_text, cursor_before, self.cursor + new_text.len());
-                self.cursor += new_text.len();
+                self.buffer.insert(self.cursor, &new_text, cursor_before, self.cursor + cursor_offset);
+                self.cursor += cursor_offset;
                 self.modified = true;
                 self.preferred_column = None; // Clear preferred column
                 
                 // Update syntax highlighting - line was inserted
                 self.syntax.line_modified(current_line); // Mark current line as dirty since its content changed
-                self.syntax.lines_inserted(current_line + 1, 1);
+                self.syntax.lines_inserted(current_line + 1, new_lines);
             }
             
             Command::InsertTab => {
@@ -224,7 +298,9 @@ impl Editor {
             
             Command::Backspace => {
                 // If there's a selection, delete it
-                if !self.delete_selection() {
+                if self.delete_empty_pair() {
+                    // Removed both halves of an empty pair
+                } else if !self.delete_selection() {
                     // Otherwise delete character before cursor
                     if self.cursor > 0 {
                         let cursor_before = self.cursor;
@@ -326,7 +402,9 @@ impl Editor {
                     cursor_moved = true;
                 } else {
                     let current_line = self.buffer.byte_to_line(self.cursor);
-                    if current_line > 0 {
+                    if self.soft_wrap && self.move_visual_line(true) {
+                    // Moved by one row of wrapped text
+                } else if let Some(new_line) = self.visible_line_above(current_line) {
                     // Set preferred column if not already set
                     if self.preferred_column.is_none() {
                         let (_, col) = self.cursor_position();
@@ -336,7 +414,6 @@ impl Editor {
                     // Use preferred column as target
                     let target_display_col = self.preferred_column.unwrap();
                     
-                    let new_line = current_line - 1;
                     let new_line_start = self.buffer.line_to_byte(new_line);
                     let new_line_text = self.buffer.line(new_line);
                     
@@ -398,7 +475,9 @@ impl Editor {
                     cursor_moved = true;
                 } else {
                     let current_line = self.buffer.byte_to_line(self.cursor);
-                    if current_line < self.buffer.len_lines() - 1 {
+                    if self.soft_wrap && self.move_visual_line(false) {
+                    // Moved by one row of wrapped text
+                } else if let Some(new_line) = self.visible_line_below(current_line) {
                     // Set preferred column if not already set
                     if self.preferred_column.is_none() {
                         let (_, col) = self.cursor_position();
@@ -408,7 +487,6 @@ impl Editor {
                     // Use preferred column as target
                     let target_display_col = self.preferred_column.unwrap();
                     
-                    let new_line = current_line + 1;
                     let new_line_start = self.buffer.line_to_byte(new_line);
                     let new_line_text = self.buffer.line(new_line);
                     
@@ -537,7 +615,9 @@ impl Editor {
                     self.selection_start = Some(self.cursor);
                 }
                 let current_line = self.buffer.byte_to_line(self.cursor);
-                if current_line > 0 {
+                if self.soft_wrap && self.move_visual_line(true) {
+                    // Moved by one row of wrapped text
+                } else if let Some(new_line) = self.visible_line_above(current_line) {
                     // Set preferred column if not already set
                     if self.preferred_column.is_none() {
                         let (_, col) = self.cursor_position();
@@ -547,7 +627,6 @@ impl Editor {
                     // Use preferred column as target
                     let target_display_col = self.preferred_column.unwrap();
                     
-                    let new_line = current_line - 1;
                     let new_line_start = self.buffer.line_to_byte(new_line);
                     let new_line_text = self.buffer.line(new_line);
                     
@@ -606,7 +685,9 @@ impl Editor {
                     self.selection_start = Some(self.cursor);
                 }
                 let current_line = self.buffer.byte_to_line(self.cursor);
-                if current_line < self.buffer.len_lines() - 1 {
+                if self.soft_wrap && self.move_visual_line(false) {
+                    // Moved by one row of wrapped text
+                } else if let Some(new_line) = self.visible_line_below(current_line) {
                     // Set preferred column if not already set
                     if self.preferred_column.is_none() {
                         let (_, col) = self.cursor_position();
@@ -616,7 +697,6 @@ impl Editor {
                     // Use preferred column as target
                     let target_display_col = self.preferred_column.unwrap();
                     
-                    let new_line = current_line + 1;
                     let new_line_start = self.buffer.line_to_byte(new_line);
                     let new_line_text = self.buffer.line(new_line);
                     
@@ -999,7 +1079,7 @@ impl Editor {
             // Clipboard operations
             Command::Copy => {
                 if let Some(text) = self.get_selected_text() {
-                    if let Err(e) = self.clipboard.set_text(text) {
+                    if let Err(e) = self.set_clipboard_text(text) {
                         eprintln!("Failed to copy to clipboard: {}", e);
                     }
                 }
@@ -1007,7 +1087,7 @@ impl Editor {
             
             Command::Cut => {
                 if let Some(text) = self.get_selected_text() {
-                    if let Err(e) = self.clipboard.set_text(text) {
+                    if let Err(e) = self.set_clipboard_text(text) {
                         eprintln!("Failed to copy to clipboard: {}", e);
                     } else {
                         self.delete_selection();
@@ -1016,7 +1096,7 @@ impl Editor {
             }
             
             Command::Paste => {
-                match self.clipboard.get_text() {
+                match self.clipboard_text() {
                     Ok(text) => {
                         // Delete selection first if any
                         self.delete_selection();
@@ -1045,11 +1125,75 @@ impl Editor {
                 }
             }
             
+            Command::ToggleSoftWrap => {
+                self.toggle_soft_wrap();
+            }
+
+            Command::ToggleFold => {
+                self.toggle_fold();
+            }
+
+            Command::ToggleAutoPair => {
+                self.toggle_auto_pair();
+            }
+
+            Command::AddNextOccurrence => {
+                self.add_next_occurrence();
+            }
+
+            Command::ToggleComment => {
+                self.toggle_comment();
+            }
+
+            Command::ToggleBlockComment => {
+                self.toggle_block_comment();
+            }
+
+            Command::DuplicateLines => {
+                self.duplicate_lines();
+            }
+
+            Command::DeleteLines => {
+                self.delete_lines();
+            }
+
+            Command::JoinLines => {
+                self.join_lines();
+            }
+
+            Command::MoveLinesUp => {
+                self.move_lines(true);
+            }
+
+            Command::MoveLinesDown => {
+                self.move_lines(false);
+            }
+
+            Command::SortLines => {
+                self.sort_lines();
+            }
+
+            Command::JumpToMatchingBracket => {
+                self.jump_to_matching_bracket();
+            }
+
+            Command::NavigateBack => {
+                self.navigate_history(true);
+            }
+
+            Command::NavigateForward => {
+                self.navigate_history(false);
+            }
+
+            Command::ToggleFoldAllCells => {
+                self.toggle_fold_all_cells();
+            }
+
             Command::Undo => {
                 if let Some(cursor) = self.buffer.undo() {
                     let cursor = cursor.min(self.buffer.len_bytes());
                     self.cursor = self.ensure_char_boundary(cursor);
-                    self.modified = self.buffer.can_undo();
+                    self.modified = !self.buffer.is_at_saved_state();
                     cursor_moved = true;
 
                     // Reinitialize syntax highlighting after undo
@@ -1061,7 +1205,7 @@ impl Editor {
                 if let Some(cursor) = self.buffer.redo() {
                     let cursor = cursor.min(self.buffer.len_bytes());
                     self.cursor = self.ensure_char_boundary(cursor);
-                    self.modified = true;
+                    self.modified = !self.buffer.is_at_saved_state();
                     cursor_moved = true;
 
                     // Reinitialize syntax highlighting after redo
@@ -1077,6 +1221,70 @@ impl Editor {
                 // This is handled in main.rs as it needs UI interaction
                 return Ok(());
             }
+
+            Command::GoToLine => {
+                // This is handled in main.rs as it needs UI interaction
+                return Ok(());
+            }
+            
+            Command::ToggleLineEnding => {
+                self.toggle_line_ending();
+            }
+            
+            Command::ToggleIndentStyle => {
+                self.toggle_indent_style();
+            }
+            
+            Command::ToggleBom => {
+                self.toggle_bom();
+            }
+
+            Command::ToggleGutter => {
+                self.toggle_gutter();
+            }
+
+            Command::ToggleInlineOutputs => {
+                self.toggle_inline_outputs();
+            }
+
+            Command::ToggleOutputCollapsed => {
+                self.toggle_output_collapsed();
+            }
+
+            Command::InsertCellAbove | Command::InsertCellBelow => {
+                self.insert_cell(matches!(cmd, Command::InsertCellBelow));
+                cursor_moved = true;
+            }
+
+            Command::SplitCell => {
+                self.split_cell();
+                cursor_moved = true;
+            }
+
+            Command::MergeCellBelow => {
+                self.merge_cell_below();
+                cursor_moved = true;
+            }
+
+            Command::MoveCellUp | Command::MoveCellDown => {
+                self.move_cell(matches!(cmd, Command::MoveCellUp));
+                cursor_moved = true;
+            }
+
+            Command::DuplicateCell => {
+                self.duplicate_cell();
+                cursor_moved = true;
+            }
+
+            Command::DeleteCell => {
+                self.delete_cell();
+                cursor_moved = true;
+            }
+
+            Command::NextCell | Command::PrevCell => {
+                self.move_to_cell(matches!(cmd, Command::NextCell));
+                cursor_moved = true;
+            }
             
             Command::FindReplace | Command::FindNext | Command::FindPrev | 
             Command::Replace | Command::ReplaceAll => {
@@ -1344,6 +1552,23 @@ impl Editor {
             
             Command::None => {}
         }
+
+        // Keep the cursor out of folded lines: undo and redo unfold what they changed,
+        // movements step over the fold
+        self.sync_folds();
+        if matches!(cmd, Command::Undo | Command::Redo) {
+            self.reveal_range(self.cursor, self.cursor);
+        } else if self.cursor != cursor_before_command && !matches!(cmd, Command::SelectAll) {
+            self.skip_folded_cursor(cursor_before_command);
+        }
+
+        // Long moves can be retraced with Alt+Left
+        if !matches!(cmd,
+            Command::PageUp | Command::PageDown | Command::JumpToMatchingBracket |
+            Command::NavigateBack | Command::NavigateForward
+        ) {
+            self.note_jump(cursor_before_command);
+        }
         
         // Update viewport if cursor moved (but not for pure viewport scrolling)
         if cursor_moved || matches!(cmd,
@@ -1355,7 +1580,11 @@ impl Editor {
             Command::MoveWordLeft | Command::MoveWordRight |
             Command::MoveParagraphUp | Command::MoveParagraphDown |
             Command::SelectWordLeft | Command::SelectWordRight |
-            Command::SelectParagraphUp | Command::SelectParagraphDown
+            Command::SelectParagraphUp | Command::SelectParagraphDown |
+            Command::AddNextOccurrence |
+            Command::ToggleComment | Command::ToggleBlockComment | Command::DuplicateLines |
+            Command::DeleteLines | Command::JoinLines | Command::MoveLinesUp | Command::MoveLinesDown |
+            Command::SortLines | Command::JumpToMatchingBracket
         ) {
             self.update_viewport_for_cursor();
             // Update bracket and text matching after cursor/selection changes
@@ -1404,9 +1633,13 @@ impl Editor {
     
     /// Move cursor to a specific position
     pub fn move_cursor_to(&mut self, position: usize) {
+        self.clear_extra_cursors();
+        let from = self.cursor;
         self.cursor = position.min(self.buffer.len_bytes());
         self.selection_start = None;
         self.preferred_column = None; // Clear preferred column
+        self.reveal_range(self.cursor, self.cursor);
+        self.note_jump(from);
         self.update_viewport_for_cursor();
     }
     
@@ -1417,6 +1650,18 @@ impl Editor {
         self.syntax.has_dirty_lines()
     }
     
+    /// Put text on the system clipboard
+    fn set_clipboard_text(&mut self, text: String) -> Result<(), String> {
+        let clipboard = self.clipboard.as_mut().ok_or("no clipboard available")?;
+        clipboard.set_text(text).map_err(|e| e.to_string())
+    }
+
+    /// Text on the system clipboard
+    fn clipboard_text(&mut self) -> Result<String, String> {
+        let clipboard = self.clipboard.as_mut().ok_or("no clipboard available")?;
+        clipboard.get_text().map_err(|e| e.to_string())
+    }
+
     /// Direct paste method for bracketed paste support
     pub fn paste_text(&mut self, text: String) {
         // Delete selection first if any
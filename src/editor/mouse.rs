@@ -9,6 +9,7 @@ impl Editor {
 
         // Clear preferred column on mouse interaction
         self.preferred_column = None;
+        self.clear_extra_cursors();
 
         // Check for double/triple click
         if let Some(last_time) = self.last_click_time {
@@ -57,9 +58,11 @@ impl Editor {
             }
             _ => {
                 // Single click - start normal selection
+                let from = self.cursor;
                 self.cursor = position;
                 self.selection_start = None;
                 self.mouse_selecting = true;
+                self.note_jump(from);
                 self.update_viewport_for_cursor();
             }
         }
@@ -100,7 +103,7 @@ impl Editor {
     }
 
     /// Select the word at the given position
-    fn select_word_at(&mut self, position: usize) {
+    pub(super) fn select_word_at(&mut self, position: usize) {
         let content = self.buffer.to_string();
         let chars: Vec<char> = content.chars().collect();
 
@@ -180,6 +183,7 @@ impl Editor {
     /// Finish mouse selection
     pub fn finish_mouse_selection(&mut self) {
         self.mouse_selecting = false;
+        self.block_anchor = None;
         // If selection start equals cursor, clear the selection
         if let Some(start) = self.selection_start {
             if start == self.cursor {
@@ -0,0 +1,392 @@
+use std::io;
+use crate::buffer::TextChange;
+use crate::commands::Command;
+use super::Editor;
+
+/// A cursor with its selection anchor, kept alongside the primary cursor
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) struct Caret {
+    pub cursor: usize,
+    pub anchor: Option<usize>,
+    pub preferred_column: Option<usize>,
+}
+
+impl Caret {
+    fn at(cursor: usize, anchor: Option<usize>) -> Self {
+        Self { cursor, anchor, preferred_column: None }
+    }
+
+    /// Selected byte range, empty at the cursor when nothing is selected
+    fn range(&self) -> (usize, usize) {
+        let anchor = self.anchor.unwrap_or(self.cursor);
+        (anchor.min(self.cursor), anchor.max(self.cursor))
+    }
+
+    fn selection(&self) -> Option<(usize, usize)> {
+        let (start, end) = self.range();
+        (start < end).then_some((start, end))
+    }
+}
+
+impl Editor {
+    /// Commands that run once at every cursor when there are several
+    fn applies_to_each_cursor(cmd: &Command) -> bool {
+        matches!(cmd,
+            Command::InsertChar(_) | Command::InsertNewline | Command::InsertTab |
+            Command::Backspace | Command::Delete | Command::Indent | Command::Dedent |
+            Command::Copy | Command::Cut | Command::Paste |
+            Command::MoveUp | Command::MoveDown | Command::MoveLeft | Command::MoveRight |
+            Command::MoveHome | Command::MoveEnd | Command::MoveWordLeft | Command::MoveWordRight |
+            Command::SelectUp | Command::SelectDown | Command::SelectLeft | Command::SelectRight |
+            Command::SelectHome | Command::SelectEnd | Command::SelectWordLeft | Command::SelectWordRight
+        )
+    }
+
+    /// Commands that leave additional cursors in place without touching them
+    fn keeps_extra_cursors(cmd: &Command) -> bool {
+        matches!(cmd,
+            Command::None | Command::Save | Command::SaveAs | Command::AddNextOccurrence |
+            Command::ToggleGutter | Command::ToggleInlineOutputs | Command::ToggleSoftWrap
+        )
+    }
+
+    pub fn execute(&mut self, cmd: Command) -> io::Result<()> {
+        if !self.extra_cursors.is_empty() {
+            if Self::applies_to_each_cursor(&cmd) {
+                return self.execute_at_each_cursor(cmd);
+            }
+            if !Self::keeps_extra_cursors(&cmd) {
+                self.clear_extra_cursors();
+            }
+        }
+        self.execute_at_cursor(cmd)
+    }
+
+    pub fn has_extra_cursors(&self) -> bool {
+        !self.extra_cursors.is_empty()
+    }
+
+    pub fn clear_extra_cursors(&mut self) {
+        self.extra_cursors.clear();
+        self.block_anchor = None;
+    }
+
+    /// Selections of all cursors, primary first
+    pub fn selections(&self) -> Vec<(usize, usize)> {
+        self.get_selection()
+            .into_iter()
+            .chain(self.extra_cursors.iter().filter_map(Caret::selection))
+            .collect()
+    }
+
+    /// Positions of the cursors other than the primary one
+    pub fn extra_cursor_positions(&self) -> Vec<usize> {
+        self.extra_cursors.iter().map(|caret| caret.cursor).collect()
+    }
+
+    fn primary_caret(&self) -> Caret {
+        Caret { cursor: self.cursor, anchor: self.selection_start, preferred_column: self.preferred_column }
+    }
+
+    fn set_primary_caret(&mut self, caret: Caret) {
+        self.cursor = caret.cursor;
+        self.selection_start = caret.anchor;
+        self.preferred_column = caret.preferred_column;
+    }
+
+    /// Move the additional cursors along with edits made since they were last placed
+    fn sync_extra_cursors(&mut self) {
+        let revision = self.buffer.revision();
+        if revision == self.extra_cursors_revision {
+            return;
+        }
+        match self.buffer.changes_since(self.extra_cursors_revision) {
+            Some(changes) => {
+                for caret in &mut self.extra_cursors {
+                    remap_caret(caret, &changes);
+                }
+            }
+            None => self.extra_cursors.clear(),
+        }
+        self.extra_cursors_revision = revision;
+    }
+
+    /// Run a command at the primary cursor and then at each additional one, as one undo step
+    fn execute_at_each_cursor(&mut self, cmd: Command) -> io::Result<()> {
+        self.sync_extra_cursors();
+        let mut carets = vec![self.primary_caret()];
+        carets.append(&mut self.extra_cursors);
+
+        // Clipboard contents are shared between the cursors
+        let mut ordered: Vec<Caret> = carets.clone();
+        ordered.sort_by_key(|caret| caret.range());
+        if matches!(cmd, Command::Copy | Command::Cut) {
+            let texts: Vec<String> = ordered.iter()
+                .filter_map(Caret::selection)
+                .map(|(start, end)| self.buffer.rope().byte_slice(start..end).to_string())
+                .collect();
+            if !texts.is_empty() {
+                if let Err(e) = self.set_clipboard_text(texts.join("\n")) {
+                    eprintln!("Failed to copy to clipboard: {}", e);
+                }
+            }
+            if matches!(cmd, Command::Copy) {
+                self.set_primary_caret(carets[0]);
+                self.extra_cursors = carets.split_off(1);
+                return Ok(());
+            }
+        }
+        let pasted: Option<Vec<String>> = if matches!(cmd, Command::Paste) {
+            match self.clipboard_text() {
+                Ok(text) => {
+                    // One line per cursor when the counts agree, otherwise everything at each
+                    let lines: Vec<&str> = text.trim_end_matches('\n').split('\n').collect();
+                    Some(if lines.len() == carets.len() {
+                        carets.iter()
+                            .map(|caret| ordered.iter().position(|c| c == caret).unwrap_or(0))
+                            .map(|i| lines[i].to_string())
+                            .collect()
+                    } else {
+                        vec![text; carets.len()]
+                    })
+                }
+                Err(_) => return Ok(()),
+            }
+        } else {
+            None
+        };
+
+        self.buffer.begin_undo_group(self.cursor);
+        let mut indented_lines: Vec<usize> = Vec::new();
+        for i in 0..carets.len() {
+            self.set_primary_caret(carets[i]);
+
+            // Lines shared by several cursors are indented only once
+            if matches!(cmd, Command::Indent | Command::Dedent) {
+                let (start, end) = carets[i].range();
+                let lines = self.buffer.byte_to_line(start)..=self.buffer.byte_to_line(end);
+                if lines.clone().any(|line| indented_lines.contains(&line)) {
+                    continue;
+                }
+                indented_lines.extend(lines);
+            }
+
+            let revision = self.buffer.revision();
+            match (&cmd, &pasted) {
+                (Command::Paste, Some(texts)) => self.paste_text(texts[i].clone()),
+                (Command::Cut, _) => {
+                    self.delete_selection();
+                }
+                _ => {
+                    if let Err(e) = self.execute_at_cursor(cmd.clone()) {
+                        self.buffer.end_undo_group();
+                        return Err(e);
+                    }
+                }
+            }
+            carets[i] = self.primary_caret();
+
+            if let Some(changes) = self.buffer.changes_since(revision) {
+                for (j, caret) in carets.iter_mut().enumerate() {
+                    if j != i {
+                        remap_caret(caret, &changes);
+                    }
+                }
+            }
+        }
+        self.buffer.end_undo_group();
+
+        let mut carets = merge_carets(carets);
+        let primary = carets.remove(0);
+        self.set_primary_caret(primary);
+        self.extra_cursors = carets;
+        self.extra_cursors_revision = self.buffer.revision();
+        self.update_viewport_for_cursor();
+        self.update_matching();
+        Ok(())
+    }
+
+    /// Add a cursor at the next occurrence of the selected text (Ctrl+D).
+    /// Without a selection, select the word at the cursor first.
+    pub(super) fn add_next_occurrence(&mut self) {
+        let Some((start, end)) = self.get_selection() else {
+            self.select_word_at(self.cursor);
+            return;
+        };
+        self.sync_extra_cursors();
+
+        let text = self.buffer.rope().byte_slice(start..end).to_string();
+        self.update_matching();
+        let mut matches = self.matching_text_positions.clone();
+        if matches.is_empty() {
+            // Short selections are not highlighted, so search for them here
+            matches = self.find_all(&text).into_iter().filter(|&(s, _)| s != start).collect();
+        }
+        let taken: Vec<(usize, usize)> = self.extra_cursors.iter().map(Caret::range).collect();
+        matches.retain(|range| !taken.contains(range));
+
+        // The first match after the newest cursor, wrapping around to the top
+        let Some(&(next_start, next_end)) = matches.iter()
+            .find(|&&(s, _)| s >= end)
+            .or_else(|| matches.first())
+        else {
+            self.status_message = Some(("No more occurrences".to_string(), false));
+            return;
+        };
+
+        let previous = self.primary_caret();
+        self.extra_cursors.push(previous);
+        self.extra_cursors_revision = self.buffer.revision();
+        self.set_primary_caret(Caret::at(next_end, Some(next_start)));
+        self.reveal_range(next_start, next_end);
+        self.status_message = Some((format!("{} cursors", self.extra_cursors.len() + 1), false));
+        self.update_viewport_for_cursor();
+    }
+
+    /// Add a cursor at a position (Alt+click), or remove the one already there
+    pub fn add_cursor_at(&mut self, position: usize) {
+        self.sync_extra_cursors();
+        let position = self.ensure_char_boundary(position.min(self.buffer.len_bytes()));
+        if let Some(index) = self.extra_cursors.iter().position(|caret| caret.cursor == position) {
+            self.extra_cursors.remove(index);
+        } else if position != self.cursor {
+            let previous = self.primary_caret();
+            self.extra_cursors.push(previous);
+            self.set_primary_caret(Caret::at(position, None));
+        }
+        self.extra_cursors_revision = self.buffer.revision();
+        self.update_viewport_for_cursor();
+    }
+
+    /// Start a rectangular selection at screen coordinates (Alt+drag)
+    pub fn start_block_selection(&mut self, screen_col: usize, screen_row: usize) {
+        self.block_anchor = self.screen_row_to_line(screen_row)
+            .map(|line| (line, self.screen_col_to_text_column(screen_col)));
+    }
+
+    pub fn is_block_selecting(&self) -> bool {
+        self.block_anchor.is_some()
+    }
+
+    /// Select the rectangle between the block anchor and the mouse, one cursor per line.
+    /// Lines that end left of the rectangle get no cursor.
+    pub fn update_block_selection(&mut self, screen_col: usize, screen_row: usize) {
+        let Some((anchor_line, anchor_col)) = self.block_anchor else { return };
+        let Some(line) = self.screen_row_to_line(screen_row) else { return };
+        let col = self.screen_col_to_text_column(screen_col);
+        let left = anchor_col.min(col);
+
+        let mut primary = None;
+        let mut extras = Vec::new();
+        for l in anchor_line.min(line)..=anchor_line.max(line) {
+            if self.is_line_hidden(l) {
+                continue;
+            }
+            let text = self.buffer.line(l);
+            let line_end = self.buffer.line_to_byte(l) + text.trim_end_matches('\n').len();
+            if l != line && self.byte_position_to_display(line_end).1 < left {
+                continue;
+            }
+            let anchor = self.byte_at_visual_column(l, 0, anchor_col);
+            let cursor = self.byte_at_visual_column(l, 0, col);
+            let caret = Caret::at(cursor, (anchor != cursor).then_some(anchor));
+            if l == line {
+                primary = Some(caret);
+            } else {
+                extras.push(caret);
+            }
+        }
+
+        if let Some(primary) = primary {
+            self.set_primary_caret(primary);
+            self.extra_cursors = extras;
+            self.extra_cursors_revision = self.buffer.revision();
+            self.update_viewport_for_cursor();
+        }
+    }
+
+    /// Text column under a screen column, accounting for the gutter and horizontal scroll
+    fn screen_col_to_text_column(&self, screen_col: usize) -> usize {
+        screen_col.saturating_sub(self.gutter_width()) + self.viewport_offset.1
+    }
+}
+
+/// Where a position ends up after a change: shifted when after it, at the
+/// change when inside the removed text
+pub(super) fn remap_position(pos: usize, change: &TextChange) -> usize {
+    if pos < change.pos {
+        pos
+    } else if pos >= change.pos + change.removed {
+        pos - change.removed + change.inserted
+    } else {
+        change.pos
+    }
+}
+
+fn remap_caret(caret: &mut Caret, changes: &[TextChange]) {
+    for change in changes {
+        caret.cursor = remap_position(caret.cursor, change);
+        caret.anchor = caret.anchor.map(|anchor| remap_position(anchor, change));
+    }
+}
+
+/// Merge cursors at the same position or with overlapping selections.
+/// The first caret is the primary one and stays first.
+fn merge_carets(carets: Vec<Caret>) -> Vec<Caret> {
+    let mut order: Vec<usize> = (0..carets.len()).collect();
+    order.sort_by_key(|&i| carets[i].range());
+
+    let mut merged: Vec<(Caret, bool)> = Vec::new();
+    for i in order {
+        let caret = carets[i];
+        let (start, end) = caret.range();
+        if let Some((last, is_primary)) = merged.last_mut() {
+            let (last_start, last_end) = last.range();
+            let touches = start == last_end && (start == end || last_start == last_end);
+            if start < last_end || touches {
+                // Keep the direction of the primary cursor, or of the earlier one
+                let keep = if i == 0 { caret } else { *last };
+                let (new_start, new_end) = (last_start, last_end.max(end));
+                let forward = keep.anchor.is_none_or(|anchor| anchor <= keep.cursor);
+                *last = Caret {
+                    cursor: if forward { new_end } else { new_start },
+                    anchor: (new_start != new_end).then_some(if forward { new_start } else { new_end }),
+                    preferred_column: keep.preferred_column,
+                };
+                *is_primary |= i == 0;
+                continue;
+            }
+        }
+        merged.push((caret, i == 0));
+    }
+
+    let primary = merged.iter().position(|&(_, is_primary)| is_primary).unwrap_or(0);
+    let (first, _) = merged.remove(primary);
+    std::iter::once(first).chain(merged.into_iter().map(|(caret, _)| caret)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remap_position() {
+        let insert = TextChange { pos: 10, removed: 0, inserted: 3 };
+        assert_eq!(remap_position(5, &insert), 5);
+        assert_eq!(remap_position(10, &insert), 13);
+        let delete = TextChange { pos: 10, removed: 4, inserted: 0 };
+        assert_eq!(remap_position(12, &delete), 10);
+        assert_eq!(remap_position(20, &delete), 16);
+    }
+
+    #[test]
+    fn test_merge_carets() {
+        // Cursors that meet are merged, keeping the primary first
+        let carets = vec![Caret::at(5, None), Caret::at(1, None), Caret::at(5, None)];
+        assert_eq!(merge_carets(carets), vec![Caret::at(5, None), Caret::at(1, None)]);
+
+        // Overlapping selections join into one
+        let carets = vec![Caret::at(8, Some(2)), Caret::at(12, Some(6)), Caret::at(20, None)];
+        assert_eq!(merge_carets(carets), vec![Caret::at(12, Some(2)), Caret::at(20, None)]);
+    }
+}
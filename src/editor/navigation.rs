@@ -0,0 +1,146 @@
+use super::multi_cursor::remap_position;
+use super::Editor;
+
+/// Cursor moves across at least this many lines are remembered for jumping back
+const JUMP_LINES: usize = 5;
+
+/// Positions kept in each direction of the navigation history
+const MAX_HISTORY: usize = 100;
+
+impl Editor {
+    /// Move the cursor to a `line` or `line:col` typed by the user (both 1-based)
+    pub fn go_to_line(&mut self, spec: &str) {
+        let Some((line, column)) = parse_line_spec(spec) else {
+            self.status_message = Some((format!("Not a line number: {}", spec.trim()), true));
+            return;
+        };
+        let line = line.clamp(1, self.buffer.len_lines()) - 1;
+        let text = self.buffer.line(line);
+        let offset = text.trim_end_matches('\n')
+            .char_indices()
+            .nth(column.unwrap_or(1).saturating_sub(1))
+            .map_or(text.trim_end_matches('\n').len(), |(i, _)| i);
+        self.move_cursor_to(self.buffer.line_to_byte(line) + offset);
+    }
+
+    /// Jump to the bracket matching the one at (or just before) the cursor, or else
+    /// to the opening bracket around the cursor
+    pub fn jump_to_matching_bracket(&mut self) {
+        self.update_matching();
+        let target = match self.get_matching_brackets() {
+            Some((open, close)) => Some(if self.cursor == open { close } else { open }),
+            None if self.cursor > 0 => {
+                // The bracket just typed, left of the cursor
+                let cursor = self.cursor;
+                self.cursor = self.buffer.char_to_byte(self.buffer.byte_to_char(cursor) - 1);
+                let before = self.cursor;
+                self.update_matching();
+                let target = self.get_matching_brackets()
+                    .map(|(open, close)| if open == before { close } else { open });
+                self.cursor = cursor;
+                target
+            }
+            None => None,
+        };
+        let target = target.or_else(|| self.code_context_at(self.cursor).open_brackets.last().map(|&(pos, _)| pos));
+
+        match target {
+            Some(pos) => {
+                let from = self.cursor;
+                self.cursor = pos;
+                self.selection_start = None;
+                self.preferred_column = None;
+                self.reveal_range(pos, pos);
+                self.note_jump(from);
+            }
+            None => self.status_message = Some(("No bracket to jump to".to_string(), false)),
+        }
+    }
+
+    /// Remember where the cursor was before a move that went far enough to count as a jump
+    pub(super) fn note_jump(&mut self, from: usize) {
+        self.sync_nav_history();
+        let from_line = self.buffer.byte_to_line(from.min(self.buffer.len_bytes()));
+        let to_line = self.buffer.byte_to_line(self.cursor);
+        if from_line.abs_diff(to_line) < JUMP_LINES {
+            return;
+        }
+        // Don't record the same spot twice in a row
+        if self.nav_back.last().is_some_and(|&pos| self.buffer.byte_to_line(pos) == from_line) {
+            return;
+        }
+        self.nav_back.push(from);
+        if self.nav_back.len() > MAX_HISTORY {
+            self.nav_back.remove(0);
+        }
+        self.nav_forward.clear();
+    }
+
+    /// Go back to where the cursor was before the last jump (Alt+Left), or forward again (Alt+Right)
+    pub fn navigate_history(&mut self, back: bool) {
+        self.sync_nav_history();
+        let (from, to) = if back {
+            (&mut self.nav_back, &mut self.nav_forward)
+        } else {
+            (&mut self.nav_forward, &mut self.nav_back)
+        };
+        let Some(target) = from.pop() else {
+            let message = if back { "No earlier position" } else { "No later position" };
+            self.status_message = Some((message.to_string(), false));
+            return;
+        };
+        to.push(self.cursor);
+
+        self.cursor = self.ensure_char_boundary(target.min(self.buffer.len_bytes()));
+        self.selection_start = None;
+        self.preferred_column = None;
+        self.reveal_range(self.cursor, self.cursor);
+        self.update_viewport_for_cursor();
+    }
+
+    /// Keep remembered positions on the same text as the buffer is edited
+    fn sync_nav_history(&mut self) {
+        let revision = self.buffer.revision();
+        if revision == self.nav_revision {
+            return;
+        }
+        match self.buffer.changes_since(self.nav_revision) {
+            Some(changes) => {
+                for pos in self.nav_back.iter_mut().chain(self.nav_forward.iter_mut()) {
+                    *pos = changes.iter().fold(*pos, remap_position);
+                }
+            }
+            None => {
+                self.nav_back.clear();
+                self.nav_forward.clear();
+            }
+        }
+        self.nav_revision = revision;
+    }
+}
+
+/// Parse `line` or `line:col` (also `line,col`), both 1-based
+fn parse_line_spec(spec: &str) -> Option<(usize, Option<usize>)> {
+    let mut parts = spec.trim().splitn(2, [':', ',']);
+    let line = parts.next()?.trim().parse().ok().filter(|&line| line > 0)?;
+    let column = match parts.next() {
+        Some(col) => Some(col.trim().parse().ok()?),
+        None => None,
+    };
+    Some((line, column))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_line_spec;
+
+    #[test]
+    fn test_parse_line_spec() {
+        assert_eq!(parse_line_spec("42"), Some((42, None)));
+        assert_eq!(parse_line_spec(" 12:5 "), Some((12, Some(5))));
+        assert_eq!(parse_line_spec("7,3"), Some((7, Some(3))));
+        assert_eq!(parse_line_spec("0"), None);
+        assert_eq!(parse_line_spec("abc"), None);
+        assert_eq!(parse_line_spec("3:x"), None);
+    }
+}
@@ -1,9 +1,20 @@
-use crate::cell::{Cell, parse_cells, get_cell_at_position, get_cell_content};
-use crate::kernel::Kernel;
+use crate::cell::{Cell, CellStatus, parse_cells, carry_over_state, execute_cell, get_cell_at_position, get_cell_content};
+use crate::kernel::{CompletionItem, Kernel};
+use crate::syntax::Language;
 use std::io;
 
 use super::Editor;
 
+/// A DataFrame column name being typed in a string: `df["na`, `df[["id", "na`,
+/// `df.loc[:, "na` or `pl.col("na`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnContext {
+    pub frame: Option<String>, // The subscripted name, or the receiver of the call `col()` is passed to
+    pub subscript: bool,       // In `frame[...]`: only a known frame's columns apply
+    pub typed: String,         // The name typed so far (everything after the quote)
+    pub quote: char,
+}
+
 impl Editor {
     /// Enable REPL mode
     pub fn enable_repl_mode(&mut self) {
@@ -26,11 +37,41 @@ impl Editor {
         self.kernel.as_ref().map(|k| k.info().display_name)
     }
 
+    /// Full info about the current kernel (None while it is busy in the background)
+    pub fn current_kernel(&self) -> Option<crate::kernel::KernelInfo> {
+        self.kernel.as_ref().map(|k| k.info())
+    }
+
     /// Take ownership of the kernel (for background execution)
     pub fn take_kernel(&mut self) -> Option<Box<dyn Kernel>> {
         self.kernel.take()
     }
 
+    /// Ask the kernel about a name for the completion preview (None while it is busy)
+    pub fn inspect(&mut self, name: &str) -> Option<crate::kernel::Documentation> {
+        self.kernel.as_mut()?.inspect(name).ok().flatten()
+    }
+
+    /// Completions from static analysis of the file for the word starting at `word_start`:
+    /// every name valid there, to be matched against the typed text locally. None if the
+    /// kernel doesn't answer in time, which leaves the names that exist at runtime.
+    pub fn static_completions(&mut self, word_start: usize) -> Vec<CompletionItem> {
+        if self.syntax.language() != Language::Python {
+            return Vec::new();
+        }
+        let Some(kernel) = self.kernel.as_mut() else {
+            return Vec::new();
+        };
+        let line = self.buffer.byte_to_line(word_start);
+        let column = self.buffer.byte_to_char(word_start) - self.buffer.byte_to_char(self.buffer.line_to_byte(line));
+        kernel.complete(&self.buffer.rope().to_string(), line, column).unwrap_or_default()
+    }
+
+    /// Ask the kernel for a callable's signature (None while it is busy)
+    pub fn signature(&mut self, name: &str, base: Option<&str>) -> Option<crate::kernel::Signature> {
+        self.kernel.as_mut()?.signature(name, base).ok().flatten()
+    }
+
     /// Get reference to cells
     pub fn get_cells_ref(&self) -> &[Cell] {
         &self.cells
@@ -88,110 +129,304 @@ impl Editor {
         let prefix = simple_word.trim_start_matches('.').to_string();
 
         // Check if we're in a method chain (look back for `)` followed by `.` and then our word)
-        let mut pos = cursor_pos;
+        let pos = cursor_pos.saturating_sub(prefix.len());
+        if pos > 0 && rope.get_char(rope.byte_to_char(pos - 1)) == Some('.') {
+            if let Some(base_callable) = self.method_chain_base(pos - 1) {
+                // Debug logging
+                if let Ok(mut f) = std::fs::OpenOptions::new().create(true).append(true).open("/tmp/sage_debug.log") {
+                    use std::io::Write;
+                    let _ = writeln!(f, "DEBUG get_completion_context: FOUND METHOD CHAIN - base_callable='{}', prefix='{}', is_sql={}", base_callable, prefix, is_sql_context);
+                }
+                return (Some(base_callable), prefix, is_sql_context);
+            }
+        }
 
-        // Move back through just the alphanumeric part (prefix), not the dot
-        if !prefix.is_empty() {
-            pos = pos.saturating_sub(prefix.len());
+        // Not in a method chain, return None for base and the prefix (without leading dot)
+        // Debug logging
+        if let Ok(mut f) = std::fs::OpenOptions::new().create(true).append(true).open("/tmp/sage_debug.log") {
+            use std::io::Write;
+            let _ = writeln!(f, "DEBUG get_completion_context: NO METHOD CHAIN - base_callable=None, prefix='{}', is_sql={}", prefix, is_sql_context);
         }
+        (None, prefix, is_sql_context)
+    }
 
-        // Check if there's a dot right before (where we are now)
-        if pos > 0 {
-            let char_before_idx = rope.byte_to_char(pos.saturating_sub(1));
-            if let Some(ch) = rope.get_char(char_before_idx) {
-                if ch == '.' {
-                    pos = pos.saturating_sub(1);
-
-                    // Now look for closing paren
-                    let mut paren_depth = 0;
-                    let mut found_callable = false;
-                    let mut callable_end = pos;
-
-                    // Move back through whitespace
-                    while pos > 0 {
-                        let idx = rope.byte_to_char(pos.saturating_sub(1));
-                        if let Some(ch) = rope.get_char(idx) {
-                            if ch.is_whitespace() {
-                                pos -= ch.len_utf8();
-                            } else {
-                                break;
-                            }
-                        } else {
-                            break;
-                        }
-                    }
+    /// The column name being typed, if the cursor is in a string that names a DataFrame column
+    pub fn column_completion_context(&self) -> Option<ColumnContext> {
+        if self.syntax.language() != Language::Python {
+            return None;
+        }
+        let context = self.code_context_at(self.cursor);
+        let quote = context.string_quote.as_deref().filter(|quote| quote.len() == 1)?.chars().next()?;
+        let line_start = self.buffer.line_to_byte(self.buffer.byte_to_line(self.cursor));
+        let before = self.buffer.rope().byte_slice(line_start..self.cursor).to_string();
+        let quote_pos = before.rfind(quote)?;
+        // The string has to be a whole argument or subscript
+        if !before[..quote_pos].trim_end().ends_with(['[', '(', ',']) {
+            return None;
+        }
 
-                    // Check for closing paren
-                    if pos > 0 {
-                        let idx = rope.byte_to_char(pos.saturating_sub(1));
-                        if let Some(ch) = rope.get_char(idx) {
-                            if ch == ')' {
-                                paren_depth = 1;
-                                pos -= ch.len_utf8();
-                                callable_end = pos;
-                                found_callable = true;
-                            }
-                        }
+        let brackets = &context.open_brackets;
+        let &(open_pos, bracket) = brackets.last()?;
+        let (frame, subscript) = match bracket {
+            '[' => {
+                let mut subscripted = self.expression_before(open_pos);
+                if subscripted.is_empty() {
+                    // A list of columns: df[["id", "na
+                    let &(outer_pos, outer) = brackets.get(brackets.len().checked_sub(2)?)?;
+                    if outer != '[' || outer_pos + 1 != open_pos {
+                        return None;
                     }
+                    subscripted = self.expression_before(outer_pos);
+                }
+                if subscripted.is_empty() || subscripted.starts_with('.') {
+                    return None;
+                }
+                (Some(subscripted), true)
+            }
+            '(' => {
+                let callee = self.expression_before(open_pos);
+                if callee != "col" && !callee.ends_with(".col") {
+                    return None;
+                }
+                // The frame a method of which gets the expression: df.filter(pl.col("na
+                let receiver = brackets.iter().rev().skip(1)
+                    .filter(|&&(_, bracket)| bracket == '(')
+                    .find_map(|&(pos, _)| self.call_receiver(pos));
+                (receiver, false)
+            }
+            _ => return None,
+        };
 
-                    // If we found a method chain, extract the base callable
-                    if found_callable {
-                        // Move back to find the matching opening paren
-                        while pos > 0 && paren_depth > 0 {
-                            let idx = rope.byte_to_char(pos.saturating_sub(1));
-                            if let Some(ch) = rope.get_char(idx) {
-                                if ch == ')' {
-                                    paren_depth += 1;
-                                } else if ch == '(' {
-                                    paren_depth -= 1;
-                                }
-                                pos -= ch.len_utf8();
-                            } else {
-                                break;
-                            }
-                        }
+        Some(ColumnContext { frame, subscript, typed: before[quote_pos + 1..].to_string(), quote })
+    }
 
-                        // Now extract the callable name before the opening paren
-                        let mut callable_start = pos;
-                        while callable_start > 0 {
-                            let idx = rope.byte_to_char(callable_start.saturating_sub(1));
-                            if let Some(ch) = rope.get_char(idx) {
-                                if ch.is_alphanumeric() || ch == '_' || ch == '.' {
-                                    callable_start -= ch.len_utf8();
-                                } else {
-                                    break;
-                                }
-                            } else {
-                                break;
-                            }
-                        }
+    /// The path being typed and its quote, if the cursor is in a string passed to a call
+    /// or assigned (`pd.read_csv("data/sa`, `path = "~/`) that isn't SQL
+    pub fn path_completion_context(&self) -> Option<(String, char)> {
+        if self.syntax.language() != Language::Python {
+            return None;
+        }
+        let context = self.code_context_at(self.cursor);
+        let quote = context.string_quote.as_deref().filter(|quote| quote.len() == 1)?.chars().next()?;
+        let line_start = self.buffer.line_to_byte(self.buffer.byte_to_line(self.cursor));
+        let before = self.buffer.rope().byte_slice(line_start..self.cursor).to_string();
+        let quote_pos = before.rfind(quote)?;
+        let typed = &before[quote_pos + 1..];
+        let opener = before[..quote_pos].trim_end_matches(['r', 'R']).trim_end();
+        if typed.is_empty() || !opener.ends_with(['(', ',', '='])
+            || crate::sql_context::is_in_sql_context(self.buffer.rope(), self.cursor)
+        {
+            return None;
+        }
+        Some((typed.to_string(), quote))
+    }
 
-                        if callable_start < pos {
-                            let base_callable = rope.slice(callable_start..pos).to_string();
-                            // Debug logging
-                            if let Ok(mut f) = std::fs::OpenOptions::new().create(true).append(true).open("/tmp/sage_debug.log") {
-                                use std::io::Write;
-                                let _ = writeln!(f, "DEBUG get_completion_context: FOUND METHOD CHAIN - base_callable='{}', prefix='{}', is_sql={}", base_callable, prefix, is_sql_context);
-                            }
-                            return (Some(base_callable), prefix.clone(), is_sql_context);
-                        }
-                    }
-                }
+    /// Directory relative paths in code are resolved against: the kernel's, or sage's own
+    pub fn working_directory(&mut self) -> std::path::PathBuf {
+        self.kernel.as_mut()
+            .and_then(|kernel| kernel.working_directory().ok().flatten())
+            .or_else(|| std::env::current_dir().ok())
+            .unwrap_or_default()
+    }
+
+    /// The object whose method is called with the parenthesis at `open_pos`
+    /// (`df` in `df.filter(`, `df.lazy` in `df.lazy().filter(`)
+    fn call_receiver(&self, open_pos: usize) -> Option<String> {
+        let expr = self.expression_before(open_pos);
+        if expr.starts_with('.') {
+            self.method_chain_base(open_pos - expr.len())
+        } else {
+            expr.rsplit_once('.').map(|(receiver, _)| receiver.to_string())
+        }
+    }
+
+    /// The dotted name right before `pos` on its line (`df.filter` before `(`), starting
+    /// with a `.` if it continues a method chain
+    pub(super) fn expression_before(&self, pos: usize) -> String {
+        let line_start = self.buffer.line_to_byte(self.buffer.byte_to_line(pos));
+        let before = self.buffer.rope().byte_slice(line_start..pos).to_string();
+        let start = before.trim_end_matches(|c: char| c.is_alphanumeric() || c == '_' || c == '.').len();
+        before[start..].to_string()
+    }
+
+    /// Tables and aliases of the SQL statement around the cursor (when it is in a SQL string)
+    pub fn sql_scope(&self) -> Option<crate::sql_context::SqlScope> {
+        let (sql, cursor) = crate::sql_context::sql_string_at(self.buffer.rope(), self.cursor)?;
+        Some(crate::sql_context::parse_sql_scope(&sql, cursor))
+    }
+
+    /// The callable whose result the `.` at `dot_pos` is applied to, for method chains
+    /// like `duckdb.sql(...).` -> "duckdb.sql"
+    pub(super) fn method_chain_base(&self, dot_pos: usize) -> Option<String> {
+        let rope = self.buffer.rope();
+        let char_before = |pos: usize| if pos > 0 { rope.get_char(rope.byte_to_char(pos - 1)) } else { None };
+        let mut pos = dot_pos;
+
+        // Move back through whitespace
+        while let Some(ch) = char_before(pos).filter(|ch| ch.is_whitespace()) {
+            pos -= ch.len_utf8();
+        }
+
+        // Check for closing paren
+        if char_before(pos) != Some(')') {
+            return None;
+        }
+        pos -= 1;
+
+        // Move back to find the matching opening paren
+        let mut paren_depth = 1;
+        while paren_depth > 0 {
+            let ch = char_before(pos)?;
+            if ch == ')' {
+                paren_depth += 1;
+            } else if ch == '(' {
+                paren_depth -= 1;
             }
+            pos -= ch.len_utf8();
         }
 
-        // Not in a method chain, return None for base and the prefix (without leading dot)
-        // Debug logging
-        if let Ok(mut f) = std::fs::OpenOptions::new().create(true).append(true).open("/tmp/sage_debug.log") {
-            use std::io::Write;
-            let _ = writeln!(f, "DEBUG get_completion_context: NO METHOD CHAIN - base_callable=None, prefix='{}', is_sql={}", prefix, is_sql_context);
+        // Now extract the callable name before the opening paren
+        let mut callable_start = pos;
+        while let Some(ch) = char_before(callable_start).filter(|&ch| ch.is_alphanumeric() || ch == '_' || ch == '.') {
+            callable_start -= ch.len_utf8();
         }
-        (None, prefix, is_sql_context)
+
+        (callable_start < pos).then(|| rope.byte_slice(callable_start..pos).to_string())
     }
 
-    /// Update cells by parsing the buffer
+    /// Update cells by parsing the buffer (execution state follows the cells it belongs to)
     pub fn update_cells(&mut self) {
-        self.cells = parse_cells(self.buffer.rope());
+        let mut cells = parse_cells(self.buffer.rope());
+        carry_over_state(&self.cells, &mut cells);
+        self.cells = cells;
+        self.cells_revision = Some(self.buffer.revision());
+        self.outputs_version += 1;
+    }
+
+    /// Re-parse cells only if the buffer changed since the last parse
+    pub fn refresh_cells(&mut self) {
+        if self.cells_revision != Some(self.buffer.revision()) {
+            self.update_cells();
+        }
+    }
+
+    /// Mark cells as executing (shown as [*] in the gutter)
+    pub fn mark_cells_running(&mut self, cell_indices: &[usize]) {
+        for &idx in cell_indices {
+            if let Some(cell) = self.cells.get_mut(idx) {
+                cell.status = CellStatus::Running;
+            }
+        }
+        self.outputs_version += 1;
+    }
+
+    /// Store the results of a background execution in the cells that were run.
+    /// Each result carries the cell index and code hash at launch (and the run time);
+    /// if the cell was edited while it ran, the output is kept but the cell is marked stale.
+    pub fn record_cell_results(&mut self, results: Vec<(usize, u64, crate::kernel::ExecutionResult, f64)>) {
+        self.refresh_cells();
+        for (idx, code_hash, result, elapsed) in results {
+            let target = self.cells.iter()
+                .position(|c| c.status == CellStatus::Running && c.code_hash == code_hash)
+                .or_else(|| (idx < self.cells.len()).then_some(idx));
+            if let Some(target) = target {
+                let cell = &mut self.cells[target];
+                cell.status = if cell.code_hash != code_hash {
+                    CellStatus::Stale
+                } else if result.success {
+                    CellStatus::Ok
+                } else {
+                    CellStatus::Error
+                };
+                cell.execution_count = result.execution_count;
+                cell.output = Some(result);
+                cell.last_run = Some((std::time::Instant::now(), elapsed));
+            }
+        }
+        self.cancel_running_cells();
+    }
+
+    /// Clear the running state of cells that never got to execute
+    /// (stopped by an earlier error, or execution was cancelled)
+    pub fn cancel_running_cells(&mut self) {
+        for cell in &mut self.cells {
+            if cell.status == CellStatus::Running {
+                cell.status = if cell.output.is_some() { CellStatus::Stale } else { CellStatus::Idle };
+            }
+        }
+        self.outputs_version += 1;
+    }
+
+    /// Index of the cell a buffer line belongs to (a delimiter line starts its cell)
+    pub fn cell_index_at_line(&self, line: usize) -> Option<usize> {
+        let position = self.buffer.line_to_byte(line);
+        let idx = self.cells.partition_point(|c| c.start <= position);
+        idx.checked_sub(1).filter(|&i| position < self.cells[i].end || i + 1 == self.cells.len())
+    }
+
+    /// Select the whole cell containing a buffer line (used for gutter clicks)
+    pub fn select_cell_at_line(&mut self, line: usize) -> bool {
+        if line >= self.buffer.len_lines() {
+            return false;
+        }
+        self.refresh_cells();
+        let (start, end) = match self.cell_index_at_line(line) {
+            Some(idx) => (self.cells[idx].start, self.cells[idx].end),
+            None => return false,
+        };
+
+        // Leave the trailing newline out so the selection ends on the cell's last line.
+        // The cursor goes to the start so the view stays where the user clicked
+        let end = if end > start && self.buffer.rope().byte(end - 1) == b'\n' { end - 1 } else { end };
+        self.set_selection_start(end);
+        self.cursor = start;
+        self.preferred_column = None;
+        true
+    }
+
+    /// Line of a cell's delimiter (or first line) and of its first code line
+    fn cell_lines(&self, idx: usize) -> (usize, usize) {
+        let header_line = self.buffer.byte_to_line(self.cells[idx].start);
+        let is_delimiter = self.buffer.line(header_line).trim_start().starts_with(crate::cell::CELL_DELIMITER);
+        let code_line = if is_delimiter && header_line + 1 < self.buffer.len_lines() { header_line + 1 } else { header_line };
+        (header_line, code_line)
+    }
+
+    /// Move the cursor to the first code line of a cell and scroll it near the top
+    pub fn jump_to_cell(&mut self, idx: usize) {
+        self.refresh_cells();
+        if idx >= self.cells.len() {
+            return;
+        }
+        let (header_line, code_line) = self.cell_lines(idx);
+        self.sync_folds();
+        // A folded cell is shown by putting the cursor on its header
+        let line = if self.is_line_hidden(code_line) { header_line } else { code_line };
+        let from = self.cursor;
+        self.cursor = self.buffer.line_to_byte(line);
+        self.selection_start = None;
+        self.preferred_column = None;
+        self.note_jump(from);
+        // One row of context above the cell header
+        self.viewport_offset.0 = self.line_to_display_row(header_line).saturating_sub(1);
+        self.update_viewport_for_cursor();
+    }
+
+    /// Move the cursor to the first code line of the next or previous cell
+    pub fn move_to_cell(&mut self, forward: bool) {
+        self.refresh_cells();
+        let (line, _) = self.cursor_position();
+        let Some(current) = self.cell_index_at_line(line) else { return };
+        let target = if forward { current + 1 } else { current.saturating_sub(1) };
+        if target == current || target >= self.cells.len() {
+            return;
+        }
+
+        let (header_line, code_line) = self.cell_lines(target);
+        let line = if self.is_line_hidden(code_line) { header_line } else { code_line };
+        self.cursor = self.buffer.line_to_byte(line);
+        self.selection_start = None;
+        self.preferred_column = None;
     }
 
     /// Get cells for rendering
@@ -238,18 +473,23 @@ impl Editor {
         for cell_idx in cells_to_execute {
             let cell = &self.cells[cell_idx];
             let code = get_cell_content(self.buffer.rope(), cell);
+            let cell_type = cell.cell_type.clone();
             let cell_number = cell_idx + 1; // Cell number is 1-indexed
 
             // Execute code with timing
             if let Some(kernel) = self.kernel.as_mut() {
                 let start_time = std::time::Instant::now();
 
-                match kernel.execute(&code) {
+                match execute_cell(kernel.as_mut(), &cell_type, &code) {
                     Ok(result) => {
                         let elapsed = start_time.elapsed().as_secs_f64();
 
                         // Store result in cell
+                        self.cells[cell_idx].status = if result.success { CellStatus::Ok } else { CellStatus::Error };
+                        self.cells[cell_idx].execution_count = result.execution_count;
                         self.cells[cell_idx].output = Some(result.clone());
+                        self.cells[cell_idx].last_run = Some((std::time::Instant::now(), elapsed));
+                        self.outputs_version += 1;
 
                         let execution_count = result.execution_count.unwrap_or(0);
                         let output_text = crate::cell::format_output(&result);
@@ -338,16 +578,18 @@ impl Editor {
         if let Some(cell_idx) = get_cell_at_position(&self.cells, self.cursor) {
             let cell = &self.cells[cell_idx];
             let code = get_cell_content(self.buffer.rope(), cell);
+            let cell_type = cell.cell_type.clone();
 
             // Get cell line number for display
             let cell_line = self.buffer.rope().byte_to_line(cell.start) + 1;
 
             // Execute code
             if let Some(kernel) = self.kernel.as_mut() {
-                match kernel.execute(&code) {
+                match execute_cell(kernel.as_mut(), &cell_type, &code) {
                     Ok(result) => {
                         // Store result in cell
                         self.cells[cell_idx].output = Some(result.clone());
+                        self.outputs_version += 1;
 
                         if result.success {
                             // Format output for display
@@ -445,3 +687,54 @@ impl Editor {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A Python editor holding `text` with the cursor at its end
+    fn editor_with(text: &str) -> Editor {
+        let mut editor = Editor::new();
+        editor.syntax.set_language_from_path("notebook.py");
+        editor.paste_text(text.to_string());
+        editor
+    }
+
+    fn column_context(text: &str) -> Option<ColumnContext> {
+        editor_with(text).column_completion_context()
+    }
+
+    fn context(frame: Option<&str>, subscript: bool, typed: &str, quote: char) -> Option<ColumnContext> {
+        Some(ColumnContext { frame: frame.map(str::to_string), subscript, typed: typed.to_string(), quote })
+    }
+
+    #[test]
+    fn test_column_completion_context() {
+        assert_eq!(column_context("df[\"na"), context(Some("df"), true, "na", '"'));
+        assert_eq!(column_context("df[[\"id\", 'na"), context(Some("df"), true, "na", '\''));
+        assert_eq!(column_context("df.loc[:, \""), context(Some("df.loc"), true, "", '"'));
+        assert_eq!(column_context("df.filter(pl.col(\"na"), context(Some("df"), false, "na", '"'));
+        assert_eq!(column_context("df.lazy().filter(pl.col(\"na"), context(Some("df.lazy"), false, "na", '"'));
+        assert_eq!(column_context("pl.col(\"na"), context(None, false, "na", '"'));
+
+        // Strings that don't name a column
+        assert_eq!(column_context("print(\"na"), None);
+        assert_eq!(column_context("name = \"na"), None);
+        assert_eq!(column_context("df[\"a\" + \"b"), None);
+        assert_eq!(column_context("[\"na"), None);
+        assert_eq!(column_context("df[\"\"\"na"), None);
+        assert_eq!(column_context("df[\"na\"]"), None);
+    }
+
+    #[test]
+    fn test_call_receiver() {
+        let receiver = |text: &str| {
+            let editor = editor_with(text);
+            editor.call_receiver(text.len() - 1)
+        };
+        assert_eq!(receiver("df.filter(").as_deref(), Some("df"));
+        assert_eq!(receiver("pl.read_csv(path).filter(").as_deref(), Some("pl.read_csv"));
+        assert_eq!(receiver("df.lazy().filter(").as_deref(), Some("df.lazy"));
+        assert_eq!(receiver("filter("), None);
+    }
+}
@@ -79,8 +79,13 @@ impl Editor {
     }
 
     pub fn select_range(&mut self, start: usize, end: usize) {
+        self.clear_extra_cursors();
+        let from = self.cursor;
         self.selection_start = Some(start);
         self.cursor = end;
+        // Search results inside a fold open it
+        self.reveal_range(start, end);
+        self.note_jump(from);
     }
 
     pub fn replace_selection(&mut self, replacement: &str) -> bool {
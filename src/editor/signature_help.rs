@@ -0,0 +1,73 @@
+use crate::syntax::Language;
+use super::Editor;
+
+/// The call whose arguments the cursor is in
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallContext {
+    pub open_pos: usize,         // Byte position of the `(`
+    pub name: String,            // Callable as written (`pd.merge`), or the method of a chain
+    pub base: Option<String>,    // For `duckdb.sql(...).limit(`: the callable whose result has the method
+    pub argument: usize,         // Index of the argument being typed
+    pub keyword: Option<String>, // Keyword of the argument being typed (`how=`)
+}
+
+impl Editor {
+    /// The call the cursor is inside the parentheses of, with the argument being typed
+    pub fn call_context(&self) -> Option<CallContext> {
+        if self.syntax.language() != Language::Python {
+            return None;
+        }
+        let context = self.code_context_at(self.cursor);
+        if context.in_comment {
+            return None;
+        }
+        let &(open_pos, bracket) = context.open_brackets.last()?;
+        if bracket != '(' {
+            return None;
+        }
+
+        // The callable right before the parenthesis
+        let expr = self.expression_before(open_pos);
+        let (name, base) = match expr.strip_prefix('.') {
+            Some(method) => (method.to_string(), Some(self.method_chain_base(open_pos - expr.len())?)),
+            None => (expr.clone(), None),
+        };
+        if name.is_empty() || name.ends_with('.') || name.starts_with(|c: char| c.is_ascii_digit()) {
+            return None;
+        }
+
+        let commas: Vec<usize> = context.commas.into_iter().filter(|&comma| comma > open_pos).collect();
+        let argument_start = commas.last().map_or(open_pos, |&comma| comma) + 1;
+        let argument = self.buffer.rope().byte_slice(argument_start..self.cursor).to_string();
+        Some(CallContext {
+            open_pos,
+            name,
+            base,
+            argument: commas.len(),
+            keyword: argument_keyword(&argument),
+        })
+    }
+}
+
+/// The keyword of a keyword argument being typed (`how` in ` how='in`)
+fn argument_keyword(argument: &str) -> Option<String> {
+    let argument = argument.trim_start();
+    let name_len = argument.find(|c: char| !c.is_alphanumeric() && c != '_')?;
+    let rest = argument[name_len..].trim_start();
+    let is_keyword = name_len > 0 && rest.starts_with('=') && !rest.starts_with("==");
+    is_keyword.then(|| argument[..name_len].to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::argument_keyword;
+
+    #[test]
+    fn test_argument_keyword() {
+        assert_eq!(argument_keyword(" how='in"), Some("how".to_string()));
+        assert_eq!(argument_keyword("on = ["), Some("on".to_string()));
+        assert_eq!(argument_keyword("x == 1"), None);
+        assert_eq!(argument_keyword("df"), None);
+        assert_eq!(argument_keyword(" f(a=1"), None);
+    }
+}
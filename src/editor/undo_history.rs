@@ -0,0 +1,37 @@
+use crate::undo_tree::{UndoHistory, UndoTree};
+use super::Editor;
+
+impl Editor {
+    /// Undo tree to save with the file's session
+    pub fn undo_history(&mut self) -> UndoHistory {
+        self.buffer.history()
+    }
+
+    /// Continue with the undo tree of the last session, if the file is unchanged since
+    pub fn restore_undo_history(&mut self, history: Option<UndoHistory>) {
+        if let Some(history) = history {
+            if !self.buffer.restore_history(history) {
+                self.status_message = Some(("File changed since last session - undo history not restored".to_string(), false));
+            }
+        }
+    }
+
+    pub fn undo_tree(&self) -> &UndoTree {
+        self.buffer.undo_tree()
+    }
+
+    /// Put the text in any state of the undo tree (from the undo tree browser)
+    pub fn goto_undo_state(&mut self, state: usize) {
+        let Some(cursor) = self.buffer.goto_state(state) else { return };
+        self.clear_extra_cursors();
+        self.cursor = self.ensure_char_boundary(cursor.min(self.buffer.len_bytes()));
+        self.selection_start = None;
+        self.preferred_column = None;
+        self.modified = !self.buffer.is_at_saved_state();
+        self.reinit_syntax_highlighting();
+        self.sync_folds();
+        self.reveal_range(self.cursor, self.cursor);
+        self.update_viewport_for_cursor();
+        self.update_matching();
+    }
+}
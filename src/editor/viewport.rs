@@ -1,5 +1,5 @@
 use unicode_width::UnicodeWidthChar;
-use super::Editor;
+use super::{DisplayRow, Editor};
 
 impl Editor {
     pub fn cursor_position(&self) -> (usize, usize) {
@@ -55,7 +55,7 @@ impl Editor {
         if let Ok((width, height)) = crossterm::terminal::size() {
             // Account for status bar and bottom window (output pane, find/replace, etc.)
             let viewport_height = (height as usize).saturating_sub(1 + bottom_window_height);
-            let viewport_width = width as usize;
+            let viewport_width = (width as usize).saturating_sub(self.gutter_width());
             self.update_viewport(viewport_height, viewport_width);
         }
     }
@@ -63,10 +63,16 @@ impl Editor {
     /// Update viewport to follow cursor with scrolloff
     pub(super) fn update_viewport(&mut self, viewport_height: usize, viewport_width: usize) {
         let scrolloff = 3;
-        let (cursor_line, cursor_col) = self.cursor_position();
+        if self.inline_outputs {
+            self.refresh_cells();
+        }
+        self.sync_folds();
+        self.refresh_output_layout();
+        self.set_wrap_width(viewport_width);
+        let (cursor_row, cursor_col) = self.cursor_display_position();
 
-        // Logical line includes the 2 virtual lines before the buffer
-        let logical_cursor_line = cursor_line + 3;
+        // Logical line includes the 2 virtual lines before the buffer, inline outputs and wrapped rows above
+        let logical_cursor_line = cursor_row + 1;
 
         // Vertical scrolling
         let cursor_screen_row = logical_cursor_line.saturating_sub(self.viewport_offset.0);
@@ -81,7 +87,11 @@ impl Editor {
             self.viewport_offset.0 = logical_cursor_line + scrolloff - viewport_height;
         }
 
-        // Horizontal scrolling - consider both cursor and selection start
+        // Horizontal scrolling - consider both cursor and selection start (none when wrapping)
+        if self.soft_wrap {
+            self.viewport_offset.1 = 0;
+            return;
+        }
         let mut left_col = cursor_col;
         let mut right_col = cursor_col;
 
@@ -112,64 +122,23 @@ impl Editor {
             }
         }
 
-        // Calculate logical line from screen row
-        let logical_line = self.viewport_offset.0 + screen_row;
-
-        // Virtual lines before the buffer (lines 0 and 1)
-        if logical_line < 2 {
-            return Some(0); // Click on virtual lines maps to start of buffer
-        }
-
-        // Map logical line to buffer line
-        let buffer_line = logical_line - 2;
-
-        // Check if the line exists in the buffer
-        if buffer_line >= self.buffer.len_lines() {
-            // Click beyond the buffer content
-            return Some(self.buffer.len_bytes());
-        }
-
-        // Get the line content
-        let line = self.buffer.line(buffer_line);
-        let line_start = self.buffer.line_to_byte(buffer_line);
-
-        // Calculate the display column accounting for horizontal scroll
-        let target_display_col = screen_col + self.viewport_offset.1;
-
-        // Find the byte position for the target display column
-        let mut byte_pos = 0;
-        let mut display_col = 0;
-
-        for ch in line.chars() {
-            if display_col >= target_display_col {
-                break;
+        // Map the screen row to a buffer line (and the row of it, when wrapped)
+        let (buffer_line, segment_start) = match self.display_row_at_screen(screen_row) {
+            DisplayRow::Line(line) => (line, 0),
+            DisplayRow::Wrap { line, start } => (line, start),
+            // Inline output maps to the end of the line it follows
+            DisplayRow::Output { line, .. } => {
+                let text = self.buffer.line(line);
+                return Some(self.buffer.line_to_byte(line) + text.trim_end_matches('\n').len());
             }
-
-            let char_width = ch.width().unwrap_or(1);
-
-            // Check if clicking in the middle of a wide character
-            if display_col + char_width > target_display_col {
-                // Click is within this character, decide based on position
-                if target_display_col - display_col < char_width / 2 {
-                    // Closer to start of character
-                    break;
-                } else {
-                    // Closer to end of character
-                    byte_pos += ch.len_utf8();
-                    break;
-                }
-            }
-
-            display_col += char_width;
-            byte_pos += ch.len_utf8();
-        }
-
-        // Don't include the newline in selection
-        if line.ends_with('\n') && byte_pos >= line.len() - 1 {
-            byte_pos = line.len().saturating_sub(1);
-        }
-
-        Some(line_start + byte_pos)
+            // Virtual lines before the buffer map to its start, those after to its end
+            DisplayRow::Filler if self.viewport_offset.0 + screen_row < 2 => return Some(0),
+            DisplayRow::Filler => return Some(self.buffer.len_bytes()),
+        };
+
+        // Calculate the display column accounting for the gutter and horizontal scroll
+        let target_display_col = screen_col.saturating_sub(self.gutter_width()) + self.viewport_offset.1;
+        Some(self.byte_at_visual_column(buffer_line, segment_start, target_display_col))
     }
 
     /// Scroll viewport vertically without moving cursor
@@ -178,8 +147,8 @@ impl Editor {
             // Scrolling down (viewport moves down, content moves up)
             self.viewport_offset.0 = self.viewport_offset.0.saturating_add(lines as usize);
             // Don't scroll past the end of the buffer
-            // We have 2 virtual lines before the buffer and allow some after
-            let max_offset = self.buffer.len_lines().saturating_add(2).saturating_add(10);
+            // We have 2 virtual lines before the buffer, inline outputs, and allow some after
+            let max_offset = self.display_row_count().saturating_add(10);
             if self.viewport_offset.0 > max_offset {
                 self.viewport_offset.0 = max_offset;
             }
@@ -191,6 +160,9 @@ impl Editor {
 
     /// Scroll viewport horizontally without moving cursor
     pub fn scroll_viewport_horizontal(&mut self, cols: i32) {
+        if self.soft_wrap {
+            return; // Wrapped lines always fit the window
+        }
         if cols > 0 {
             // Scrolling right (viewport moves right, content moves left)
             self.viewport_offset.1 = self.viewport_offset.1.saturating_add(cols as usize);
@@ -228,16 +200,57 @@ impl Editor {
         self.viewport_offset = offset;
     }
 
+    /// Put the cursor and viewport back where they were in a previous session
+    /// (clamped, since the file may have changed in the meantime)
+    pub fn restore_position(&mut self, cursor: usize, viewport_offset: (usize, usize)) {
+        self.cursor = self.ensure_char_boundary(cursor.min(self.buffer.len_bytes()));
+        let max_row = self.display_row_count().saturating_sub(1);
+        self.viewport_offset = (viewport_offset.0.min(max_row), viewport_offset.1);
+        self.update_viewport_for_cursor();
+    }
+
     /// Get cursor screen position (for drawing overlays like autocomplete)
     pub fn cursor_screen_position(&self) -> (usize, usize) {
-        let (cursor_line, cursor_col) = self.cursor_position();
+        let (logical_cursor_line, cursor_col) = self.cursor_display_position();
         let (viewport_row, viewport_col) = self.viewport_offset();
 
-        // Calculate screen position (add 2 for virtual lines before buffer)
-        let logical_cursor_line = cursor_line + 2;
+        // Calculate screen position (virtual lines, inline outputs and wrapped rows come before the cursor row)
         let screen_row = logical_cursor_line.saturating_sub(viewport_row);
-        let screen_col = cursor_col.saturating_sub(viewport_col);
+        let screen_col = cursor_col.saturating_sub(viewport_col) + self.gutter_width();
 
         (screen_col, screen_row)
     }
+
+    /// Show or hide the line number gutter
+    pub fn toggle_gutter(&mut self) {
+        self.show_gutter = !self.show_gutter;
+        self.update_viewport_for_cursor();
+    }
+
+    /// Width of the gutter in columns (0 when hidden):
+    /// status glyph, space, line number or [execution count], space
+    pub fn gutter_width(&self) -> usize {
+        if !self.show_gutter {
+            return 0;
+        }
+        let line_digits = self.buffer.len_lines().to_string().len();
+        let count_width = self.cells.iter()
+            .filter_map(|c| c.execution_count)
+            .max()
+            .map_or(3, |n| n.to_string().len() + 2);
+        line_digits.max(count_width).max(3) + 3
+    }
+
+    /// Whether a screen column falls inside the gutter
+    pub fn is_in_gutter(&self, screen_col: usize) -> bool {
+        screen_col < self.gutter_width()
+    }
+
+    /// Buffer line shown on a screen row, if any
+    pub fn screen_row_to_line(&self, screen_row: usize) -> Option<usize> {
+        match self.display_row_at_screen(screen_row) {
+            DisplayRow::Line(line) | DisplayRow::Wrap { line, .. } => Some(line),
+            _ => None,
+        }
+    }
 }
@@ -0,0 +1,337 @@
+use crate::buffer::TextChange;
+use unicode_width::UnicodeWidthChar;
+use super::multi_cursor::remap_position;
+use super::Editor;
+
+/// Narrowest text area that is still wrapped (below this lines are just cut off)
+const MIN_WRAP_WIDTH: usize = 8;
+
+/// Extra indent of continuation rows beyond the line's own indentation
+const HANGING_INDENT: usize = 4;
+
+/// Rows every line wraps into, kept in step with edits so that counting the rows above
+/// a line doesn't rewrap the whole file
+pub(super) struct WrapRows {
+    revision: u64,
+    width: usize,
+    extra: Vec<usize>,  // Rows each line adds beyond its first
+    before: Vec<usize>, // Rows added by the lines before each line (one entry more than lines)
+}
+
+impl WrapRows {
+    fn new(revision: u64, width: usize, extra: Vec<usize>) -> Self {
+        let mut rows = WrapRows { revision, width, extra, before: vec![0] };
+        rows.sum_from(0);
+        rows
+    }
+
+    fn sum_from(&mut self, line: usize) {
+        self.before.truncate(line + 1);
+        for line in line..self.extra.len() {
+            self.before.push(self.before[line] + self.extra[line]);
+        }
+    }
+
+    /// Replace the rows of the lines an edit rewrote, which are `first..=last` now.
+    /// Returns false when the edit doesn't fit the lines known before it.
+    fn update(&mut self, first: usize, last: usize, len_lines: usize, line_rows: impl Fn(usize) -> usize) -> bool {
+        // Lines outside the span are unchanged, those after it shifted
+        let unchanged = len_lines - (last - first + 1);
+        if unchanged >= self.extra.len() {
+            return false;
+        }
+        let old_last = self.extra.len() - (len_lines - last);
+        self.extra.splice(first..=old_last, (first..=last).map(line_rows));
+        self.sum_from(first);
+        true
+    }
+
+    /// Rows added by the lines in a range
+    fn added(&self, lines: std::ops::Range<usize>) -> usize {
+        let end = lines.end.min(self.extra.len());
+        self.before[end] - self.before[lines.start.min(end)]
+    }
+}
+
+impl Editor {
+    /// Turn soft wrapping of long lines on or off
+    pub fn toggle_soft_wrap(&mut self) {
+        self.soft_wrap = !self.soft_wrap;
+        self.viewport_offset.1 = 0;
+        self.preferred_column = None;
+        self.status_message = Some((
+            if self.soft_wrap { "Soft wrap on" } else { "Soft wrap off" }.to_string(),
+            false
+        ));
+        self.update_viewport_for_cursor();
+    }
+
+    pub fn soft_wrap_enabled(&self) -> bool {
+        self.soft_wrap
+    }
+
+    /// Width of the text area lines are wrapped to (one column is kept free for the cursor)
+    pub fn set_wrap_width(&mut self, text_width: usize) {
+        self.wrap_width = text_width.saturating_sub(1);
+        self.refresh_wrap_rows();
+    }
+
+    /// Bring the per-line wrap rows up to date: only lines touched by edits since the last
+    /// refresh are rewrapped, and everything when the width changed
+    fn refresh_wrap_rows(&mut self) {
+        if !self.soft_wrap {
+            self.wrap_rows = None;
+            return;
+        }
+        let revision = self.buffer.revision();
+        let len_lines = self.buffer.len_lines();
+        let line_rows = |line: usize| wrap_points(&self.buffer.line(line), self.wrap_width).len() - 1;
+
+        let mut rows = self.wrap_rows.take().filter(|rows| rows.width == self.wrap_width);
+        if let Some(stale) = rows.as_mut().filter(|rows| rows.revision != revision) {
+            let span = self.buffer.changes_since(stale.revision).and_then(|changes| changed_span(&changes));
+            let updated = span.is_some_and(|(start, end)| {
+                let (first, last) = (self.buffer.byte_to_line(start), self.buffer.byte_to_line(end));
+                stale.update(first, last, len_lines, line_rows)
+            });
+            if updated {
+                stale.revision = revision;
+            } else {
+                rows = None;
+            }
+        }
+        self.wrap_rows = Some(rows.unwrap_or_else(|| {
+            WrapRows::new(revision, self.wrap_width, (0..len_lines).map(line_rows).collect())
+        }));
+    }
+
+    /// Visual rows of a buffer line as (byte offset in the line, indent) pairs.
+    /// A single row starting at 0 unless soft wrap is on and the line is too long.
+    pub fn wrap_segments(&self, line: usize) -> Vec<(usize, usize)> {
+        if !self.soft_wrap {
+            return vec![(0, 0)];
+        }
+        wrap_points(&self.buffer.line(line), self.wrap_width)
+    }
+
+    /// Byte range and indent of the visual row of a line that starts at `start`
+    pub fn wrap_segment(&self, line: usize, start: usize) -> (usize, usize, usize) {
+        let text = self.buffer.line(line);
+        let line_len = text.trim_end_matches('\n').len();
+        let segments = self.wrap_segments(line);
+        let idx = segments.partition_point(|&(s, _)| s <= start).saturating_sub(1);
+        let end = segments.get(idx + 1).map_or(line_len, |&(s, _)| s);
+        (segments[idx].0, end, segments[idx].1)
+    }
+
+    /// Additional display rows that wrapping adds for the visible lines in `lines`
+    pub(super) fn wrap_rows_in(&self, lines: std::ops::Range<usize>) -> usize {
+        if !self.soft_wrap {
+            return 0;
+        }
+        let hidden = self.hidden_ranges();
+        let current = self.wrap_rows.as_ref()
+            .filter(|rows| rows.revision == self.buffer.revision() && rows.width == self.wrap_width);
+        let Some(rows) = current else {
+            // Not refreshed since the last edit: count directly
+            return lines
+                .filter(|line| !hidden.iter().any(|r| (r.first..=r.last).contains(line)))
+                .map(|line| self.wrap_segments(line).len() - 1)
+                .sum();
+        };
+        let folded: usize = hidden.iter()
+            .map(|r| rows.added(r.first.max(lines.start)..(r.last + 1).min(lines.end)))
+            .sum();
+        rows.added(lines) - folded
+    }
+
+    /// Display row and column (within the text area, before horizontal scrolling) of a byte position
+    pub fn position_to_display(&self, byte_pos: usize) -> (usize, usize) {
+        let (line, col) = self.byte_position_to_display(byte_pos);
+        if !self.soft_wrap {
+            return (self.line_to_display_row(line), col);
+        }
+
+        let text = self.buffer.line(line);
+        let offset = byte_pos.saturating_sub(self.buffer.line_to_byte(line)).min(text.len());
+        let segments = self.wrap_segments(line);
+        let idx = segments.partition_point(|&(s, _)| s <= offset).saturating_sub(1);
+        let (start, indent) = segments[idx];
+        let col = indent + text.get(start..offset).map_or(0, |s| s.chars().map(|c| c.width().unwrap_or(1)).sum());
+        (self.line_to_display_row(line) + idx, col)
+    }
+
+    /// Display row and column of the cursor
+    pub fn cursor_display_position(&self) -> (usize, usize) {
+        self.position_to_display(self.cursor)
+    }
+
+    /// Byte position in a visual row closest to a column of the text area
+    pub(super) fn byte_at_visual_column(&self, line: usize, segment_start: usize, column: usize) -> usize {
+        let text = self.buffer.line(line);
+        let (start, end, indent) = self.wrap_segment(line, segment_start);
+        let is_last_row = end >= text.trim_end_matches('\n').len();
+        let target = column.saturating_sub(indent);
+
+        let mut byte_pos = start;
+        let mut col = 0;
+        for ch in text[start..end].chars() {
+            if col >= target {
+                break;
+            }
+            let char_width = ch.width().unwrap_or(1);
+            if col + char_width > target {
+                // Inside a wide character: round to the nearer side
+                if target - col >= char_width / 2 {
+                    byte_pos += ch.len_utf8();
+                }
+                break;
+            }
+            col += char_width;
+            byte_pos += ch.len_utf8();
+        }
+
+        // The end of a wrapped row is drawn at the start of the next one
+        if !is_last_row && byte_pos >= end {
+            byte_pos = text[..end].char_indices().next_back().map_or(start, |(i, _)| i);
+        }
+        self.buffer.line_to_byte(line) + byte_pos
+    }
+
+    /// Move the cursor up or down one visual row of wrapped text, keeping its column.
+    /// Returns false at the top or bottom of the buffer.
+    pub(super) fn move_visual_line(&mut self, up: bool) -> bool {
+        let line = self.buffer.byte_to_line(self.cursor);
+        let offset = self.cursor - self.buffer.line_to_byte(line);
+        let segments = self.wrap_segments(line);
+        let idx = segments.partition_point(|&(s, _)| s <= offset).saturating_sub(1);
+
+        let target = if up {
+            match idx.checked_sub(1) {
+                Some(prev) => Some((line, segments[prev].0)),
+                None => self.visible_line_above(line)
+                    .map(|above| (above, self.wrap_segments(above).last().map_or(0, |&(s, _)| s))),
+            }
+        } else {
+            match segments.get(idx + 1) {
+                Some(&(next, _)) => Some((line, next)),
+                None => self.visible_line_below(line).map(|below| (below, 0)),
+            }
+        };
+        let Some((target_line, target_start)) = target else { return false };
+
+        let column = match self.preferred_column {
+            Some(column) => column,
+            None => {
+                let (_, column) = self.position_to_display(self.cursor);
+                self.preferred_column = Some(column);
+                column
+            }
+        };
+        self.cursor = self.byte_at_visual_column(target_line, target_start, column);
+        true
+    }
+}
+
+/// Byte range of the text that a series of changes rewrote, in the text after them
+fn changed_span(changes: &[TextChange]) -> Option<(usize, usize)> {
+    changes.iter().fold(None, |span, change| {
+        let changed_end = change.pos + change.inserted;
+        Some(match span {
+            Some((start, end)) => (
+                remap_position(start, change).min(change.pos),
+                remap_position(end, change).max(changed_end),
+            ),
+            None => (change.pos, changed_end),
+        })
+    })
+}
+
+/// Where a line is broken into visual rows of at most `width` columns, as
+/// (byte offset, indent) pairs. Breaks go after a space when the row has one;
+/// continuation rows are indented past the line's own indentation.
+fn wrap_points(line: &str, width: usize) -> Vec<(usize, usize)> {
+    let mut segments = vec![(0, 0)];
+    if width < MIN_WRAP_WIDTH {
+        return segments;
+    }
+
+    let text = line.trim_end_matches('\n');
+    let indent_width: usize = text.chars()
+        .take_while(|c| c.is_whitespace())
+        .map(|c| c.width().unwrap_or(1))
+        .sum();
+    let hang = (indent_width + HANGING_INDENT).min(width / 2);
+
+    let mut row_start = 0;
+    let mut col = 0;
+    let mut last_break = None; // Byte offset just after a space that follows text
+    let mut seen_text = false;
+    for (i, ch) in text.char_indices() {
+        let char_width = ch.width().unwrap_or(1);
+        while col + char_width > width && i > row_start {
+            let at = last_break.filter(|&b| b > row_start).unwrap_or(i);
+            segments.push((at, hang));
+            row_start = at;
+            col = hang + text[at..i].chars().map(|c| c.width().unwrap_or(1)).sum::<usize>();
+            last_break = None;
+        }
+        col += char_width;
+        if ch == ' ' && seen_text {
+            last_break = Some(i + 1);
+        }
+        seen_text |= !ch.is_whitespace();
+    }
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap_points() {
+        assert_eq!(wrap_points("short line\n", 20), vec![(0, 0)]);
+        // Breaks after the last space that fits; continuation rows get a hanging indent
+        assert_eq!(wrap_points("select a, b from table_name", 12), vec![(0, 0), (12, 4), (17, 4), (25, 4)]);
+        // Words longer than a row are cut
+        assert_eq!(wrap_points("abcdefghijklmnop", 10), vec![(0, 0), (10, 4)]);
+        // Wide characters count as two columns
+        assert_eq!(wrap_points("日本語日本語", 8), vec![(0, 0), (12, 4)]);
+    }
+
+    #[test]
+    fn test_changed_span() {
+        let change = |pos, removed, inserted| TextChange { pos, removed, inserted };
+        assert_eq!(changed_span(&[]), None);
+        assert_eq!(changed_span(&[change(10, 2, 5)]), Some((10, 15)));
+        // A later edit before the first shifts it; one after it widens the span
+        assert_eq!(changed_span(&[change(10, 0, 5), change(2, 0, 1)]), Some((2, 16)));
+        assert_eq!(changed_span(&[change(10, 0, 5), change(30, 3, 0)]), Some((10, 30)));
+    }
+
+    #[test]
+    fn test_wrap_rows_follow_edits() {
+        let mut buffer = crate::buffer::Buffer::from_string("short\n".repeat(5));
+        let line_rows = |buffer: &crate::buffer::Buffer, line: usize| wrap_points(&buffer.line(line), 10).len() - 1;
+        let all_rows = |buffer: &crate::buffer::Buffer| (0..buffer.len_lines()).map(|l| line_rows(buffer, l)).collect::<Vec<_>>();
+        let mut rows = WrapRows::new(buffer.revision(), 10, all_rows(&buffer));
+
+        let edits: [(usize, usize, &str); 4] = [
+            (6, 0, "a much longer line that wraps\n"), // New line in the middle
+            (0, 12, ""),                                  // Join the first lines
+            (3, 0, "x\ny\nz z z z z z z z z z"),         // Split one line into several
+            (0, 0, ""),
+        ];
+        for (pos, removed, text) in edits {
+            let revision = buffer.revision();
+            buffer.replace(pos, pos + removed, text, 0, 0);
+            if let Some((start, end)) = changed_span(&buffer.changes_since(revision).unwrap()) {
+                let (first, last) = (buffer.byte_to_line(start), buffer.byte_to_line(end));
+                assert!(rows.update(first, last, buffer.len_lines(), |line| line_rows(&buffer, line)));
+            }
+            assert_eq!(rows.extra, all_rows(&buffer));
+            assert_eq!(rows.added(0..buffer.len_lines()), all_rows(&buffer).iter().sum::<usize>());
+        }
+    }
+}
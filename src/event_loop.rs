@@ -1,4 +1,4 @@
-use crate::{editor, renderer, find_replace, output_pane, kernel, autocomplete, prompt, exit_prompt, kernel_selector, commands, direct_kernel};
+use crate::{editor, renderer, find_replace, output_pane, kernel, autocomplete, prompt, exit_prompt, kernel_selector, commands, direct_kernel, choice_prompt, diff, diff_view, session, cell_outline, undo_browser, signature_help};
 use crossterm::{
     event::{self, Event, KeyCode, KeyModifiers, MouseEventKind, MouseButton},
     execute,
@@ -6,6 +6,20 @@ use crossterm::{
 use std::io::{self, Write};
 use std::time::Duration;
 
+/// How long the user must be idle before unsaved changes are written to the swap file
+const SWAP_IDLE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Sent back by the background execution thread: the kernel, per-cell output for the
+/// output pane, completion data, and each executed cell's (index, code hash, result, seconds)
+type ExecutionMessage = (
+    Box<dyn kernel::Kernel>,
+    Vec<(usize, usize, String, bool, f64)>,
+    Vec<kernel::CompletionItem>,
+    kernel::TypeRelationships,
+    kernel::SqlMetadata,
+    Vec<(usize, u64, kernel::ExecutionResult, f64)>,
+);
+
 fn debug_log(msg: &str) {
     use std::fs::OpenOptions;
     if let Ok(mut log) = OpenOptions::new().create(true).append(true).open("/tmp/sage_debug.log") {
@@ -14,32 +28,43 @@ fn debug_log(msg: &str) {
     }
 }
 
-pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io::Result<()> {
+pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer, session: session::FileSession) -> io::Result<()> {
     let mut find_replace: Option<find_replace::FindReplace> = None;
+    let mut find_history = session.find_history;
+    let mut outline: Option<cell_outline::CellOutline> = None;
+    let mut undo_browser: Option<undo_browser::UndoBrowser> = None;
     let mut output_pane = output_pane::OutputPane::new();
+    for entry in session.outputs {
+        output_pane.add_output(entry);
+    }
     let mut output_pane_visible = true; // Visible by default
     let mut output_pane_height = 8; // Default height in lines
     let mut needs_redraw = true; // Track if we need to redraw
     let mut skip_event_read = false; // Skip event read to force immediate redraw
 
     // State for background execution with live timer
-    let mut execution_rx: Option<std::sync::mpsc::Receiver<(Box<dyn kernel::Kernel>, Vec<(usize, usize, String, bool, f64)>, Vec<kernel::CompletionItem>, kernel::TypeRelationships, kernel::SqlMetadata)>> = None;
+    let mut execution_rx: Option<std::sync::mpsc::Receiver<ExecutionMessage>> = None;
     let mut execution_start_time: Option<std::time::Instant> = None;
     let mut executing_kernel_info: Option<kernel::KernelInfo> = None;
 
     // Autocomplete
     let mut autocomplete = autocomplete::Autocomplete::new();
+    let mut signature_help = signature_help::SignatureHelp::new();
     let mut suppress_autocomplete_once = false; // Suppress after Tab completion
 
     loop {
         debug_log(&format!("Loop iteration start"));
 
+        // Periodically snapshot unsaved changes even while the user keeps typing
+        editor.update_swap_file(false);
+
         // Check if background execution is complete
         if let Some(ref rx) = execution_rx {
             match rx.try_recv() {
-                Ok((kernel, results, completions, type_relationships, sql_metadata)) => {
+                Ok((kernel, results, completions, type_relationships, sql_metadata, cell_results)) => {
                     // Execution complete! Put kernel back and process results
                     editor.set_kernel(kernel);
+                    editor.record_cell_results(cell_results);
                     execution_rx = None;
                     executing_kernel_info = None;
                     let elapsed = execution_start_time.take().map(|t| t.elapsed().as_secs_f64()).unwrap_or(0.0);
@@ -57,15 +82,13 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
 
                     // Update autocomplete with dynamic completions
                     if !completions.is_empty() {
-                        let completion_names: Vec<String> = completions.iter()
-                            .map(|c| c.name.clone())
-                            .collect();
-                        autocomplete.add_dynamic_completions(completion_names);
+                        autocomplete.add_dynamic_completions(completions);
                     }
 
                     // Update autocomplete with type relationships and SQL metadata
                     autocomplete.set_type_relationships(type_relationships);
                     autocomplete.set_sql_metadata(sql_metadata);
+                    signature_help.invalidate();
 
                     // Update status message with final time
                     editor.status_message = Some((format!("Executed ({:.3}s)", elapsed), false));
@@ -91,6 +114,7 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
                 Err(std::sync::mpsc::TryRecvError::Disconnected) => {
                     // Thread panicked or channel closed unexpectedly
                     editor.status_message = Some(("Execution failed".to_string(), true));
+                    editor.cancel_running_cells();
                     execution_rx = None;
                     execution_start_time = None;
                     executing_kernel_info = None;
@@ -111,6 +135,11 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
                 0
             };
 
+            // Follow the cursor into the arguments of a call for the signature hint
+            if signature_help.update(editor.call_context(), |name, base| editor.signature(name, base)) {
+                renderer.force_redraw();
+            }
+
             debug_log(&format!("About to call draw_with_bottom_window"));
             // Draw the editor with bottom window if needed
             renderer.draw_with_bottom_window(editor, bottom_window_height)?;
@@ -133,8 +162,29 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
                 debug_log(&format!("output_pane draw completed"));
             }
 
+            // Draw the cell outline over the editor
+            if let Some(ref mut ol) = outline {
+                ol.draw(&mut io::stdout(), editor, bottom_window_height)?;
+            }
+            if let Some(ref mut ub) = undo_browser {
+                ub.draw(&mut io::stdout(), editor, bottom_window_height)?;
+            }
+
+            // Draw the signature hint under the autocomplete dropdown, which can cover it
+            if signature_help.is_visible() && outline.is_none() && undo_browser.is_none() && !output_pane.is_focused() {
+                let (screen_col, screen_row) = editor.cursor_screen_position();
+                let (width, _) = crossterm::terminal::size()?;
+                signature_help.draw(&mut io::stdout(), screen_row as u16, screen_col as u16, width)?;
+                renderer.reposition_cursor(editor, bottom_window_height)?;
+            }
+
             // Draw autocomplete dropdown if visible
             if autocomplete.is_visible() {
+                // Fetch the selected item's documentation the first time it is shown
+                if let Some(name) = autocomplete.pending_docs_request() {
+                    let docs = editor.inspect(&name);
+                    autocomplete.set_docs(name, docs);
+                }
                 let (screen_col, screen_row) = editor.cursor_screen_position();
                 let (width, height) = crossterm::terminal::size()?;
                 autocomplete.draw(&mut io::stdout(), screen_row as u16, screen_col as u16, height, width)?;
@@ -159,12 +209,21 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
         let event_available = if execution_rx.is_some() {
             // Poll with 100ms timeout to update timer frequently
             event::poll(std::time::Duration::from_millis(100))?
+        } else if editor.is_modified() || editor.file_path().is_some() {
+            // Wake up after a short idle period to snapshot unsaved changes to the swap file
+            // and to notice if another program changed the file
+            event::poll(SWAP_IDLE_TIMEOUT)?
         } else {
             // Block waiting for event when not executing
             event::poll(std::time::Duration::from_secs(3600))? // 1 hour timeout (effectively blocking)
         };
 
         if !event_available {
+            // User is idle - good moment to write the swap file
+            editor.update_swap_file(true);
+            if editor.poll_disk_changes() != editor::DiskChange::Unchanged {
+                renderer.draw(editor)?;
+            }
             // No event, continue loop to update timer
             continue;
         }
@@ -180,9 +239,10 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
             Event::Mouse(mouse_event) => {
                 // Check if shift is held for horizontal scrolling
                 let shift_held = mouse_event.modifiers.contains(crossterm::event::KeyModifiers::SHIFT);
+                let alt_held = mouse_event.modifiers.contains(crossterm::event::KeyModifiers::ALT);
                 
-                // Only handle mouse events if find/replace is NOT open
-                if find_replace.is_none() {
+                // Only handle mouse events if find/replace, the outline and the undo browser are NOT open
+                if find_replace.is_none() && outline.is_none() && undo_browser.is_none() {
                     // Handle mouse events for text selection
                     match mouse_event.kind {
                         MouseEventKind::Down(MouseButton::Left) => {
@@ -207,7 +267,32 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
                             } else {
                                 // Click is in editor - unfocus output pane and start selection
                                 output_pane.set_focused(false);
-                                if let Some(position) = editor.screen_to_buffer_position(
+                                let gutter_line = editor.is_in_gutter(mouse_event.column as usize)
+                                    .then(|| editor.screen_row_to_line(mouse_event.row as usize))
+                                    .flatten();
+                                if let Some(cell) = editor.output_cell_at_screen_row(mouse_event.row as usize) {
+                                    // Click on an inline output folds or unfolds it
+                                    editor.toggle_cell_output(cell);
+                                    needs_redraw = true;
+                                } else if editor.fold_toggle_at_screen(mouse_event.column as usize, mouse_event.row as usize)
+                                    .is_some_and(|line| editor.toggle_fold_at_line(line))
+                                {
+                                    // Click on a fold marker or placeholder folds or unfolds the block
+                                    needs_redraw = true;
+                                } else if let Some(line) = gutter_line {
+                                    // Click in the gutter selects the whole cell
+                                    if editor.select_cell_at_line(line) {
+                                        needs_redraw = true;
+                                    }
+                                } else if let Some(position) = editor.screen_to_buffer_position(
+                                    mouse_event.column as usize,
+                                    mouse_event.row as usize,
+                                ).filter(|_| alt_held) {
+                                    // Alt+click adds a cursor, Alt+drag selects a block
+                                    editor.add_cursor_at(position);
+                                    editor.start_block_selection(mouse_event.column as usize, mouse_event.row as usize);
+                                    needs_redraw = true;
+                                } else if let Some(position) = editor.screen_to_buffer_position(
                                     mouse_event.column as usize,
                                     mouse_event.row as usize,
                                 ) {
@@ -232,6 +317,9 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
                                     mouse_event.row as usize,
                                 );
                                 needs_redraw = true;
+                            } else if editor.is_block_selecting() {
+                                editor.update_block_selection(mouse_event.column as usize, mouse_event.row as usize);
+                                needs_redraw = true;
                             } else {
                                 // Update selection in editor
                                 if let Some(position) = editor.screen_to_buffer_position(
@@ -353,6 +441,47 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
                 }
 
                 needs_redraw = true; // Key events usually need redraw
+
+                // The cell outline takes all keys while it is open
+                if let Some(ref mut ol) = outline {
+                    match ol.handle_key(key.code, key.modifiers, editor) {
+                        cell_outline::OutlineAction::Continue => {}
+                        cell_outline::OutlineAction::Close => {
+                            outline = None;
+                            renderer.force_redraw();
+                        }
+                        cell_outline::OutlineAction::Jump(idx) => {
+                            outline = None;
+                            editor.jump_to_cell(idx);
+                            let bottom_height = if output_pane_visible { output_pane_height } else { 0 };
+                            editor.update_viewport_for_cursor_with_bottom(bottom_height);
+                            renderer.force_redraw();
+                        }
+                    }
+                    continue;
+                }
+
+                // So does the undo tree browser, previewing each state as it is selected
+                if let Some(ref mut ub) = undo_browser {
+                    match ub.handle_key(key.code, key.modifiers, editor) {
+                        undo_browser::UndoBrowserAction::Continue => {}
+                        undo_browser::UndoBrowserAction::Preview(state) => {
+                            editor.goto_undo_state(state);
+                        }
+                        undo_browser::UndoBrowserAction::Close => {
+                            undo_browser = None;
+                            renderer.force_redraw();
+                        }
+                        undo_browser::UndoBrowserAction::Cancel(state) => {
+                            undo_browser = None;
+                            editor.goto_undo_state(state);
+                            renderer.force_redraw();
+                        }
+                    }
+                    let bottom_height = if output_pane_visible { output_pane_height } else { 0 };
+                    editor.update_viewport_for_cursor_with_bottom(bottom_height);
+                    continue;
+                }
                 
                 // If find/replace window is active, handle its input first
                 if let Some(ref mut fr) = find_replace {
@@ -448,6 +577,8 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
                         let result = fr.handle_input(key.code, key.modifiers);
                         match result {
                             find_replace::InputResult::Close => {
+                                fr.remember_search();
+                                find_history = fr.history().to_vec();
                                 find_replace = None;
                                 // Clear selection and find matches when closing find
                                 editor.selection_start = None;
@@ -475,6 +606,7 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
                                 }
                             }
                             find_replace::InputResult::FindNext => {
+                                fr.remember_search();
                                 if !fr.is_empty() {
                                     if let Some((start, end)) = fr.next_match() {
                                         editor.select_range(start, end);
@@ -499,6 +631,9 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
                         if autocomplete.is_visible() {
                             autocomplete.hide();
                             needs_redraw = true;
+                        } else if editor.has_extra_cursors() {
+                            editor.clear_extra_cursors();
+                            needs_redraw = true;
                         } else if output_pane_visible {
                             output_pane.toggle_focus();
                             needs_redraw = true;
@@ -546,7 +681,7 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
                                                 renderer.draw(editor)?;
                                                 continue; // Don't exit if save failed
                                             } else {
-                                                return Ok(()); // Successfully saved, exit
+                                                break; // Successfully saved, exit
                                             }
                                         } else {
                                             // User cancelled Save As, don't exit
@@ -560,16 +695,16 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
                                         }
                                     } else {
                                         // Normal save
-                                        if editor.save().is_err() {
+                                        if !confirm_overwrite(editor, renderer)? || editor.save().is_err() {
                                             renderer.draw(editor)?;
                                             continue; // Don't exit if save failed
                                         } else {
-                                            return Ok(()); // Successfully saved, exit
+                                            break; // Successfully saved, exit
                                         }
                                     }
                                 }
                                 exit_prompt::ExitOption::ExitWithoutSaving => {
-                                    return Ok(()); // Exit without saving
+                                    break; // Exit without saving
                                 }
                                 exit_prompt::ExitOption::Cancel => {
                                     // Cancel exit, redraw and continue
@@ -579,7 +714,7 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
                             }
                         } else {
                             // No unsaved changes, exit immediately
-                            return Ok(());
+                            break;
                         }
                     }
                     
@@ -610,33 +745,7 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
                             // Drop the channel - abandons the background thread
                             execution_rx = None;
                             execution_start_time = None;
-
-                            // Recreate a fresh kernel using stored info
-                            if let Some(kernel_info) = executing_kernel_info.take() {
-                                match kernel_info.kernel_type {
-                                    kernel::KernelType::Direct => {
-                                        let mut new_kernel: Box<dyn kernel::Kernel> = Box::new(direct_kernel::DirectKernel::new(
-                                            kernel_info.python_path.clone(),
-                                            kernel_info.name.clone(),
-                                            kernel_info.display_name.clone(),
-                                        ));
-                                        // Connect the new kernel
-                                        if new_kernel.connect().is_ok() {
-                                            editor.set_kernel(new_kernel);
-                                            editor.status_message = Some(("CANCELLED - Kernel reset (all variables lost)".to_string(), true));
-                                        } else {
-                                            editor.status_message = Some(("CANCELLED - Kernel reconnection failed".to_string(), true));
-                                        }
-                                    }
-                                    _ => {
-                                        // For Jupyter or other kernel types, just report cancellation
-                                        editor.status_message = Some(("Execution cancelled - please reconnect kernel".to_string(), true));
-                                    }
-                                }
-                            } else {
-                                editor.status_message = Some(("Execution cancelled".to_string(), true));
-                            }
-
+                            cancel_execution(editor, executing_kernel_info.take());
                             renderer.force_redraw();
                             needs_redraw = true;
                         } else {
@@ -662,6 +771,11 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
                         }
                     }
 
+                    // Add a cursor at the next occurrence of the selection
+                    KeyCode::Char('d') | KeyCode::Char('D') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        commands::Command::AddNextOccurrence
+                    }
+
                     KeyCode::Char('x') | KeyCode::Char('X') if key.modifiers.contains(KeyModifiers::CONTROL) => {
                         commands::Command::Cut
                     }
@@ -675,6 +789,14 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
                         commands::Command::FindReplace
                     }
                     
+                    // Go to line (Ctrl+G) and to the matching bracket (Ctrl+], reported as Ctrl+5 by many terminals)
+                    KeyCode::Char('g') | KeyCode::Char('G') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        commands::Command::GoToLine
+                    }
+                    KeyCode::Char(']') | KeyCode::Char('5') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        commands::Command::JumpToMatchingBracket
+                    }
+
                     // Select All
                     KeyCode::Char('a') | KeyCode::Char('A') if key.modifiers.contains(KeyModifiers::CONTROL) => {
                         commands::Command::SelectAll
@@ -706,6 +828,7 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
                     // Clear Output Pane (Ctrl+L)
                     KeyCode::Char('l') | KeyCode::Char('L') if key.modifiers.contains(KeyModifiers::CONTROL) => {
                         output_pane.clear();
+                        editor.clear_cell_outputs();
                         editor.status_message = Some(("Output cleared".to_string(), false));
                         needs_redraw = true;
                         commands::Command::None
@@ -722,6 +845,13 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
                     }
 
                     // Kernel Selection (Ctrl+K)
+                    // Cell outline / jump to cell (Ctrl+P)
+                    KeyCode::Char('p') | KeyCode::Char('P') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        autocomplete.hide();
+                        outline = Some(cell_outline::CellOutline::new(editor));
+                        commands::Command::None
+                    }
+
                     KeyCode::Char('k') | KeyCode::Char('K') if key.modifiers.contains(KeyModifiers::CONTROL) => {
                         // Show loading message
                         editor.status_message = Some(("Discovering Python kernels...".to_string(), false));
@@ -845,8 +975,8 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
                             }
                             needs_redraw = true;
                             commands::Command::None
-                        } else if key.modifiers.contains(KeyModifiers::ALT) {
-                            // Alt+Up = Increase output pane height
+                        } else if key.modifiers.contains(KeyModifiers::ALT | KeyModifiers::SHIFT) {
+                            // Alt+Shift+Up = Increase output pane height
                             let (_, term_height) = crossterm::terminal::size()?;
                             let max_height = (term_height as usize).saturating_sub(3); // Leave 3 lines for editor
                             if output_pane_visible && output_pane_height < max_height {
@@ -857,6 +987,12 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
                                 needs_redraw = true;
                             }
                             commands::Command::None
+                        } else if key.modifiers.contains(KeyModifiers::ALT | KeyModifiers::CONTROL) {
+                            // Ctrl+Alt+Up = Move the current line(s) up
+                            commands::Command::MoveLinesUp
+                        } else if key.modifiers.contains(KeyModifiers::ALT) {
+                            // Alt+Up = Move the current cell up
+                            commands::Command::MoveCellUp
                         } else if key.modifiers.contains(KeyModifiers::CONTROL | KeyModifiers::SHIFT) {
                             commands::Command::SelectParagraphUp
                         } else if key.modifiers.contains(KeyModifiers::CONTROL) {
@@ -885,8 +1021,8 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
                             }
                             needs_redraw = true;
                             commands::Command::None
-                        } else if key.modifiers.contains(KeyModifiers::ALT) {
-                            // Alt+Down = Decrease output pane height
+                        } else if key.modifiers.contains(KeyModifiers::ALT | KeyModifiers::SHIFT) {
+                            // Alt+Shift+Down = Decrease output pane height
                             if output_pane_visible && output_pane_height > 3 {
                                 output_pane_height -= 1;
                                 // Update viewport to account for new bottom window height
@@ -895,6 +1031,12 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
                                 needs_redraw = true;
                             }
                             commands::Command::None
+                        } else if key.modifiers.contains(KeyModifiers::ALT | KeyModifiers::CONTROL) {
+                            // Ctrl+Alt+Down = Move the current line(s) down
+                            commands::Command::MoveLinesDown
+                        } else if key.modifiers.contains(KeyModifiers::ALT) {
+                            // Alt+Down = Move the current cell down
+                            commands::Command::MoveCellDown
                         } else if key.modifiers.contains(KeyModifiers::CONTROL | KeyModifiers::SHIFT) {
                             commands::Command::SelectParagraphDown
                         } else if key.modifiers.contains(KeyModifiers::CONTROL) {
@@ -917,6 +1059,9 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
                             }
                             needs_redraw = true;
                             commands::Command::None
+                        } else if key.modifiers.contains(KeyModifiers::ALT) {
+                            // Alt+Left = Back to the position before the last jump
+                            commands::Command::NavigateBack
                         } else if key.modifiers.contains(KeyModifiers::CONTROL | KeyModifiers::SHIFT) {
                             commands::Command::SelectWordLeft
                         } else if key.modifiers.contains(KeyModifiers::CONTROL) {
@@ -939,6 +1084,9 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
                             }
                             needs_redraw = true;
                             commands::Command::None
+                        } else if key.modifiers.contains(KeyModifiers::ALT) {
+                            // Alt+Right = Forward again after going back
+                            commands::Command::NavigateForward
                         } else if key.modifiers.contains(KeyModifiers::CONTROL | KeyModifiers::SHIFT) {
                             commands::Command::SelectWordRight
                         } else if key.modifiers.contains(KeyModifiers::CONTROL) {
@@ -997,34 +1145,11 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
                     // Editing
                     // Ctrl+H is often sent by terminals for Ctrl+Backspace - handle cancellation
                     KeyCode::Char('h') if key.modifiers.contains(KeyModifiers::CONTROL) && find_replace.is_none() => {
-                        // Same cancellation logic as Ctrl+Backspace
+                        // Same cancellation as Ctrl+Backspace
                         if execution_rx.is_some() {
                             execution_rx = None;
                             execution_start_time = None;
-
-                            if let Some(kernel_info) = executing_kernel_info.take() {
-                                match kernel_info.kernel_type {
-                                    kernel::KernelType::Direct => {
-                                        let mut new_kernel: Box<dyn kernel::Kernel> = Box::new(direct_kernel::DirectKernel::new(
-                                            kernel_info.python_path.clone(),
-                                            kernel_info.name.clone(),
-                                            kernel_info.display_name.clone(),
-                                        ));
-                                        if new_kernel.connect().is_ok() {
-                                            editor.set_kernel(new_kernel);
-                                            editor.status_message = Some(("CANCELLED - Kernel reset (all variables lost)".to_string(), true));
-                                        } else {
-                                            editor.status_message = Some(("CANCELLED - Kernel reconnection failed".to_string(), true));
-                                        }
-                                    }
-                                    _ => {
-                                        editor.status_message = Some(("Execution cancelled - please reconnect kernel".to_string(), true));
-                                    }
-                                }
-                            } else {
-                                editor.status_message = Some(("Execution cancelled".to_string(), true));
-                            }
-
+                            cancel_execution(editor, executing_kernel_info.take());
                             renderer.force_redraw();
                             needs_redraw = true;
                         } else {
@@ -1032,6 +1157,100 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
                         }
                         commands::Command::None
                     }
+                    // File format conversion (Alt+L line endings, Alt+T indentation, Alt+B BOM)
+                    KeyCode::Char('l') | KeyCode::Char('L') if key.modifiers.contains(KeyModifiers::ALT) => {
+                        commands::Command::ToggleLineEnding
+                    }
+                    KeyCode::Char('t') | KeyCode::Char('T') if key.modifiers.contains(KeyModifiers::ALT) => {
+                        commands::Command::ToggleIndentStyle
+                    }
+                    KeyCode::Char('b') | KeyCode::Char('B') if key.modifiers.contains(KeyModifiers::ALT) => {
+                        commands::Command::ToggleBom
+                    }
+                    // Toggle the line number / cell status gutter (Alt+G)
+                    KeyCode::Char('g') | KeyCode::Char('G') if key.modifiers.contains(KeyModifiers::ALT) => {
+                        renderer.force_redraw();
+                        commands::Command::ToggleGutter
+                    }
+                    // Show cell outputs inline below each cell (Alt+I)
+                    KeyCode::Char('i') | KeyCode::Char('I') if key.modifiers.contains(KeyModifiers::ALT) => {
+                        commands::Command::ToggleInlineOutputs
+                    }
+                    // Fold the inline output of the current cell (Alt+O)
+                    KeyCode::Char('o') | KeyCode::Char('O') if key.modifiers.contains(KeyModifiers::ALT) => {
+                        commands::Command::ToggleOutputCollapsed
+                    }
+                    // Wrap long lines at the window width (Alt+W)
+                    KeyCode::Char('w') | KeyCode::Char('W') if key.modifiers.contains(KeyModifiers::ALT) => {
+                        renderer.force_redraw();
+                        commands::Command::ToggleSoftWrap
+                    }
+                    // Fold the cell or block at the cursor (Alt+F); fold all cells or unfold all (Alt+Shift+F)
+                    KeyCode::Char('f') | KeyCode::Char('F') if key.modifiers.contains(KeyModifiers::ALT) => {
+                        if key.modifiers.contains(KeyModifiers::SHIFT) || key.code == KeyCode::Char('F') {
+                            commands::Command::ToggleFoldAllCells
+                        } else {
+                            commands::Command::ToggleFold
+                        }
+                    }
+                    // Cell structure: Alt+N new cell below (Alt+Shift+N above), Alt+S split,
+                    // Alt+M merge with next, Alt+D duplicate, Alt+X delete
+                    KeyCode::Char('n') | KeyCode::Char('N') if key.modifiers.contains(KeyModifiers::ALT) => {
+                        if key.modifiers.contains(KeyModifiers::SHIFT) || key.code == KeyCode::Char('N') {
+                            commands::Command::InsertCellAbove
+                        } else {
+                            commands::Command::InsertCellBelow
+                        }
+                    }
+                    KeyCode::Char('s') | KeyCode::Char('S') if key.modifiers.contains(KeyModifiers::ALT) => {
+                        commands::Command::SplitCell
+                    }
+                    KeyCode::Char('p') | KeyCode::Char('P') if key.modifiers.contains(KeyModifiers::ALT) => {
+                        commands::Command::ToggleAutoPair
+                    }
+                    // Undo tree browser (Alt+U)
+                    KeyCode::Char('u') | KeyCode::Char('U') if key.modifiers.contains(KeyModifiers::ALT) => {
+                        autocomplete.hide();
+                        undo_browser = Some(undo_browser::UndoBrowser::new(editor));
+                        commands::Command::None
+                    }
+                    KeyCode::Char('m') | KeyCode::Char('M') if key.modifiers.contains(KeyModifiers::ALT) => {
+                        commands::Command::MergeCellBelow
+                    }
+                    // Alt+Shift+D / Alt+Shift+X do the same for the current line(s)
+                    KeyCode::Char('d') | KeyCode::Char('D') if key.modifiers.contains(KeyModifiers::ALT) => {
+                        if key.modifiers.contains(KeyModifiers::SHIFT) || key.code == KeyCode::Char('D') {
+                            commands::Command::DuplicateLines
+                        } else {
+                            commands::Command::DuplicateCell
+                        }
+                    }
+                    KeyCode::Char('x') | KeyCode::Char('X') if key.modifiers.contains(KeyModifiers::ALT) => {
+                        if key.modifiers.contains(KeyModifiers::SHIFT) || key.code == KeyCode::Char('X') {
+                            commands::Command::DeleteLines
+                        } else {
+                            commands::Command::DeleteCell
+                        }
+                    }
+                    // Next / previous cell (Alt+J / Alt+K), Alt+Shift+J joins lines
+                    KeyCode::Char('j') | KeyCode::Char('J') if key.modifiers.contains(KeyModifiers::ALT) => {
+                        if key.modifiers.contains(KeyModifiers::SHIFT) || key.code == KeyCode::Char('J') {
+                            commands::Command::JoinLines
+                        } else {
+                            commands::Command::NextCell
+                        }
+                    }
+                    // Comments: Ctrl+/ (reported as Ctrl+7 by many terminals) and Alt+/ for a block comment
+                    KeyCode::Char('/') | KeyCode::Char('7') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        commands::Command::ToggleComment
+                    }
+                    KeyCode::Char('/') if key.modifiers.contains(KeyModifiers::ALT) => {
+                        commands::Command::ToggleBlockComment
+                    }
+                    KeyCode::F(9) => commands::Command::SortLines,
+                    KeyCode::Char('k') | KeyCode::Char('K') if key.modifiers.contains(KeyModifiers::ALT) => {
+                        commands::Command::PrevCell
+                    }
                     KeyCode::Char(c) => commands::Command::InsertChar(c),
                     KeyCode::Enter => {
                         // Ctrl+Enter = Execute cell (primary binding)
@@ -1058,18 +1277,26 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
                     KeyCode::Tab => {
                         if autocomplete.is_visible() && !key.modifiers.contains(KeyModifiers::SHIFT) {
                             // Accept autocomplete suggestion
-                            if let Some(suggestion) = autocomplete.get_selected() {
-                                let prefix = editor.get_word_at_cursor();
+                            if let Some(suggestion) = autocomplete.get_selected().map(str::to_string) {
+                                let (text, replaced) = autocomplete.insertion(&suggestion, &editor.get_word_at_cursor(), editor.char_after_cursor());
                                 // Delete the prefix and insert the full suggestion
-                                for _ in 0..prefix.len() {
+                                for _ in 0..replaced {
                                     editor.execute(commands::Command::Backspace)?;
                                 }
-                                for ch in suggestion.chars() {
+                                for ch in text.chars() {
                                     editor.execute(commands::Command::InsertChar(ch))?;
                                 }
+                                autocomplete.record_accepted(&suggestion);
                                 autocomplete.hide();
                                 renderer.force_redraw(); // Force full redraw to clear autocomplete artifacts
-                                suppress_autocomplete_once = true; // Don't show autocomplete on next key
+                                match editor.path_completion_context().filter(|_| text.ends_with('/')) {
+                                    // Accepted a directory: list what is in it
+                                    Some((typed, quote)) => {
+                                        let base = editor.working_directory();
+                                        autocomplete.update_paths(&typed, quote, &base);
+                                    }
+                                    None => suppress_autocomplete_once = true, // Don't show autocomplete on next key
+                                }
                                 needs_redraw = true;
                             }
                             commands::Command::None
@@ -1124,7 +1351,7 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
                             
                             // Redraw the editor
                             renderer.draw(editor)?;
-                        } else {
+                        } else if confirm_overwrite(editor, renderer)? {
                             // Normal save
                             let _ = editor.execute(cmd);
                         }
@@ -1154,9 +1381,28 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
                         // Redraw the editor
                         renderer.draw(editor)?;
                     }
+                    commands::Command::GoToLine => {
+                        let mut prompt = prompt::Prompt::new("Go to Line (line or line:col)", "")
+                            .with_help("[Enter: Go] [Esc: Cancel]");
+                        execute!(io::stdout(), crossterm::cursor::Hide)?;
+                        let result = prompt.run_text(&mut io::stdout())?;
+
+                        // Clear the entire screen and force complete redraw
+                        execute!(io::stdout(),
+                            crossterm::terminal::Clear(crossterm::terminal::ClearType::All),
+                            crossterm::cursor::Hide
+                        )?;
+                        renderer.force_redraw();
+                        if let Some(spec) = result {
+                            editor.go_to_line(&spec);
+                        }
+                        renderer.draw(editor)?;
+                    }
                     commands::Command::FindReplace => {
                         // Open find/replace window
-                        find_replace = Some(find_replace::FindReplace::new());
+                        let mut fr = find_replace::FindReplace::new();
+                        fr.set_history(find_history.clone());
+                        find_replace = Some(fr);
                     }
                     commands::Command::None => {
                         // No command - don't override needs_redraw flag
@@ -1184,6 +1430,26 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
                         if suppress_autocomplete_once {
                             // Skip autocomplete update this cycle (after Tab completion)
                             suppress_autocomplete_once = false;
+                        } else if let Some(columns) = editor.column_completion_context()
+                            .filter(|_| should_update_autocomplete || should_check_backspace_delete)
+                        {
+                            // A DataFrame column name in a string: df["... or pl.col("...
+                            autocomplete.update_columns(columns);
+                            renderer.force_redraw();
+                        } else if let Some((typed, quote)) = editor.path_completion_context()
+                            .filter(|_| should_update_autocomplete || should_check_backspace_delete)
+                        {
+                            // A file path in a string: pd.read_csv("da...
+                            let base = editor.working_directory();
+                            autocomplete.update_paths(&typed, quote, &base);
+                            renderer.force_redraw();
+                        } else if let Some(import) = editor.import_completion_context()
+                            .filter(|_| should_update_autocomplete || should_check_backspace_delete)
+                        {
+                            // A module in an import statement: import pa..., from os import pa...
+                            let modules = editor.importable_modules(import.package.as_deref());
+                            autocomplete.update_imports(&import, &modules);
+                            renderer.force_redraw();
                         } else if should_update_autocomplete {
                             let (base_callable, prefix, is_sql_context) = editor.get_completion_context();
                             // Debug logging
@@ -1191,6 +1457,7 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
                                 use std::io::Write;
                                 let _ = writeln!(f, "DEBUG event_loop: should_update_autocomplete, base_callable={:?}, prefix='{}', is_sql={}", base_callable, prefix, is_sql_context);
                             }
+                            refresh_completion_sources(editor, &mut autocomplete, &prefix, is_sql_context);
                             autocomplete.update_with_context(base_callable, &prefix, is_sql_context);
                             renderer.force_redraw(); // Clear artifacts when menu changes
                         } else if should_check_backspace_delete {
@@ -1199,6 +1466,7 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
                                 autocomplete.hide();
                                 renderer.force_redraw();
                             } else {
+                                refresh_completion_sources(editor, &mut autocomplete, &prefix, is_sql_context);
                                 autocomplete.update_with_context(base_callable, &prefix, is_sql_context);
                                 renderer.force_redraw(); // Clear artifacts when menu changes
                             }
@@ -1214,16 +1482,130 @@ pub fn run(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io
                 renderer.force_redraw();
                 needs_redraw = true;
             }
+            Event::FocusGained => {
+                // Coming back from another program - the file may have been edited there
+                needs_redraw |= editor.check_disk_changes() != editor::DiskChange::Unchanged;
+            }
             _ => {
                 // Other events don't need redraw
             }
         }
     }
+
+    // Remember where we were for the next time this file is opened
+    let undo = editor.undo_history();
+    if let Some(path) = editor.file_path() {
+        session::save(path, &session::FileSession {
+            cursor: editor.cursor(),
+            viewport_offset: editor.viewport_offset(),
+            kernel: editor.current_kernel().or(executing_kernel_info),
+            outputs: output_pane.outputs().to_vec(),
+            find_history,
+            undo: Some(undo),
+        });
+    }
+    Ok(())
+}
+
+/// Before saving over a file that changed on disk, ask whether to reload it,
+/// overwrite it or look at the differences first. Returns true if the save should go ahead
+/// Gather what the suggestions for the word at the cursor are drawn from: the tables of
+/// the SQL statement around it, or static-analysis completions from the kernel when the
+/// cursor is in a new word (they cover every name valid where the word starts, so typing
+/// more of it only filters them)
+fn refresh_completion_sources(editor: &mut editor::Editor, autocomplete: &mut autocomplete::Autocomplete, prefix: &str, is_sql_context: bool) {
+    if is_sql_context {
+        autocomplete.set_sql_scope(editor.sql_scope().unwrap_or_default());
+        return;
+    }
+    if prefix.is_empty() {
+        return;
+    }
+    let word = prefix.rsplit('.').next().unwrap_or(prefix);
+    let word_start = editor.cursor().saturating_sub(word.len());
+    if autocomplete.needs_static_completions(word_start) {
+        let completions = editor.static_completions(word_start);
+        autocomplete.set_static_completions(word_start, completions);
+    }
+}
+
+fn confirm_overwrite(editor: &mut editor::Editor, renderer: &mut renderer::Renderer) -> io::Result<bool> {
+    if !editor.has_disk_conflict() {
+        return Ok(true);
+    }
+
+    let mut stdout = io::stdout();
+    execute!(stdout, crossterm::cursor::Hide)?;
+    let message = format!("{} was modified by another program since it was opened.", editor.file_name());
+    let proceed = loop {
+        let mut prompt = choice_prompt::ChoicePrompt::new(
+            "File changed on disk",
+            &[&message, "Overwrite it with your version, or reload and lose your edits?"],
+            &["Reload", "Overwrite", "View diff", "Cancel"],
+        );
+        match prompt.run(&mut stdout)? {
+            Some(0) => {
+                if let Err(e) = editor.reload_from_disk() {
+                    editor.status_message = Some((format!("Reload failed: {}", e), true));
+                }
+                break false;
+            }
+            Some(1) => break true,
+            Some(2) => {
+                let disk = editor.disk_text().unwrap_or_default();
+                let buffer = editor.buffer().to_string();
+                let title = format!("{}: disk (-) vs. buffer (+)", editor.file_name());
+                execute!(stdout, crossterm::terminal::Clear(crossterm::terminal::ClearType::All))?;
+                diff_view::DiffView::new(&title, diff::line_diff(&disk, &buffer)).run(&mut stdout)?;
+            }
+            _ => break false,
+        }
+    };
+
+    execute!(stdout,
+        crossterm::terminal::Clear(crossterm::terminal::ClearType::All),
+        crossterm::cursor::Hide
+    )?;
+    renderer.force_redraw();
+    renderer.draw(editor)?;
+    Ok(proceed)
+}
+
+/// Give up on the running execution (its channel was dropped): its cells stop showing as
+/// running, and a direct kernel, still busy with the abandoned code, is replaced by a fresh one
+fn cancel_execution(editor: &mut editor::Editor, executing_kernel_info: Option<kernel::KernelInfo>) {
+    editor.cancel_running_cells();
+
+    // Recreate a fresh kernel using stored info
+    let Some(kernel_info) = executing_kernel_info else {
+        editor.status_message = Some(("Execution cancelled".to_string(), true));
+        return;
+    };
+    match kernel_info.kernel_type {
+        kernel::KernelType::Direct => {
+            let mut new_kernel: Box<dyn kernel::Kernel> = Box::new(direct_kernel::DirectKernel::new(
+                kernel_info.python_path.clone(),
+                kernel_info.name.clone(),
+                kernel_info.display_name.clone(),
+            ));
+            // Connect the new kernel
+            if new_kernel.connect().is_ok() {
+                editor.set_kernel(new_kernel);
+                editor.status_message = Some(("CANCELLED - Kernel reset (all variables lost)".to_string(), true));
+            } else {
+                editor.status_message = Some(("CANCELLED - Kernel reconnection failed".to_string(), true));
+            }
+        }
+        _ => {
+            // For Jupyter or other kernel types, just report cancellation
+            editor.status_message = Some(("Execution cancelled - please reconnect kernel".to_string(), true));
+        }
+    }
 }
 
 fn spawn_background_execution(
     editor: &mut editor::Editor,
-) -> Option<(std::sync::mpsc::Receiver<(Box<dyn kernel::Kernel>, Vec<(usize, usize, String, bool, f64)>, Vec<kernel::CompletionItem>, kernel::TypeRelationships, kernel::SqlMetadata)>, kernel::KernelInfo)> {
+) -> Option<(std::sync::mpsc::Receiver<ExecutionMessage>, kernel::KernelInfo)> {
     // Extract kernel from editor (temporarily)
     let mut kernel = editor.take_kernel()?;
 
@@ -1236,7 +1618,7 @@ fn spawn_background_execution(
 
     // Clone cell data we need
     editor.update_cells();
-    let cells: Vec<(usize, usize, String)> = {
+    let cells: Vec<(usize, u64, crate::cell::CellType, String)> = {
         use crate::cell::{get_cell_at_position, get_cell_content};
 
         // Find cells to execute (same logic as execute_selected_cells_with_output)
@@ -1257,8 +1639,7 @@ fn spawn_background_execution(
         cells_to_execute.iter().map(|&idx| {
             let cell = &editor.get_cells_ref()[idx];
             let code = get_cell_content(editor.buffer_rope(), cell);
-            let cell_number = idx + 1;
-            (idx, cell_number, code)
+            (idx, cell.code_hash, cell.cell_type.clone(), code)
         }).collect()
     };
 
@@ -1267,6 +1648,9 @@ fn spawn_background_execution(
         return None;
     }
 
+    let running: Vec<usize> = cells.iter().map(|(idx, ..)| *idx).collect();
+    editor.mark_cells_running(&running);
+
     // Spawn background thread
     let (tx, rx) = std::sync::mpsc::channel();
     std::thread::spawn(move || {
@@ -1274,25 +1658,28 @@ fn spawn_background_execution(
         let mut all_completions = Vec::new();
         let mut type_relationships = crate::kernel::TypeRelationships::default();
         let mut sql_metadata = crate::kernel::SqlMetadata::default();
+        let mut cell_results = Vec::new();
 
-        for (_cell_idx, cell_number, code) in cells {
+        for (cell_idx, code_hash, cell_type, code) in cells {
+            let cell_number = cell_idx + 1;
             let start_time = std::time::Instant::now();
 
-            match kernel.execute(&code) {
-                Ok(result) => {
+            match crate::cell::execute_cell(kernel.as_mut(), &cell_type, &code) {
+                Ok(mut result) => {
                     let elapsed = start_time.elapsed().as_secs_f64();
                     let execution_count = result.execution_count.unwrap_or(0);
                     let output_text = crate::cell::format_output(&result);
                     let is_error = !result.success;
 
                     // Collect completions from this execution
-                    all_completions.extend(result.completions);
+                    all_completions.extend(std::mem::take(&mut result.completions));
 
                     // Update type relationships and SQL metadata (keep the latest)
-                    type_relationships = result.type_relationships;
-                    sql_metadata = result.sql_metadata;
+                    type_relationships = std::mem::take(&mut result.type_relationships);
+                    sql_metadata = std::mem::take(&mut result.sql_metadata);
 
                     results.push((execution_count, cell_number, output_text, is_error, elapsed));
+                    cell_results.push((cell_idx, code_hash, result, elapsed));
 
                     // Stop execution if this cell had an error
                     if is_error {
@@ -1302,14 +1689,26 @@ fn spawn_background_execution(
                 Err(e) => {
                     let elapsed = start_time.elapsed().as_secs_f64();
                     results.push((0, cell_number, format!("Error: {}", e), true, elapsed));
+                    cell_results.push((cell_idx, code_hash, crate::kernel::ExecutionResult {
+                        outputs: vec![crate::kernel::ExecutionOutput::Error {
+                            ename: "KernelError".to_string(),
+                            evalue: e.to_string(),
+                            traceback: Vec::new(),
+                        }],
+                        execution_count: None,
+                        success: false,
+                        completions: Vec::new(),
+                        type_relationships: Default::default(),
+                        sql_metadata: Default::default(),
+                    }, elapsed));
                     // Stop execution on kernel error
                     break;
                 }
             }
         }
 
-        // Send back kernel, results, completions, type relationships, and SQL metadata
-        let _ = tx.send((kernel, results, all_completions, type_relationships, sql_metadata));
+        // Send back kernel, results, completions, type relationships, SQL metadata and cell results
+        let _ = tx.send((kernel, results, all_completions, type_relationships, sql_metadata, cell_results));
     });
 
     Some((rx, kernel_info))
@@ -0,0 +1,292 @@
+/// Line ending style used by a file on disk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+}
+
+/// Indentation style used by a file on disk
+/// The buffer always holds spaces; this only controls what is written back
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndentStyle {
+    Spaces,
+    Tabs,
+    Mixed, // Both: only the lines that had tabs get them back
+}
+
+/// Width of one indentation level in the buffer
+pub const TAB_WIDTH: usize = 4;
+
+/// How a line was written on disk where the file's format alone would write it differently:
+/// leading whitespace in another style (`"\t    "` in a tab-indented file, `"  \t"` anywhere)
+/// or the line ending of the minority. None follows the file's format.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LineFormat {
+    pub indent: Option<String>,
+    pub line_ending: Option<LineEnding>,
+}
+
+/// Width of leading whitespace in the buffer, where each tab becomes one indentation level
+pub fn indent_width(indent: &str) -> usize {
+    indent.chars().map(|c| if c == '\t' { TAB_WIDTH } else { 1 }).sum()
+}
+
+/// Leading spaces and tabs of a line
+fn leading_whitespace(line: &str) -> &str {
+    &line[..line.len() - line.trim_start_matches([' ', '\t']).len()]
+}
+
+/// On-disk formatting of a file, detected on load and restored on save
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileFormat {
+    pub line_ending: LineEnding,
+    pub indent_style: IndentStyle,
+    pub has_bom: bool,
+}
+
+impl Default for FileFormat {
+    fn default() -> Self {
+        FileFormat {
+            line_ending: LineEnding::Lf,
+            indent_style: IndentStyle::Spaces,
+            has_bom: false,
+        }
+    }
+}
+
+impl LineEnding {
+    pub fn label(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "LF",
+            LineEnding::Crlf => "CRLF",
+        }
+    }
+
+    pub fn toggled(&self) -> Self {
+        match self {
+            LineEnding::Lf => LineEnding::Crlf,
+            LineEnding::Crlf => LineEnding::Lf,
+        }
+    }
+}
+
+impl IndentStyle {
+    pub fn label(&self) -> &'static str {
+        match self {
+            IndentStyle::Spaces => "Spaces",
+            IndentStyle::Tabs => "Tabs",
+            IndentStyle::Mixed => "Mixed",
+        }
+    }
+
+    pub fn toggled(&self) -> Self {
+        match self {
+            IndentStyle::Spaces => IndentStyle::Tabs,
+            IndentStyle::Tabs | IndentStyle::Mixed => IndentStyle::Spaces,
+        }
+    }
+}
+
+impl FileFormat {
+    /// Detect the formatting of raw file content (before normalization)
+    pub fn detect(content: &str) -> Self {
+        let has_bom = content.starts_with('\u{FEFF}');
+
+        // Majority vote between CRLF and bare LF line endings
+        let crlf_count = content.matches("\r\n").count();
+        let lf_count = content.matches('\n').count() - crlf_count;
+        let line_ending = if crlf_count > lf_count {
+            LineEnding::Crlf
+        } else {
+            LineEnding::Lf
+        };
+
+        let content = content.strip_prefix('\u{FEFF}').unwrap_or(content);
+        let tab_lines = content.lines().filter(|line| line.starts_with('\t')).count();
+        let space_lines = content.lines()
+            .filter(|line| line.starts_with(' ') && !line.trim().is_empty())
+            .count();
+        let indent_style = match (tab_lines > 0, space_lines > 0) {
+            (true, true) => IndentStyle::Mixed,
+            (true, false) => IndentStyle::Tabs,
+            _ => IndentStyle::Spaces,
+        };
+
+        FileFormat {
+            line_ending,
+            indent_style,
+            has_bom,
+        }
+    }
+
+    /// Text the buffer holds for raw file content: no BOM, LF line endings, and spaces for
+    /// the tabs in each line's indentation (tabs inside a line, like in TSV data, stay)
+    pub fn buffer_text(content: &str) -> String {
+        let content = content.strip_prefix('\u{FEFF}').unwrap_or(content);
+        let mut text = String::with_capacity(content.len());
+        for line in content.split_inclusive('\n') {
+            let indent = leading_whitespace(line);
+            text.extend(std::iter::repeat_n(' ', indent_width(indent)));
+            text.extend(line[indent.len()..].chars().filter(|&c| c != '\r'));
+        }
+        text
+    }
+
+    /// The lines of raw file content this format wouldn't write back as they are, by where
+    /// they start in its `buffer_text`
+    pub fn line_formats(&self, content: &str) -> Vec<(usize, LineFormat)> {
+        let content = content.strip_prefix('\u{FEFF}').unwrap_or(content);
+        let mut formats = Vec::new();
+        let mut start = 0;
+        for line in content.split_inclusive('\n') {
+            let indent = leading_whitespace(line);
+            let width = indent_width(indent);
+            let line_ending = if line.ends_with("\r\n") {
+                Some(LineEnding::Crlf)
+            } else if line.ends_with('\n') {
+                Some(LineEnding::Lf)
+            } else {
+                None
+            };
+            let format = LineFormat {
+                indent: (indent != self.indent(width)).then(|| indent.to_string()),
+                line_ending: line_ending.filter(|&ending| ending != self.line_ending),
+            };
+            if format != LineFormat::default() {
+                formats.push((start, format));
+            }
+            let rest = &line[indent.len()..];
+            start += width + rest.len() - rest.matches('\r').count();
+        }
+        formats
+    }
+
+    /// Leading whitespace this format writes for `width` columns of buffer indentation
+    fn indent(&self, width: usize) -> String {
+        match self.indent_style {
+            // Each full indentation level becomes a tab
+            IndentStyle::Tabs => "\t".repeat(width / TAB_WIDTH) + &" ".repeat(width % TAB_WIDTH),
+            // With mixed indentation, only lines with their own format get tabs
+            IndentStyle::Spaces | IndentStyle::Mixed => " ".repeat(width),
+        }
+    }
+
+    /// Convert buffer text (LF, space indentation) back to this format. The lines starting
+    /// at the offsets in `line_formats` (sorted) keep their own line ending, and their own
+    /// leading whitespace unless its width was edited.
+    pub fn apply(&self, text: &str, line_formats: &[(usize, LineFormat)]) -> String {
+        let mut result = String::with_capacity(text.len() + 16);
+
+        if self.has_bom {
+            result.push('\u{FEFF}');
+        }
+
+        let mut formats = line_formats.iter().peekable();
+        let mut start = 0;
+        for line in text.split_inclusive('\n') {
+            while formats.next_if(|(pos, _)| *pos < start).is_some() {}
+            let format = formats.next_if(|(pos, _)| *pos == start).map(|(_, format)| format);
+            start += line.len();
+
+            let (body, has_newline) = match line.strip_suffix('\n') {
+                Some(body) => (body, true),
+                None => (line, false),
+            };
+
+            let width = body.len() - body.trim_start_matches(' ').len();
+            match format.and_then(|format| format.indent.as_deref()) {
+                Some(indent) if indent_width(indent) == width => result.push_str(indent),
+                _ => result.push_str(&self.indent(width)),
+            }
+            result.push_str(&body[width..]);
+
+            if has_newline {
+                match format.and_then(|format| format.line_ending).unwrap_or(self.line_ending) {
+                    LineEnding::Lf => result.push('\n'),
+                    LineEnding::Crlf => result.push_str("\r\n"),
+                }
+            }
+        }
+
+        result
+    }
+
+    /// Short description for the status bar, e.g. "CRLF Tabs BOM"
+    pub fn label(&self) -> String {
+        let mut label = format!("{} {}", self.line_ending.label(), self.indent_style.label());
+        if self.has_bom {
+            label.push_str(" BOM");
+        }
+        label
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_and_apply_round_trip() {
+        // Tab-indented CRLF file with a BOM
+        let original = "\u{FEFF}all:\r\n\tcc -o main main.c\r\n\t\techo done\r\n";
+        let format = FileFormat::detect(original);
+        assert_eq!(format.line_ending, LineEnding::Crlf);
+        assert_eq!(format.indent_style, IndentStyle::Tabs);
+        assert!(format.has_bom);
+
+        // What the editor holds after normalization
+        let normalized = "all:\n    cc -o main main.c\n        echo done\n";
+        assert_eq!(format.apply(normalized, &[]), original);
+
+        // Plain LF/space files are written back untouched
+        let plain = "def f():\n    return 1\n";
+        let format = FileFormat::detect(plain);
+        assert_eq!(format, FileFormat::default());
+        assert_eq!(format.apply(plain, &[]), plain);
+    }
+
+    /// Load `original` the way the editor does and write it back
+    fn round_trip(original: &str) -> String {
+        let format = FileFormat::detect(original);
+        format.apply(&FileFormat::buffer_text(original), &format.line_formats(original))
+    }
+
+    #[test]
+    fn test_mixed_indentation_round_trip() {
+        // Tab-indented recipes and space-aligned continuation lines in one file
+        let original = "all: main\n\tcc -o main \\\n\t   main.c\nFILES = a.c \\\n        b.c\n";
+        let format = FileFormat::detect(original);
+        assert_eq!(format.indent_style, IndentStyle::Mixed);
+        let indented: Vec<usize> = format.line_formats(original).into_iter().map(|(pos, _)| pos).collect();
+        assert_eq!(indented, vec![10, 27]);
+
+        let normalized = "all: main\n    cc -o main \\\n       main.c\nFILES = a.c \\\n        b.c\n";
+        assert_eq!(FileFormat::buffer_text(original), normalized);
+        assert_eq!(round_trip(original), original);
+    }
+
+    #[test]
+    fn test_lines_keep_their_own_format() {
+        let originals = [
+            "all:\n\tcc -o main \\\n\t    main.c\n", // Tab, then spaces to align
+            "x = 1\n  \tx = 1\n",                    // Spaces, then a tab
+            "a\r\nb\nc\n",                           // Mixed line endings
+            "s = '\u{200B}'\u{FEFF}\n",               // Invisible characters
+            "\u{FEFF}\t\n  \n\tx\r\n",               // Whitespace-only lines
+        ];
+        for original in originals {
+            assert_eq!(round_trip(original), original);
+        }
+
+        // Lines whose indentation was edited, and new lines, follow the file's format
+        let original = "a:\n\t    b\nc\r\n";
+        let format = FileFormat::detect(original);
+        assert_eq!((format.line_ending, format.indent_style), (LineEnding::Lf, IndentStyle::Tabs));
+        let line_formats = format.line_formats(original);
+        assert_eq!(FileFormat::buffer_text(original), "a:\n        b\nc\n");
+        assert_eq!(line_formats.iter().map(|(pos, _)| *pos).collect::<Vec<_>>(), vec![3, 13]);
+
+        let edited = [(3, line_formats[0].1.clone()), (9, line_formats[1].1.clone())];
+        assert_eq!(format.apply("a:\n    b\nc\nd\n", &edited), "a:\n\tb\nc\r\nd\n");
+    }
+}
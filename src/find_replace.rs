@@ -18,6 +18,8 @@ pub struct FindReplace {
     total_matches: usize,
     matches: Vec<(usize, usize)>, // (start_byte, end_byte) positions
     clipboard: Clipboard,
+    history: Vec<String>,          // Previous searches, most recent first
+    history_index: Option<usize>,  // Position while browsing history with Up/Down
 }
 
 #[derive(Clone, Copy, PartialEq)]
@@ -38,8 +40,56 @@ impl FindReplace {
             total_matches: 0,
             matches: Vec::new(),
             clipboard: Clipboard::new().expect("Failed to access clipboard"),
+            history: Vec::new(),
+            history_index: None,
         }
     }
+
+    /// Set the search history (most recent first)
+    pub fn set_history(&mut self, history: Vec<String>) {
+        self.history = history;
+        self.history_index = None;
+    }
+
+    /// Get the search history (most recent first)
+    pub fn history(&self) -> &[String] {
+        &self.history
+    }
+
+    /// Add the current find text to the top of the history
+    pub fn remember_search(&mut self) {
+        if self.find_text.is_empty() {
+            return;
+        }
+        self.history.retain(|h| h != &self.find_text);
+        self.history.insert(0, self.find_text.clone());
+        self.history.truncate(crate::session::MAX_FIND_HISTORY);
+        self.history_index = None;
+    }
+
+    /// Step through previous searches (older = true goes back in time)
+    fn recall_history(&mut self, older: bool) -> bool {
+        if self.history.is_empty() {
+            return false;
+        }
+        let index = match (self.history_index, older) {
+            (None, true) => 0,
+            (None, false) => return false,
+            (Some(i), true) => (i + 1).min(self.history.len() - 1),
+            (Some(0), false) => {
+                // Back past the newest entry - clear the field
+                self.history_index = None;
+                self.find_text.clear();
+                self.cursor_pos = 0;
+                return true;
+            }
+            (Some(i), false) => i - 1,
+        };
+        self.history_index = Some(index);
+        self.find_text = self.history[index].clone();
+        self.cursor_pos = self.find_text.len();
+        true
+    }
     
     /// Update the search results
     pub fn update_matches(&mut self, matches: Vec<(usize, usize)>) {
@@ -647,6 +697,16 @@ impl FindReplace {
                 }
             }
             
+            // Up/Down in the find field = browse previous searches
+            KeyCode::Up | KeyCode::Down if self.active_field == Field::Find => {
+                self.selection_start = None;
+                if self.recall_history(key == KeyCode::Up) {
+                    InputResult::FindTextChanged
+                } else {
+                    InputResult::Continue
+                }
+            }
+
             KeyCode::Left => {
                 if modifiers.contains(KeyModifiers::SHIFT) {
                     // Shift+Left = select left
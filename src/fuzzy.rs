@@ -0,0 +1,88 @@
+/// Case-insensitive subsequence matching for filter-as-you-type lists.
+/// Returns a score (higher is better) and the char indices of `candidate` that matched,
+/// or None if the query characters don't all appear in order.
+pub fn fuzzy_match(query: &str, candidate: &str) -> Option<(i64, Vec<usize>)> {
+    let query: Vec<char> = query.chars().filter(|c| !c.is_whitespace()).flat_map(char::to_lowercase).collect();
+    if query.is_empty() {
+        return Some((0, Vec::new()));
+    }
+
+    let chars: Vec<char> = candidate.chars().collect();
+    let lower: Vec<char> = chars.iter().map(|c| c.to_lowercase().next().unwrap_or(*c)).collect();
+    let (n, m) = (chars.len(), query.len());
+    if m > n {
+        return None;
+    }
+
+    // Matches at word starts (after punctuation, or a camelCase hump) rank higher
+    let word_start: Vec<i64> = (0..n).map(|j| {
+        let prev = j.checked_sub(1).map(|p| chars[p]);
+        let is_start = prev.is_none_or(|p| !p.is_alphanumeric())
+            || (prev.is_some_and(|p| p.is_lowercase()) && chars[j].is_uppercase());
+        if is_start { 8 } else { 0 }
+    }).collect();
+
+    // best[i][j]: best score with query[i] matched at candidate position j
+    const NONE: i64 = i64::MIN;
+    let mut best = vec![vec![NONE; n]; m];
+    let mut from = vec![vec![0usize; n]; m];
+    for j in 0..n {
+        if lower[j] == query[0] {
+            best[0][j] = 1 + word_start[j] - j as i64; // Prefer matches that start early
+        }
+    }
+    for i in 1..m {
+        let (mut gap_best, mut gap_from) = (NONE, 0);
+        for j in i..n {
+            if j >= 2 && best[i - 1][j - 2] > gap_best {
+                gap_best = best[i - 1][j - 2];
+                gap_from = j - 2;
+            }
+            if lower[j] != query[i] {
+                continue;
+            }
+            if gap_best != NONE {
+                best[i][j] = gap_best + 1 + word_start[j];
+                from[i][j] = gap_from;
+            }
+            // Runs of consecutive matches get a bonus
+            if best[i - 1][j - 1] != NONE && best[i - 1][j - 1] + 6 + word_start[j] > best[i][j] {
+                best[i][j] = best[i - 1][j - 1] + 6 + word_start[j];
+                from[i][j] = j - 1;
+            }
+        }
+    }
+
+    let (mut j, score) = best[m - 1].iter().copied().enumerate()
+        .filter(|&(_, score)| score != NONE)
+        .max_by_key(|&(j, score)| (score, std::cmp::Reverse(j)))?;
+    let mut positions = vec![0; m];
+    for i in (0..m).rev() {
+        positions[i] = j;
+        j = from[i][j];
+    }
+
+    // Prefer short candidates
+    Some((score - (n as i64) / 8, positions))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzzy_match() {
+        assert!(fuzzy_match("ld", "Load data").is_some());
+        assert!(fuzzy_match("xyz", "Load data").is_none());
+        assert_eq!(fuzzy_match("", "anything").map(|(s, _)| s), Some(0));
+        assert_eq!(fuzzy_match("LD", "Load data").unwrap().1, vec![0, 5]);
+
+        // Word starts and consecutive runs beat scattered matches
+        let (word_start, _) = fuzzy_match("gb", "group_by").unwrap();
+        let (scattered, _) = fuzzy_match("gb", "aggrebate").unwrap();
+        assert!(word_start > scattered);
+        let (prefix, _) = fuzzy_match("dat", "data").unwrap();
+        let (spread, _) = fuzzy_match("dat", "delta_time").unwrap();
+        assert!(prefix > spread);
+    }
+}
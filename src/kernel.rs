@@ -25,6 +25,37 @@ pub struct CompletionItem {
     pub item_type: String,
 }
 
+/// What the kernel knows about a name, for the completion preview
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Documentation {
+    /// "function", "class", "module", or the type name of a value
+    pub kind: String,
+    /// Call signature like "(by=None, axis=0)", for callables
+    pub signature: Option<String>,
+    /// First paragraph of the docstring
+    pub doc: String,
+}
+
+/// Parameters of a callable, for the signature help shown while typing its arguments
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Signature {
+    /// Name the callable is called by, like "merge"
+    pub label: String,
+    pub parameters: Vec<SignatureParameter>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SignatureParameter {
+    pub name: String,
+    /// The parameter as written in the signature, like "how='inner'" or "*args"
+    pub text: String,
+    /// `inspect.Parameter` kind in lower case, like "positional_or_keyword"
+    pub kind: String,
+}
+
 /// Type relationship data for intelligent autocomplete
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TypeRelationships {
@@ -35,6 +66,10 @@ pub struct TypeRelationships {
     /// Maps type names to their methods (e.g., "DataFrame" -> ["head", "tail", ...])
     #[serde(default)]
     pub type_methods: std::collections::HashMap<String, Vec<String>>,
+
+    /// Maps DataFrame and relation variables to their column names (e.g., "df" -> ["id", "name"])
+    #[serde(default)]
+    pub frame_columns: std::collections::HashMap<String, Vec<String>>,
 }
 
 impl Default for TypeRelationships {
@@ -42,6 +77,7 @@ impl Default for TypeRelationships {
         TypeRelationships {
             return_types: std::collections::HashMap::new(),
             type_methods: std::collections::HashMap::new(),
+            frame_columns: std::collections::HashMap::new(),
         }
     }
 }
@@ -57,6 +93,10 @@ pub struct SqlMetadata {
     #[serde(default)]
     pub columns: Vec<String>,
 
+    /// Column types by "table.column" (from DESCRIBE)
+    #[serde(default)]
+    pub column_types: std::collections::HashMap<String, String>,
+
     /// SQL function names
     #[serde(default)]
     pub functions: Vec<String>,
@@ -67,6 +107,7 @@ impl Default for SqlMetadata {
         SqlMetadata {
             tables: Vec::new(),
             columns: Vec::new(),
+            column_types: std::collections::HashMap::new(),
             functions: Vec::new(),
         }
     }
@@ -84,7 +125,7 @@ pub struct ExecutionResult {
 }
 
 /// Information about an available Python kernel
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct KernelInfo {
     pub name: String,
     pub display_name: String,
@@ -93,7 +134,7 @@ pub struct KernelInfo {
 }
 
 /// Type of kernel connection
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum KernelType {
     /// Direct Python subprocess
     Direct,
@@ -117,6 +158,40 @@ pub trait Kernel: Send {
 
     /// Get kernel information
     fn info(&self) -> KernelInfo;
+
+    /// Look up the documentation of a (dotted) name without running user code
+    fn inspect(&mut self, _name: &str) -> Result<Option<Documentation>, Box<dyn Error>> {
+        Ok(None)
+    }
+
+    /// Look up the signature of a callable without running user code. With a `base`
+    /// callable, `name` is a method of what `base` returns (for method chains).
+    fn signature(&mut self, _name: &str, _base: Option<&str>) -> Result<Option<Signature>, Box<dyn Error>> {
+        Ok(None)
+    }
+
+    /// Completions from static analysis of `code` at a 0-based line and column (in chars),
+    /// merged with the names that exist at runtime. Empty if the kernel can't analyze code.
+    fn complete(&mut self, _code: &str, _line: usize, _column: usize) -> Result<Vec<CompletionItem>, Box<dyn Error>> {
+        Ok(Vec::new())
+    }
+
+    /// Modules that can be imported: the top-level ones, or with a `package`, its submodules
+    /// (type "module") and the names it exports, for `from package import`
+    fn modules(&mut self, _package: Option<&str>) -> Result<Vec<CompletionItem>, Box<dyn Error>> {
+        Ok(Vec::new())
+    }
+
+    /// Run the SQL of a `##$$ sql` cell on a connection in the namespace (the only one
+    /// there is if `connection` is None), binding the result to `variable` if given
+    fn execute_sql(&mut self, _sql: &str, _connection: Option<&str>, _variable: Option<&str>) -> Result<ExecutionResult, Box<dyn Error>> {
+        Err("This kernel can't run SQL cells".into())
+    }
+
+    /// The kernel's working directory, which relative paths in code are resolved against
+    fn working_directory(&mut self) -> Result<Option<std::path::PathBuf>, Box<dyn Error>> {
+        Ok(None)
+    }
 }
 
 /// Discover available Python kernels on the system
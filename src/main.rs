@@ -14,6 +14,20 @@ mod output_pane;
 mod autocomplete;
 mod event_loop;
 mod sql_context;
+mod file_format;
+mod state;
+mod swap;
+mod diff;
+mod diff_view;
+mod choice_prompt;
+mod session;
+mod recent_files;
+mod fuzzy;
+mod cell_outline;
+mod undo_tree;
+mod undo_browser;
+mod signature_help;
+mod path_completion;
 
 use kernel::Kernel;
 
@@ -23,6 +37,7 @@ use crossterm::{
     terminal::{disable_raw_mode, enable_raw_mode},
 };
 use std::io::{self, Write};
+use std::path::Path;
 
 fn debug_log(msg: &str) {
     use std::fs::OpenOptions;
@@ -172,7 +187,7 @@ fn execute_file(file_path: Option<String>, python_path: Option<String>) -> io::R
         }
 
         // Execute cell
-        match kernel.execute(&code) {
+        match cell::execute_cell(&mut kernel, &cell.cell_type, &code) {
             Ok(result) => {
                 // Print outputs
                 for output in &result.outputs {
@@ -340,6 +355,9 @@ fn main() -> io::Result<()> {
     // Enable bracketed paste mode
     execute!(io::stdout(), event::EnableBracketedPaste)?;
 
+    // Report focus changes so files edited elsewhere are picked up on return
+    let _ = execute!(io::stdout(), event::EnableFocusChange);
+
     // Enable enhanced keyboard protocol for better key combination support
     // This helps disambiguate Ctrl+Backspace from Ctrl+H
     if let Ok(_) = execute!(
@@ -359,10 +377,24 @@ fn main() -> io::Result<()> {
     // Set initial help message
     editor.status_message = Some(("Press Ctrl+K to select kernel, Ctrl+E to execute cell".to_string(), false));
 
-    // Load file if provided
+    // Without a file argument, offer to reopen a recent file
+    let file_to_execute = match file_to_execute {
+        Some(path) => Some(path),
+        None => recent_files::RecentFiles::new(session::recent_files())
+            .run(&mut io::stdout())?
+            .map(|path| path.to_string_lossy().to_string()),
+    };
+
+    // Load file if provided, restoring the cursor, interpreter and undo history from its last session
+    let mut file_session = session::FileSession::default();
     if let Some(path) = file_to_execute {
         match editor.load_file(&path) {
-            Ok(_) => {},
+            Ok(_) => {
+                file_session = session::load(Path::new(&path)).unwrap_or_default();
+                editor.restore_position(file_session.cursor, file_session.viewport_offset);
+                editor.select_initial_kernel(file_session.kernel.as_ref());
+                editor.restore_undo_history(file_session.undo.take());
+            },
             Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
                 // File doesn't exist - create new file with this path
                 editor.set_file_path(&path);
@@ -373,16 +405,27 @@ fn main() -> io::Result<()> {
         }
     }
     
+    // Offer to recover unsaved changes left behind by a crashed session
+    if let Err(e) = offer_recovery(&mut editor) {
+        editor.status_message = Some((format!("Recovery failed: {}", e), true));
+    }
+
     // Initialize viewport to follow cursor
     editor.update_viewport_for_cursor();
     
     // Main loop
-    let result = event_loop::run(&mut editor, &mut renderer);
+    let result = event_loop::run(&mut editor, &mut renderer, file_session);
+
+    // Clean exit - the swap file is no longer needed
+    if result.is_ok() {
+        editor.remove_swap_file();
+    }
     
     // Cleanup
     renderer.cleanup()?;
     execute!(io::stdout(), crossterm::event::DisableMouseCapture)?;
     execute!(io::stdout(), event::DisableBracketedPaste)?;
+    let _ = execute!(io::stdout(), event::DisableFocusChange);
     // Disable enhanced keyboard protocol
     let _ = execute!(io::stdout(), crossterm::event::PopKeyboardEnhancementFlags);
     disable_raw_mode()?;
@@ -394,6 +437,68 @@ fn main() -> io::Result<()> {
     Ok(())
 }
 
+/// Check for a swap file from a session that didn't exit cleanly and let the user
+/// restore it, compare it with the file on disk, or throw it away
+fn offer_recovery(editor: &mut editor::Editor) -> io::Result<()> {
+    let (swap_path, snapshot) = match editor.find_recovery() {
+        Some(found) => found,
+        None => return Ok(()),
+    };
+
+    let age_secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+        .saturating_sub(snapshot.saved_at);
+    let age = if age_secs < 120 {
+        format!("{} seconds ago", age_secs)
+    } else if age_secs < 2 * 3600 {
+        format!("{} minutes ago", age_secs / 60)
+    } else if age_secs < 2 * 86400 {
+        format!("{} hours ago", age_secs / 3600)
+    } else {
+        format!("{} days ago", age_secs / 86400)
+    };
+
+    let name = editor.file_name().to_string();
+    let first_line = format!("{} has unsaved changes from a session that ended unexpectedly", name);
+    let second_line = format!("Snapshot saved {}", age);
+
+    let mut stdout = io::stdout();
+    loop {
+        let mut prompt = choice_prompt::ChoicePrompt::new(
+            "Recover unsaved changes?",
+            &[&first_line, &second_line],
+            &["Restore", "View diff", "Discard"],
+        );
+        let choice = prompt.run(&mut stdout)?;
+        execute!(stdout, crossterm::terminal::Clear(crossterm::terminal::ClearType::All))?;
+
+        match choice {
+            Some(0) => {
+                editor.replace_buffer_text(&snapshot.content);
+                swap::remove_file(&swap_path);
+                editor.status_message = Some(("Recovered unsaved changes (Ctrl+Z to undo)".to_string(), false));
+                return Ok(());
+            }
+            Some(1) => {
+                let lines = diff::line_diff(&editor.buffer().to_string(), &snapshot.content);
+                let title = format!("{}: on disk (-) vs recovered (+)", name);
+                diff_view::DiffView::new(&title, lines).run(&mut stdout)?;
+            }
+            Some(2) => {
+                swap::remove_file(&swap_path);
+                editor.status_message = Some(("Discarded recovered changes".to_string(), false));
+                return Ok(());
+            }
+            _ => {
+                // Esc: leave the swap file alone for now
+                return Ok(());
+            }
+        }
+    }
+}
+
 fn launch_in_terminal(terminal: &str, command: &[&str]) -> bool {
     // First check if the terminal exists
     if let Ok(output) = std::process::Command::new("which").arg(terminal).output() {
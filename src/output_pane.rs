@@ -4,10 +4,11 @@ use crossterm::{
     terminal::{Clear, ClearType},
     style::{Color, Print, ResetColor, SetForegroundColor},
 };
+use serde::{Deserialize, Serialize};
 use std::io::{self, Write};
 use std::time::{Duration, Instant};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OutputEntry {
     pub execution_count: usize,
     pub cell_line: usize,
@@ -104,6 +105,11 @@ impl OutputPane {
         self.scroll_to_bottom();
     }
 
+    /// All outputs, oldest first (for saving the session)
+    pub fn outputs(&self) -> &[OutputEntry] {
+        &self.outputs
+    }
+
     pub fn scroll_to_bottom(&mut self) {
         // Enable auto-scroll mode and move cursor to end
         self.auto_scroll = true;
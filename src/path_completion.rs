@@ -0,0 +1,93 @@
+use glob::{MatchOptions, Pattern};
+use std::path::{Path, PathBuf};
+
+/// Entries listed at most for one completion
+const MAX_CANDIDATES: usize = 200;
+
+/// A file or directory whose name starts with the last component of a typed path
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathCandidate {
+    pub name: String,
+    pub is_dir: bool,
+}
+
+/// Entries of the directory part of `typed` whose names start with its last component,
+/// directories first. Relative paths are resolved against `base` and `~/` against the home
+/// directory. Hidden entries are only listed when the typed name starts with a dot.
+pub fn complete_path(base: &Path, typed: &str) -> Vec<PathCandidate> {
+    let (dir, partial) = match typed.rfind('/') {
+        Some(slash) => (&typed[..=slash], &typed[slash + 1..]),
+        None => ("", typed),
+    };
+    let dir = match dir.strip_prefix("~/") {
+        Some(rest) => match std::env::var_os("HOME") {
+            Some(home) => PathBuf::from(home).join(rest),
+            None => return Vec::new(),
+        },
+        None => base.join(dir),
+    };
+    let Some(dir) = dir.to_str() else {
+        return Vec::new();
+    };
+
+    let pattern = format!("{}/{}*", Pattern::escape(dir.trim_end_matches('/')), Pattern::escape(partial));
+    let options = MatchOptions {
+        case_sensitive: true,
+        require_literal_separator: true,
+        require_literal_leading_dot: !partial.starts_with('.'),
+    };
+    let Ok(paths) = glob::glob_with(&pattern, options) else {
+        return Vec::new();
+    };
+    let mut candidates: Vec<PathCandidate> = paths.flatten()
+        .take(MAX_CANDIDATES)
+        .filter_map(|path| {
+            let name = path.file_name()?.to_str()?.to_string();
+            Some(PathCandidate { name, is_dir: path.is_dir() })
+        })
+        .collect();
+    candidates.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.name.cmp(&b.name)));
+    candidates
+}
+
+/// Longest text all the candidates' names start with
+pub fn common_prefix(candidates: &[PathCandidate]) -> String {
+    let Some((first, rest)) = candidates.split_first() else {
+        return String::new();
+    };
+    let mut prefix = first.name.as_str();
+    for candidate in rest {
+        let len = prefix.char_indices()
+            .zip(candidate.name.chars())
+            .find(|((_, a), b)| a != b)
+            .map_or(prefix.len().min(candidate.name.len()), |((i, _), _)| i);
+        prefix = &prefix[..len];
+    }
+    prefix.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_complete_path() {
+        let base = std::env::temp_dir().join(format!("sage_path_test_{}", std::process::id()));
+        std::fs::create_dir_all(base.join("data_dir")).unwrap();
+        for file in ["data.csv", "notes.txt", ".hidden", "data_dir/inner.parquet"] {
+            std::fs::write(base.join(file), "").unwrap();
+        }
+
+        let names = |typed: &str| -> Vec<(String, bool)> {
+            complete_path(&base, typed).into_iter().map(|c| (c.name, c.is_dir)).collect()
+        };
+        assert_eq!(names("da"), vec![("data_dir".to_string(), true), ("data.csv".to_string(), false)]);
+        assert_eq!(names("data_dir/"), vec![("inner.parquet".to_string(), false)]);
+        assert!(!names("").iter().any(|(name, _)| name == ".hidden"));
+        assert_eq!(names(".h"), vec![(".hidden".to_string(), false)]);
+        assert!(names("missing/").is_empty());
+        assert_eq!(common_prefix(&complete_path(&base, "da")), "data");
+
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+}
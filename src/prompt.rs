@@ -7,12 +7,17 @@ use crossterm::{
 };
 use std::io::{self, Write};
 use std::path::{Path, PathBuf};
+use crate::path_completion::{common_prefix, complete_path};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 pub struct Prompt {
     title: String,
     input: String,
     cursor_pos: usize,
     initial_path: String,
+    help_text: String,
+    complete_paths: bool, // Tab completes file names (when entering a path)
+    listing: Option<String>, // Names matching an ambiguous Tab, shown in place of the help
     // Cache window dimensions
     prompt_x: u16,
     prompt_y: u16,
@@ -28,6 +33,9 @@ impl Prompt {
             input: initial_path.to_string(),
             cursor_pos: initial_path.len(),
             initial_path: initial_path.to_string(),
+            help_text: "[Enter: Save] [Tab: Complete] [Esc: Cancel]".to_string(),
+            complete_paths: false,
+            listing: None,
             prompt_x: 0,
             prompt_y: 0,
             prompt_width: 0,
@@ -36,6 +44,12 @@ impl Prompt {
         }
     }
     
+    /// Replace the key help shown at the bottom of the window
+    pub fn with_help(mut self, help_text: &str) -> Self {
+        self.help_text = help_text.to_string();
+        self
+    }
+
     /// Draw the complete prompt window (borders, title, etc.)
     fn draw_window(&mut self, stdout: &mut io::Stdout) -> io::Result<()> {
         let (width, height) = terminal::size()?;
@@ -97,15 +111,23 @@ impl Prompt {
                 // Input line - will be updated separately
                 write!(stdout, "│{:width$}│", "", width = self.prompt_width - 2)?;
             } else if y == self.prompt_height - 2 {
-                // Help text line
-                let help_text = "[Enter: Save] [Esc: Cancel]";
-                let padding = (self.prompt_width - 2 - help_text.len()) / 2;
+                // Help text line (or the names a Tab could complete to)
+                let mut help_width = 0;
+                let help_text: String = self.listing.as_deref().unwrap_or(&self.help_text)
+                    .chars()
+                    .take_while(|c| {
+                        help_width += c.width().unwrap_or(0);
+                        help_width <= self.prompt_width.saturating_sub(4)
+                    })
+                    .collect();
+                let help_width = help_text.width();
+                let padding = self.prompt_width.saturating_sub(2 + help_width) / 2;
                 write!(stdout, "│")?;
                 execute!(
                     stdout,
                     SetForegroundColor(Color::Rgb { r: 130, g: 130, b: 140 }),
                     Print(format!("{:>width$}{}{:<width2$}", "", help_text, "", 
-                        width = padding, width2 = self.prompt_width - 2 - padding - help_text.len())),
+                        width = padding, width2 = self.prompt_width.saturating_sub(2 + padding + help_width))),
                     SetForegroundColor(Color::Rgb { r: 200, g: 200, b: 205 }),
                 )?;
                 write!(stdout, "│")?;
@@ -176,6 +198,38 @@ impl Prompt {
     
     /// Handle input and return the final path when Enter is pressed
     pub fn run(&mut self, stdout: &mut io::Stdout) -> io::Result<Option<PathBuf>> {
+        self.complete_paths = true;
+        Ok(self.run_text(stdout)?.map(PathBuf::from))
+    }
+
+    /// Complete the file name before the cursor, relative to the current directory: as far
+    /// as all the matching names agree, listing them if there are several
+    fn complete_path(&mut self) {
+        let typed = &self.input[..self.cursor_pos];
+        let partial_len = typed.len() - typed.rfind('/').map_or(0, |slash| slash + 1);
+        let base = std::env::current_dir().unwrap_or_default();
+        let candidates = complete_path(&base, typed);
+
+        let completion = match candidates.as_slice() {
+            [] => return,
+            [only] if only.is_dir => format!("{}/", only.name),
+            [only] => only.name.clone(),
+            _ => {
+                let names: Vec<String> = candidates.iter()
+                    .map(|c| if c.is_dir { format!("{}/", c.name) } else { c.name.clone() })
+                    .collect();
+                self.listing = Some(names.join("  "));
+                common_prefix(&candidates)
+            }
+        };
+        if completion.len() > partial_len {
+            self.input.insert_str(self.cursor_pos, &completion[partial_len..]);
+            self.cursor_pos += completion.len() - partial_len;
+        }
+    }
+
+    /// Handle input and return the entered text when Enter is pressed
+    pub fn run_text(&mut self, stdout: &mut io::Stdout) -> io::Result<Option<String>> {
         // Draw the complete window initially
         self.draw_window(stdout)?;
         self.update_input_field(stdout)?;
@@ -189,16 +243,27 @@ impl Prompt {
                 }
                 
                 let mut input_changed = false;
+                if self.listing.take().is_some() {
+                    self.draw_window(stdout)?;
+                    input_changed = true;
+                }
                 
                 match key.code {
                     KeyCode::Enter => {
                         if !self.input.is_empty() {
-                            return Ok(Some(PathBuf::from(&self.input)));
+                            return Ok(Some(self.input.clone()));
                         }
                     }
                     KeyCode::Esc => {
                         return Ok(None);
                     }
+                    KeyCode::Tab if self.complete_paths => {
+                        self.complete_path();
+                        if self.listing.is_some() {
+                            self.draw_window(stdout)?;
+                        }
+                        input_changed = true;
+                    }
                     KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
                         self.input.insert(self.cursor_pos, c);
                         self.cursor_pos += 1;
@@ -0,0 +1,151 @@
+use crossterm::{
+    cursor::{Hide, MoveTo},
+    event::{self, Event, KeyCode},
+    execute,
+    style::{Attribute, Color, Print, ResetColor, SetAttribute, SetBackgroundColor, SetForegroundColor},
+    terminal::{self, Clear, ClearType},
+};
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+/// List of recently opened files, shown when Sage starts without a file argument
+pub struct RecentFiles {
+    files: Vec<PathBuf>,
+    selected: usize,
+}
+
+impl RecentFiles {
+    pub fn new(files: Vec<PathBuf>) -> Self {
+        Self { files, selected: 0 }
+    }
+
+    /// Show the list and return the chosen file (None = start with an empty buffer)
+    pub fn run(&mut self, stdout: &mut io::Stdout) -> io::Result<Option<PathBuf>> {
+        if self.files.is_empty() {
+            return Ok(None);
+        }
+
+        let result = loop {
+            self.draw(stdout)?;
+
+            if let Event::Key(key) = event::read()? {
+                if key.kind == event::KeyEventKind::Release {
+                    continue;
+                }
+
+                match key.code {
+                    KeyCode::Enter => break Some(self.files[self.selected].clone()),
+                    KeyCode::Esc | KeyCode::Char('q') => break None,
+                    KeyCode::Up | KeyCode::Char('k') => self.selected = self.selected.saturating_sub(1),
+                    KeyCode::Down | KeyCode::Char('j') => {
+                        self.selected = (self.selected + 1).min(self.files.len() - 1);
+                    }
+                    KeyCode::Home => self.selected = 0,
+                    KeyCode::End => self.selected = self.files.len() - 1,
+                    KeyCode::Char(c) if c.is_ascii_digit() && c != '0' => {
+                        // 1-9 open the corresponding entry directly
+                        let idx = c as usize - '1' as usize;
+                        if idx < self.files.len() {
+                            break Some(self.files[idx].clone());
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        };
+
+        execute!(stdout, Clear(ClearType::All))?;
+        Ok(result)
+    }
+
+    fn draw(&self, stdout: &mut io::Stdout) -> io::Result<()> {
+        let (width, height) = terminal::size()?;
+        let box_width = (width as usize).min(90);
+        let inner_width = box_width.saturating_sub(2);
+        let list_height = self.files.len().min((height as usize).saturating_sub(6)).max(1);
+        let box_height = list_height + 4;
+        let start_x = (width as usize).saturating_sub(box_width) / 2;
+        let start_y = (height as usize).saturating_sub(box_height) / 2;
+
+        let scroll = (self.selected + 1).saturating_sub(list_height);
+
+        let background = Color::Rgb { r: 40, g: 40, b: 45 };
+        let border = Color::Rgb { r: 200, g: 200, b: 205 };
+        let home = std::env::var("HOME").unwrap_or_default();
+
+        execute!(
+            stdout,
+            MoveTo(start_x as u16, start_y as u16),
+            SetBackgroundColor(background),
+            SetForegroundColor(border),
+            Print(format!("╭{}╮", "─".repeat(inner_width))),
+            MoveTo(start_x as u16, (start_y + 1) as u16),
+            Print("│ "),
+            SetAttribute(Attribute::Bold),
+            SetForegroundColor(Color::White),
+            Print(Self::fit("Recent files", inner_width.saturating_sub(1))),
+            SetAttribute(Attribute::Reset),
+            SetBackgroundColor(background),
+            SetForegroundColor(border),
+            Print("│"),
+            MoveTo(start_x as u16, (start_y + 2) as u16),
+            Print(format!("├{}┤", "─".repeat(inner_width))),
+        )?;
+
+        for row in 0..list_height {
+            let idx = scroll + row;
+            let y = (start_y + 3 + row) as u16;
+            execute!(stdout, MoveTo(start_x as u16, y), SetBackgroundColor(background), SetForegroundColor(border), Print("│"))?;
+
+            let text = match self.files.get(idx) {
+                Some(path) => {
+                    let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+                    let dir = path.parent().map(|p| p.to_string_lossy().to_string()).unwrap_or_default();
+                    // Show the home directory as ~ to keep paths short
+                    let dir = match dir.strip_prefix(&home) {
+                        Some(rest) if !home.is_empty() => format!("~{}", rest),
+                        _ => dir,
+                    };
+                    let number = if idx < 9 { format!("{}", idx + 1) } else { " ".to_string() };
+                    format!(" {} {}  {}", number, name, dir)
+                }
+                None => String::new(),
+            };
+
+            let selected = idx == self.selected;
+            execute!(
+                stdout,
+                SetBackgroundColor(if selected { Color::Rgb { r: 60, g: 90, b: 130 } } else { background }),
+                SetForegroundColor(if selected { Color::White } else { Color::Rgb { r: 180, g: 180, b: 185 } }),
+                Print(Self::fit(&text, inner_width)),
+                SetBackgroundColor(background),
+                SetForegroundColor(border),
+                Print("│"),
+            )?;
+        }
+
+        let footer = " ↑↓: Select  Enter/1-9: Open  Esc: New file ";
+        execute!(
+            stdout,
+            MoveTo(start_x as u16, (start_y + 3 + list_height) as u16),
+            Print(format!("╰{}╯", Self::fit_with(footer, inner_width, '─'))),
+            ResetColor,
+            Hide
+        )?;
+
+        stdout.flush()?;
+        Ok(())
+    }
+
+    fn fit(text: &str, width: usize) -> String {
+        Self::fit_with(text, width, ' ')
+    }
+
+    /// Truncate or pad text to exactly `width` characters
+    fn fit_with(text: &str, width: usize, pad: char) -> String {
+        let mut result: String = text.chars().take(width).collect();
+        let used = result.chars().count();
+        result.extend(std::iter::repeat_n(pad, width - used));
+        result
+    }
+}
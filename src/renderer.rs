@@ -1,4 +1,4 @@
-use crate::editor::Editor;
+use crate::editor::{DisplayRow, Editor, OutputStyle};
 use crate::syntax::{HighlightSpan, SyntaxState};
 use crossterm::{
     cursor::{Hide, MoveTo, Show},
@@ -156,11 +156,22 @@ impl Renderer {
             crate::debug_log("draw_with_bottom_window: update_syntax_highlighting complete");
         }
 
+        // Keep cell boundaries and execution state current for the gutter and inline outputs
+        if editor.gutter_width() > 0 || editor.inline_outputs_enabled() {
+            editor.refresh_cells();
+        }
+        editor.sync_folds();
+        editor.refresh_output_layout();
+        let gutter_width = editor.gutter_width();
+        let text_width = (width as usize).saturating_sub(gutter_width);
+        editor.set_wrap_width(text_width);
+
         crate::debug_log("draw_with_bottom_window: getting viewport_offset");
         // Now get all the data we need with immutable borrows
         let viewport_offset = editor.viewport_offset();
         crate::debug_log("draw_with_bottom_window: getting selection");
-        let selection = editor.selection();
+        let selections = editor.selections();
+        let extra_cursors = editor.extra_cursor_positions();
         crate::debug_log("draw_with_bottom_window: getting buffer");
         let buffer = editor.buffer();
         crate::debug_log("draw_with_bottom_window: getting matching_brackets");
@@ -171,6 +182,7 @@ impl Renderer {
         let find_matches = editor.get_find_matches();
         crate::debug_log("draw_with_bottom_window: getting current_find_match");
         let current_find_match = editor.get_current_find_match();
+        let display_rows = editor.display_rows(viewport_offset.0, content_height);
 
         crate::debug_log("draw_with_bottom_window: hiding cursor");
         // Hide cursor while drawing
@@ -182,7 +194,7 @@ impl Renderer {
 
         crate::debug_log(&format!("draw_with_bottom_window: starting line drawing loop, content_height = {}", content_height));
         // Draw all lines
-        for screen_row in 0..content_height {
+        for (screen_row, display_row) in display_rows.iter().enumerate() {
             if screen_row == 0 || screen_row == content_height - 1 || screen_row % 10 == 0 {
                 crate::debug_log(&format!("draw_with_bottom_window: drawing screen_row {}", screen_row));
             }
@@ -191,16 +203,20 @@ impl Renderer {
             // Calculate which logical line we're displaying
             // Logical lines: 0 and 1 are virtual, 2+ map to buffer lines 0+
             let logical_line = viewport_offset.0 + screen_row;
-            
-            if logical_line < 2 {
+
+            if let DisplayRow::Output { text, style, .. } = display_row {
+                // Inline output of the cell above (not part of the buffer)
+                line_content = Self::format_output_row(text, *style, gutter_width, text_width);
+            } else if logical_line < 2 {
                 // Virtual lines before the buffer - respect horizontal scrolling
                 if viewport_offset.1 == 0 {
                     // Only show the ~ if we're not horizontally scrolled
                     line_content.push_str("\x1b[48;2;30;30;30m"); // Consistent background
+                    line_content.push_str(&" ".repeat(gutter_width));
                     line_content.push_str("\x1b[38;2;110;110;110m"); // Dim grey like comments
                     line_content.push('~');
                     line_content.push_str("\x1b[39m"); // Reset foreground color
-                    for _ in 1..width {
+                    for _ in 1..text_width {
                         line_content.push(' ');
                     }
                     line_content.push_str("\x1b[0m");
@@ -213,8 +229,12 @@ impl Renderer {
                     line_content.push_str("\x1b[0m");
                 }
             } else {
-                // Map logical line to buffer line (subtract 2 for the virtual lines)
-                let file_row = logical_line - 2;
+                // Map the display row to a buffer line (past the end for the virtual lines after it)
+                let (file_row, segment_start) = match *display_row {
+                    DisplayRow::Line(line) => (line, 0),
+                    DisplayRow::Wrap { line, start } => (line, start),
+                    _ => (buffer.len_lines(), 0),
+                };
                 
                 if file_row < buffer.len_lines() {
                     let line = buffer.line(file_row);
@@ -226,6 +246,11 @@ impl Renderer {
                     
                     // Calculate byte positions for this line
                     let line_byte_start = buffer.line_to_byte(file_row);
+
+                    // Part of the line on this row (all of it unless soft-wrapped)
+                    let (segment_start, segment_end, segment_indent) = editor.wrap_segment(file_row, segment_start);
+                    let is_first_row = segment_start == 0;
+                    let is_last_row = segment_end >= line_display.len();
                     
                     // Get syntax highlighting for this line
                     let syntax_spans = editor.get_syntax_spans(file_row);
@@ -240,20 +265,32 @@ impl Renderer {
                     } else {
                         "\x1b[48;2;30;30;30m" // Normal background RGB(30,30,30)
                     };
+                    if gutter_width > 0 && is_first_row {
+                        formatted_line.push_str(&Self::format_gutter(editor, file_row, gutter_width, is_current_line));
+                    } else if gutter_width > 0 {
+                        // Wrapped rows leave the gutter empty
+                        formatted_line.push_str("\x1b[48;2;30;30;30m");
+                        formatted_line.push_str(&" ".repeat(gutter_width));
+                    }
                     formatted_line.push_str(line_bg_color); // Set line background
-                    let mut byte_pos = line_byte_start;
+                    let mut byte_pos = line_byte_start + segment_start;
                     let mut display_col = 0;  // Display column position (accounts for wide chars)
                     let mut screen_col = 0;    // Screen column position after horizontal scroll
-                    let mut line_byte_offset = 0;  // Byte offset within the line
+                    let mut line_byte_offset = segment_start;  // Byte offset within the line
+
+                    // Continuation rows of a wrapped line are indented
+                    let segment_indent = segment_indent.min(text_width);
+                    formatted_line.push_str(&" ".repeat(segment_indent));
+                    screen_col += segment_indent;
                     
-                    for ch in line_display.chars() {
+                    for ch in line_display[segment_start..segment_end].chars() {
                         // Get the display width of this character (0, 1, or 2 columns)
                         let char_width = ch.width().unwrap_or(1);
                         
                         // Check if we're past the horizontal scroll offset
                         if display_col + char_width > viewport_offset.1 {
                             // Check if this character fits on screen
-                            if screen_col + char_width > width as usize {
+                            if screen_col + char_width > text_width {
                                 // Character doesn't fit, stop here
                                 break;
                             }
@@ -265,10 +302,11 @@ impl Renderer {
                                 formatted_line.push(' ');
                                 screen_col += 1;
                             } else {
-                                // Check if this character is selected
-                                let is_selected = selection.map_or(false, |(sel_start, sel_end)| {
-                                    byte_pos >= sel_start && byte_pos < sel_end
-                                });
+                                // Check if this character is selected (extra cursors are drawn like selections)
+                                let is_selected = extra_cursors.contains(&byte_pos)
+                                    || selections.iter().any(|&(sel_start, sel_end)| {
+                                        byte_pos >= sel_start && byte_pos < sel_end
+                                    });
 
                                 // Check if this character is a matching bracket
                                 let is_matching_bracket = matching_brackets.map_or(false, |(pos1, pos2)| {
@@ -375,7 +413,8 @@ impl Renderer {
                                             }
                                         }
                                     }
-                                    formatted_line.push(ch);
+                                    // Tabs kept inside a line (e.g. TSV data) are drawn one column wide
+                                    formatted_line.push(if ch == '\t' { ' ' } else { ch });
                                     if is_selected || is_current_find_match || is_find_match {
                                         formatted_line.push_str("\x1b[0m");
                                         formatted_line.push_str(line_bg_color); // Reset and restore line background
@@ -471,7 +510,8 @@ impl Renderer {
                                             }
                                         }
                                     }
-                                    formatted_line.push(ch);
+                                    // Tabs kept inside a line (e.g. TSV data) are drawn one column wide
+                                    formatted_line.push(if ch == '\t' { ' ' } else { ch });
                                     if is_selected || is_current_find_match || is_find_match {
                                         formatted_line.push_str("\x1b[0m");
                                         formatted_line.push_str(line_bg_color); // Reset and restore line background
@@ -498,9 +538,39 @@ impl Renderer {
                         display_col += char_width;
                     }
                     
-                    // Pad the rest of the line with spaces (background already set)
-                    while screen_col < width as usize {
-                        formatted_line.push(' ');
+                    // Extra cursor at the end of the line
+                    if is_last_row && byte_pos == line_byte_start + line_display.len()
+                        && display_col >= viewport_offset.1 && screen_col < text_width
+                        && extra_cursors.contains(&byte_pos)
+                    {
+                        formatted_line.push_str("\x1b[48;2;95;158;160m \x1b[0m");
+                        formatted_line.push_str(line_bg_color);
+                        screen_col += 1;
+                    }
+
+                    // Placeholder for the lines folded below this one
+                    if let Some(hidden) = editor.folded_line_count(file_row).filter(|_| is_last_row) {
+                        let placeholder = format!(" ⋯ {} lines ", hidden);
+                        if screen_col + 1 + placeholder.chars().count() <= text_width {
+                            formatted_line.push(' ');
+                            formatted_line.push_str("\x1b[48;2;50;50;56m\x1b[38;2;130;130;135m");
+                            formatted_line.push_str(&placeholder);
+                            formatted_line.push_str("\x1b[0m");
+                            formatted_line.push_str(line_bg_color);
+                            screen_col += 1 + placeholder.chars().count();
+                        }
+                    }
+
+                    // Pad the rest of the line with spaces (background already set),
+                    // or draw a rule after a cell delimiter to mark the cell boundary
+                    let is_cell_delimiter = gutter_width > 0 && is_last_row
+                        && line_display.trim_start().starts_with(crate::cell::CELL_DELIMITER);
+                    if is_cell_delimiter && screen_col < text_width {
+                        formatted_line.push_str("\x1b[38;2;70;70;70m "); // Dim rule
+                        screen_col += 1;
+                    }
+                    while screen_col < text_width {
+                        formatted_line.push(if is_cell_delimiter { '─' } else { ' ' });
                         screen_col += 1;
                     }
                     formatted_line.push_str("\x1b[0m"); // Reset at end of line
@@ -511,10 +581,11 @@ impl Renderer {
                     if viewport_offset.1 == 0 {
                         // Only show the ~ if we're not horizontally scrolled
                         line_content.push_str("\x1b[48;2;30;30;30m"); // Consistent background
+                        line_content.push_str(&" ".repeat(gutter_width));
                         line_content.push_str("\x1b[38;2;110;110;110m"); // Dim grey like comments
                         line_content.push('~');
                         line_content.push_str("\x1b[39m"); // Reset foreground color
-                        for _ in 1..width {
+                        for _ in 1..text_width {
                             line_content.push(' ');
                         }
                         line_content.push_str("\x1b[0m");
@@ -600,7 +671,9 @@ impl Renderer {
         // Right-align the entire row/total as one unit (19 chars) and column (4 chars)
         // This accommodates up to 999,999,999 lines (9 digits + "/" + 9 digits)
         let row_info = format!("{}/{}", line + 1, total_lines);
-        let right_status = format!(" {:>19}  {:>4} ",
+        let right_status = format!(" {}{}  {:>19}  {:>4} ",
+            if editor.soft_wrap_enabled() { "Wrap  " } else { "" },
+            editor.file_format().label(),
             row_info,
             col + 1
         );
@@ -703,16 +776,16 @@ impl Renderer {
         // Position cursor - map buffer position to screen position
         // Only show cursor if there's no bottom window (find/replace is closed)
         if bottom_window_height == 0 {
-            let (cursor_line, cursor_col) = editor.cursor_position();
-            let logical_cursor_line = cursor_line + 2; // Add 2 for virtual lines before buffer
+            // Display row accounts for the virtual lines, inline outputs and wrapped rows above
+            let (logical_cursor_line, cursor_col) = editor.cursor_display_position();
 
             if logical_cursor_line >= viewport_offset.0 &&
                logical_cursor_line < viewport_offset.0 + content_height &&
                cursor_col >= viewport_offset.1 &&
-               cursor_col < viewport_offset.1 + width as usize {
+               cursor_col < viewport_offset.1 + text_width {
 
                 let screen_row = logical_cursor_line - viewport_offset.0;
-                let screen_col = cursor_col - viewport_offset.1;
+                let screen_col = cursor_col - viewport_offset.1 + gutter_width;
 
                 #[cfg(target_os = "windows")]
                 write!(self.stdout, "\x1b[{};{}H\x1b[?25h",
@@ -739,6 +812,88 @@ impl Renderer {
         Ok(())
     }
     
+    /// Gutter for one buffer line: a status glyph, then the line number, or the
+    /// execution count ([n], [*] while running) on the first line of a cell
+    fn format_gutter(editor: &Editor, file_row: usize, gutter_width: usize, is_current_line: bool) -> String {
+        use crate::cell::CellStatus;
+
+        let number_width = gutter_width.saturating_sub(3);
+        let cells = editor.get_cells();
+        let cell = editor.cell_index_at_line(file_row).map(|idx| &cells[idx]);
+        let status = cell.map_or(CellStatus::Idle, |c| c.status);
+        let is_first_line = cell.is_some_and(|c| editor.buffer().line_to_byte(file_row) == c.start);
+        let is_delimiter = editor.buffer().line(file_row).trim_start().starts_with(crate::cell::CELL_DELIMITER);
+
+        let status_color = match status {
+            CellStatus::Idle => "\x1b[38;2;90;90;90m",
+            CellStatus::Running => "\x1b[38;2;220;190;90m",
+            CellStatus::Ok => "\x1b[38;2;110;170;110m",
+            CellStatus::Error => "\x1b[38;2;210;100;100m",
+            CellStatus::Stale => "\x1b[38;2;140;125;95m",
+        };
+        let glyph = match (status, is_first_line) {
+            (CellStatus::Idle, _) => ' ',
+            (CellStatus::Running, true) => '◐',
+            (_, true) => '●',
+            (_, false) => '│',
+        };
+
+        let mut gutter = String::new();
+        gutter.push_str("\x1b[48;2;30;30;30m");
+        gutter.push_str(status_color);
+        gutter.push(glyph);
+        gutter.push(' ');
+
+        if is_first_line && (is_delimiter || status != CellStatus::Idle) {
+            let count = match (status, cell.and_then(|c| c.execution_count)) {
+                (CellStatus::Running, _) => "[*]".to_string(),
+                (_, Some(n)) => format!("[{}]", n),
+                (_, None) => "[ ]".to_string(),
+            };
+            gutter.push_str(&format!("{:>width$}", count, width = number_width));
+        } else {
+            let number_color = if is_current_line { "\x1b[38;2;160;160;160m" } else { "\x1b[38;2;85;85;85m" };
+            gutter.push_str(number_color);
+            gutter.push_str(&format!("{:>width$}", file_row + 1, width = number_width));
+        }
+        // Fold marker in the last column
+        if editor.folded_line_count(file_row).is_some() {
+            gutter.push_str("\x1b[38;2;160;160;160m▸");
+        } else {
+            gutter.push(' ');
+        }
+        gutter.push_str("\x1b[39m");
+        gutter
+    }
+
+    /// One row of a cell's inline output, drawn on a slightly lighter background
+    fn format_output_row(text: &str, style: OutputStyle, gutter_width: usize, text_width: usize) -> String {
+        let color = match style {
+            OutputStyle::Text => "\x1b[38;2;150;150;155m",
+            OutputStyle::Error => "\x1b[38;2;200;110;110m",
+            OutputStyle::Fold => "\x1b[38;2;100;100;105m",
+        };
+
+        let mut row = String::new();
+        row.push_str("\x1b[48;2;30;30;30m");
+        row.push_str(&" ".repeat(gutter_width));
+        row.push_str("\x1b[48;2;36;36;40m");
+        row.push_str(color);
+
+        let mut used = 0;
+        for ch in "  ".chars().chain(text.chars()) {
+            let char_width = ch.width().unwrap_or(1);
+            if used + char_width > text_width {
+                break;
+            }
+            row.push(ch);
+            used += char_width;
+        }
+        row.push_str(&" ".repeat(text_width - used));
+        row.push_str("\x1b[0m");
+        row
+    }
+
     /// Force a complete redraw by clearing cached state
     pub fn force_redraw(&mut self) {
         self.last_screen = vec![String::new(); self.last_size.1 as usize];
@@ -754,7 +909,7 @@ impl Renderer {
     /// Reposition and show cursor at editor position (call after drawing output pane)
     pub fn reposition_cursor(&mut self, editor: &Editor, bottom_window_height: usize) -> io::Result<()> {
         let (width, height) = terminal::size()?;
-        let (cursor_line, cursor_col) = editor.cursor_position();
+        let (logical_cursor_line, cursor_col) = editor.cursor_display_position();
         let (viewport_row, viewport_col) = editor.viewport_offset();
 
         // Update cursor style based on editor selection
@@ -774,19 +929,18 @@ impl Renderer {
 
         // Calculate content height (excluding status bar and bottom window)
         let content_height = height.saturating_sub(1 + bottom_window_height as u16) as usize;
-
-        // Calculate logical cursor position (add 2 for virtual lines before buffer)
-        let logical_cursor_line = cursor_line + 2;
+        let gutter_width = editor.gutter_width();
+        let text_width = (width as usize).saturating_sub(gutter_width);
 
         // Check if cursor is within viewport bounds BEFORE calculating screen position
         // This prevents saturating_sub from hiding out-of-bounds positions as 0
         if logical_cursor_line >= viewport_row &&
            logical_cursor_line < viewport_row + content_height &&
            cursor_col >= viewport_col &&
-           cursor_col < viewport_col + width as usize {
+           cursor_col < viewport_col + text_width {
 
             let screen_row = logical_cursor_line - viewport_row;
-            let screen_col = cursor_col - viewport_col;
+            let screen_col = cursor_col - viewport_col + gutter_width;
 
             #[cfg(target_os = "windows")]
             write!(self.stdout, "\x1b[{};{}H\x1b[?25h",
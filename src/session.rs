@@ -0,0 +1,214 @@
+use crate::kernel::KernelInfo;
+use crate::output_pane::OutputEntry;
+use crate::state;
+use crate::undo_tree::UndoHistory;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Number of files remembered in the recent files list
+const MAX_RECENT_FILES: usize = 20;
+
+/// Only the most recent outputs are kept so session files stay small
+const MAX_SESSION_OUTPUTS: usize = 100;
+
+/// Number of searches remembered per file
+pub const MAX_FIND_HISTORY: usize = 50;
+
+/// Undo states kept per file; the oldest are dropped first
+const MAX_SESSION_UNDO_STATES: usize = 1000;
+
+/// Completions whose acceptance count is remembered; the least used are dropped first
+const MAX_COMPLETION_USAGE: usize = 2000;
+
+/// Per-file state restored when the file is opened again
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FileSession {
+    pub cursor: usize,
+    pub viewport_offset: (usize, usize),
+    pub kernel: Option<KernelInfo>,  // Interpreter selected for this file
+    pub outputs: Vec<OutputEntry>,   // Output pane contents
+    pub find_history: Vec<String>,   // Most recent search first
+    pub undo: Option<UndoHistory>,   // Undo tree for the text as saved on disk
+}
+
+fn session_path(file_path: &Path) -> Option<PathBuf> {
+    let dir = state::state_subdir("sessions")?;
+    Some(dir.join(format!("{}.json", state::path_key(file_path))))
+}
+
+/// Load the saved session for a file, if there is one
+pub fn load(file_path: &Path) -> Option<FileSession> {
+    let json = fs::read_to_string(session_path(file_path)?).ok()?;
+    serde_json::from_str(&json).ok()
+}
+
+/// Save the session for a file and move it to the top of the recent files list
+pub fn save(file_path: &Path, session: &FileSession) {
+    if let (Some(path), Ok(json)) = (session_path(file_path), serde_json::to_string(&trimmed(session))) {
+        let _ = state::write_atomic(&path, json.as_bytes());
+    }
+    add_recent(file_path);
+}
+
+/// Copy of a session with only as much output, search and undo history as is saved
+fn trimmed(session: &FileSession) -> FileSession {
+    let mut session = session.clone();
+    let skip = session.outputs.len().saturating_sub(MAX_SESSION_OUTPUTS);
+    session.outputs.drain(..skip);
+    session.find_history.truncate(MAX_FIND_HISTORY);
+    if let Some(undo) = &mut session.undo {
+        undo.tree = undo.tree.pruned(MAX_SESSION_UNDO_STATES);
+    }
+    session
+}
+
+fn recent_path() -> Option<PathBuf> {
+    state::state_dir().map(|dir| dir.join("recent.json"))
+}
+
+/// Recently opened files that still exist, most recent first
+pub fn recent_files() -> Vec<PathBuf> {
+    let paths: Vec<String> = recent_path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default();
+    existing_files(paths)
+}
+
+/// The paths that still name a file
+fn existing_files(paths: Vec<String>) -> Vec<PathBuf> {
+    paths.into_iter()
+        .map(PathBuf::from)
+        .filter(|path| path.is_file())
+        .collect()
+}
+
+/// Recent files list with `path` moved (or added) to the top
+fn with_recent(mut recent: Vec<PathBuf>, path: PathBuf) -> Vec<PathBuf> {
+    recent.retain(|p| p != &path);
+    recent.insert(0, path);
+    recent.truncate(MAX_RECENT_FILES);
+    recent
+}
+
+fn add_recent(file_path: &Path) {
+    let recent = with_recent(recent_files(), state::absolute_path(file_path));
+
+    let paths: Vec<String> = recent.iter().map(|p| p.to_string_lossy().to_string()).collect();
+    if let (Some(dir), Some(path), Ok(json)) = (state::state_dir(), recent_path(), serde_json::to_string(&paths)) {
+        if fs::create_dir_all(dir).is_ok() {
+            let _ = state::write_atomic(&path, json.as_bytes());
+        }
+    }
+}
+
+fn completion_usage_path() -> Option<PathBuf> {
+    state::state_dir().map(|dir| dir.join("completions.json"))
+}
+
+/// How often each autocomplete suggestion has been accepted, across all files
+pub fn completion_usage() -> HashMap<String, u32> {
+    completion_usage_path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+pub fn save_completion_usage(usage: &HashMap<String, u32>) {
+    let mut entries: Vec<(&String, &u32)> = usage.iter().collect();
+    if entries.len() > MAX_COMPLETION_USAGE {
+        entries.sort_by_key(|&(_, count)| std::cmp::Reverse(*count));
+        entries.truncate(MAX_COMPLETION_USAGE);
+    }
+    let usage: HashMap<&String, &u32> = entries.into_iter().collect();
+    if let (Some(dir), Some(path), Ok(json)) = (state::state_dir(), completion_usage_path(), serde_json::to_string(&usage)) {
+        if fs::create_dir_all(dir).is_ok() {
+            let _ = state::write_atomic(&path, json.as_bytes());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::Editor;
+    use crate::kernel::KernelType;
+
+    fn output(execution_count: usize) -> OutputEntry {
+        OutputEntry { execution_count, cell_line: 0, output: String::new(), is_error: false, elapsed_secs: 0.0 }
+    }
+
+    #[test]
+    fn test_session_round_trip() {
+        let mut editor = Editor::new();
+        editor.paste_text("x = 1\n".repeat(200));
+        editor.restore_position(600, (150, 2));
+        let session = FileSession {
+            cursor: editor.cursor(),
+            viewport_offset: editor.viewport_offset(),
+            kernel: Some(KernelInfo {
+                name: "venv".to_string(),
+                display_name: "Python 3 (venv)".to_string(),
+                python_path: "/project/.venv/bin/python".to_string(),
+                kernel_type: KernelType::Direct,
+            }),
+            outputs: (1..=150).map(output).collect(),
+            find_history: (0..60).map(|i| format!("search {}", i)).collect(),
+            undo: None,
+        };
+
+        // Only the most recent outputs and searches are kept
+        let json = serde_json::to_string(&trimmed(&session)).unwrap();
+        let restored: FileSession = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.outputs.len(), MAX_SESSION_OUTPUTS);
+        assert_eq!(restored.outputs[0].execution_count, 51);
+        assert_eq!(restored.find_history.len(), MAX_FIND_HISTORY);
+        assert_eq!(restored.find_history[0], "search 0");
+        let kernel = restored.kernel.unwrap();
+        assert_eq!((kernel.python_path.as_str(), kernel.kernel_type), ("/project/.venv/bin/python", KernelType::Direct));
+
+        let mut reopened = Editor::new();
+        reopened.paste_text("x = 1\n".repeat(200));
+        reopened.restore_position(restored.cursor, restored.viewport_offset);
+        assert_eq!(reopened.cursor(), 600);
+        assert_eq!(reopened.viewport_offset(), editor.viewport_offset());
+
+        // A shorter file on disk doesn't put the cursor or viewport past its end
+        let mut shorter = Editor::new();
+        shorter.paste_text("x = 1\n".repeat(3));
+        shorter.restore_position(restored.cursor, restored.viewport_offset);
+        assert_eq!(shorter.cursor(), 18);
+        assert!(shorter.viewport_offset().0 <= 3);
+
+        // Sessions saved by older versions load with defaults for the missing fields
+        let old: FileSession = serde_json::from_str("{\"cursor\": 5}").unwrap();
+        assert_eq!(old.cursor, 5);
+        assert!(old.kernel.is_none() && old.outputs.is_empty() && old.undo.is_none());
+    }
+
+    #[test]
+    fn test_recent_files() {
+        let dir = std::env::temp_dir().join(format!("sage-recent-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (a, b) = (dir.join("a.py"), dir.join("b.py"));
+        fs::write(&a, "").unwrap();
+        fs::write(&b, "").unwrap();
+
+        // Files that were deleted or moved drop out of the list
+        let paths = [&a, &dir.join("deleted.py"), &b, &dir].map(|p| p.to_string_lossy().to_string());
+        assert_eq!(existing_files(paths.to_vec()), vec![a.clone(), b.clone()]);
+
+        // Reopened files move to the top, and only the most recent are kept
+        assert_eq!(with_recent(vec![a.clone(), b.clone()], b.clone()), vec![b.clone(), a.clone()]);
+        let many: Vec<PathBuf> = (0..MAX_RECENT_FILES).map(|i| dir.join(format!("{}.py", i))).collect();
+        let recent = with_recent(many.clone(), a.clone());
+        assert_eq!(recent.len(), MAX_RECENT_FILES);
+        assert_eq!(recent[0], a);
+        assert_eq!(recent[1..], many[..MAX_RECENT_FILES - 1]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,172 @@
+use crossterm::{
+    cursor,
+    execute,
+    style::{Attribute, Color, Print, ResetColor, SetAttribute, SetBackgroundColor, SetForegroundColor},
+};
+use crate::editor::CallContext;
+use crate::kernel::{Signature, SignatureParameter};
+use std::io::{self, Write};
+
+/// Signature of the call the cursor is in, shown above the cursor line while typing
+/// its arguments, with the parameter being filled in highlighted
+#[derive(Default)]
+pub struct SignatureHelp {
+    call: Option<(usize, String, Option<String>)>, // Call the signature was fetched for
+    signature: Option<Signature>,
+    active: Option<usize>, // Index of the highlighted parameter
+}
+
+impl SignatureHelp {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Follow the cursor into `context`, fetching the signature when it enters another call.
+    /// Returns true when what is shown changed.
+    pub fn update(&mut self, context: Option<CallContext>, fetch: impl FnOnce(&str, Option<&str>) -> Option<Signature>) -> bool {
+        let Some(context) = context else {
+            let changed = self.signature.is_some();
+            *self = Self::default();
+            return changed;
+        };
+
+        let mut changed = false;
+        let call = (context.open_pos, context.name, context.base);
+        if self.call.as_ref() != Some(&call) {
+            let had_signature = self.signature.is_some();
+            self.signature = fetch(&call.1, call.2.as_deref());
+            self.call = Some(call);
+            changed = had_signature || self.signature.is_some();
+        }
+
+        let active = self.signature.as_ref()
+            .and_then(|signature| active_parameter(&signature.parameters, context.argument, context.keyword.as_deref()));
+        changed |= active != self.active;
+        self.active = active;
+        changed
+    }
+
+    /// Fetch the signature again next time, as running code may have changed what the name is
+    pub fn invalidate(&mut self) {
+        self.call = None;
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.signature.is_some()
+    }
+
+    /// Draw the signature on the row above the cursor (below it on the first row)
+    pub fn draw<W: Write>(&self, writer: &mut W, cursor_row: u16, cursor_col: u16, max_col: u16) -> io::Result<()> {
+        let Some(signature) = &self.signature else {
+            return Ok(());
+        };
+
+        // The signature as Python writes it, with `/` and `*` between parameter groups
+        let mut parts: Vec<(String, bool)> = vec![(format!("{}(", signature.label), false)];
+        let mut keyword_marker_needed = true;
+        for (i, parameter) in signature.parameters.iter().enumerate() {
+            if i > 0 {
+                parts.push((", ".to_string(), false));
+            }
+            if parameter.kind == "var_positional" {
+                keyword_marker_needed = false;
+            }
+            if parameter.kind == "keyword_only" && keyword_marker_needed {
+                parts.push(("*, ".to_string(), false));
+                keyword_marker_needed = false;
+            }
+            parts.push((parameter.text.clone(), self.active == Some(i)));
+            let next_kind = signature.parameters.get(i + 1).map(|p| p.kind.as_str());
+            if parameter.kind == "positional_only" && next_kind != Some("positional_only") {
+                parts.push((", /".to_string(), false));
+            }
+        }
+        parts.push((")".to_string(), false));
+
+        // Scroll long signatures so the highlighted parameter stays in view
+        let width = (max_col as usize).saturating_sub(2).min(100);
+        let total: usize = parts.iter().map(|(text, _)| text.chars().count()).sum();
+        let highlight_start: usize = parts.iter()
+            .take_while(|(_, active)| !active)
+            .map(|(text, _)| text.chars().count())
+            .sum();
+        let mut skip = if total > width { highlight_start.saturating_sub(width / 3).min(total - width) } else { 0 };
+        let shown_width = total.min(width);
+
+        let row = if cursor_row > 0 { cursor_row - 1 } else { cursor_row + 1 };
+        let col = (cursor_col as usize).min((max_col as usize).saturating_sub(shown_width + 2));
+        execute!(
+            writer,
+            cursor::MoveTo(col as u16, row),
+            SetBackgroundColor(Color::Rgb { r: 45, g: 45, b: 52 }),
+            Print(' '),
+        )?;
+
+        let mut remaining = shown_width;
+        for (text, active) in &parts {
+            let visible: String = text.chars().skip(skip).take(remaining).collect();
+            skip = skip.saturating_sub(text.chars().count());
+            remaining -= visible.chars().count();
+            if visible.is_empty() {
+                continue;
+            }
+            if *active {
+                execute!(writer, SetForegroundColor(Color::Yellow), SetAttribute(Attribute::Bold), Print(visible), SetAttribute(Attribute::NormalIntensity))?;
+            } else {
+                execute!(writer, SetForegroundColor(Color::Rgb { r: 190, g: 190, b: 195 }), Print(visible))?;
+            }
+        }
+        execute!(writer, Print(' '), ResetColor)?;
+        Ok(())
+    }
+}
+
+/// Parameter that the argument at `index` (or the `keyword` argument) fills
+fn active_parameter(parameters: &[SignatureParameter], index: usize, keyword: Option<&str>) -> Option<usize> {
+    if let Some(keyword) = keyword {
+        return parameters.iter()
+            .position(|p| p.name == keyword && matches!(p.kind.as_str(), "positional_or_keyword" | "keyword_only"))
+            .or_else(|| parameters.iter().position(|p| p.kind == "var_keyword"));
+    }
+    let mut positional = 0;
+    for (i, parameter) in parameters.iter().enumerate() {
+        match parameter.kind.as_str() {
+            "positional_only" | "positional_or_keyword" => {
+                if positional == index {
+                    return Some(i);
+                }
+                positional += 1;
+            }
+            "var_positional" => return Some(i),
+            _ => break,
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parameter(name: &str, kind: &str) -> SignatureParameter {
+        SignatureParameter { name: name.to_string(), text: name.to_string(), kind: kind.to_string() }
+    }
+
+    #[test]
+    fn test_active_parameter() {
+        // def f(a, /, b, *args, c, **kw)
+        let parameters = vec![
+            parameter("a", "positional_only"),
+            parameter("b", "positional_or_keyword"),
+            parameter("args", "var_positional"),
+            parameter("c", "keyword_only"),
+            parameter("kw", "var_keyword"),
+        ];
+        assert_eq!(active_parameter(&parameters, 0, None), Some(0));
+        assert_eq!(active_parameter(&parameters, 1, None), Some(1));
+        assert_eq!(active_parameter(&parameters, 5, None), Some(2));
+        assert_eq!(active_parameter(&parameters, 1, Some("c")), Some(3));
+        assert_eq!(active_parameter(&parameters, 0, Some("a")), Some(4));
+        assert_eq!(active_parameter(&parameters[..2], 2, None), None);
+    }
+}
@@ -1,8 +1,56 @@
+use crate::cell::{cell_type_from_header, CellType, CELL_DELIMITER};
 use ropey::Rope;
 
 /// Detect if the cursor is inside a SQL string context
-/// Returns true if we're inside a string that's an argument to a SQL function
+/// Returns true if we're inside a string that's an argument to a SQL function,
+/// or in the body of a `##$$ sql` cell
 pub fn is_in_sql_context(rope: &Rope, cursor_pos: usize) -> bool {
+    sql_cell_at(rope, cursor_pos).is_some() || sql_string_start(rope, cursor_pos).is_some()
+}
+
+/// Byte range of the body of the `##$$ sql` cell the cursor is in
+pub fn sql_cell_at(rope: &Rope, cursor_pos: usize) -> Option<(usize, usize)> {
+    let is_header = |line: usize| rope.line(line).to_string().trim_start().starts_with(CELL_DELIMITER);
+    let cursor_line = rope.byte_to_line(cursor_pos);
+    let header = (0..=cursor_line).rev().find(|&line| is_header(line))?;
+    if header == cursor_line || !matches!(cell_type_from_header(&rope.line(header).to_string()), CellType::Sql { .. }) {
+        return None;
+    }
+    let end = (cursor_line + 1..rope.len_lines())
+        .find(|&line| is_header(line))
+        .map_or(rope.len_bytes(), |line| rope.line_to_byte(line));
+    Some((rope.line_to_byte(header + 1), end))
+}
+
+/// Text of the SQL string (or SQL cell) the cursor is in, and the cursor's byte offset in it
+pub fn sql_string_at(rope: &Rope, cursor_pos: usize) -> Option<(String, usize)> {
+    if let Some((start, end)) = sql_cell_at(rope, cursor_pos) {
+        return Some((rope.byte_slice(start..end).to_string(), cursor_pos - start));
+    }
+    let (string_start, triple) = sql_string_start(rope, cursor_pos)?;
+    let quote = rope.get_char(rope.byte_to_char(string_start))?.to_string().repeat(if triple { 3 } else { 1 });
+    let content_start = string_start + quote.len();
+
+    // The string ends at its closing quote, or where it breaks off while being typed
+    let after: String = rope.byte_slice(cursor_pos..).to_string();
+    let mut end = after.len();
+    let mut escaped = false;
+    for (i, ch) in after.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if ch == '\\' {
+            escaped = true;
+        } else if after[i..].starts_with(&quote) || (ch == '\n' && !triple) {
+            end = i;
+            break;
+        }
+    }
+    let text = rope.byte_slice(content_start..cursor_pos).to_string() + &after[..end];
+    Some((text, cursor_pos - content_start))
+}
+
+/// Where the SQL string around the cursor starts (its opening quote) and whether it is triple-quoted
+fn sql_string_start(rope: &Rope, cursor_pos: usize) -> Option<(usize, bool)> {
     // Common SQL function patterns to detect
     const SQL_PATTERNS: &[&str] = &[
         ".sql(",
@@ -12,11 +60,19 @@ pub fn is_in_sql_context(rope: &Rope, cursor_pos: usize) -> bool {
         ".read_sql_query(",
         ".read_sql_table(",
         "spark.sql(",
+        // sqlite3 / DB-API cursors, SQLAlchemy, psycopg and Polars
+        ".executemany(",
+        ".executescript(",
+        ".exec_driver_sql(",
+        "text(",
+        "SQL(",
+        ".read_database(",
+        ".read_database_uri(",
     ];
 
     // First, check if we're inside a string at all
     if !is_in_string(rope, cursor_pos) {
-        return false;
+        return None;
     }
 
     // Look backwards from cursor to find the opening quote of the string
@@ -78,7 +134,7 @@ pub fn is_in_sql_context(rope: &Rope, cursor_pos: usize) -> bool {
     }
 
     if !in_string {
-        return false;
+        return None;
     }
 
     // Check if this is an f-string (f"..." or F"...")
@@ -99,22 +155,23 @@ pub fn is_in_sql_context(rope: &Rope, cursor_pos: usize) -> bool {
     let search_start = check_start.saturating_sub(1000);
     let search_text = rope.slice(search_start..check_start).to_string();
 
-    // Check if any SQL pattern appears near the string start
+    // Check if any SQL pattern appears near the string start, with only whitespace
+    // or a `sql=`/`query=` keyword between it and the quote
+    let search_text = search_text.trim_end();
     for pattern in SQL_PATTERNS {
-        if search_text.ends_with(pattern) {
-            return true;
-        }
-
-        // Also check with whitespace between pattern and quote
-        if let Some(trimmed_pos) = search_text.trim_end().rfind(pattern) {
-            let after_pattern = &search_text[trimmed_pos + pattern.len()..];
-            if after_pattern.trim().is_empty() {
-                return true;
+        if let Some(pattern_pos) = search_text.rfind(pattern) {
+            // `text(` must not be the end of a longer name like `context(`
+            let whole_name = pattern.starts_with('.')
+                || !search_text[..pattern_pos].ends_with(|c: char| c.is_alphanumeric() || c == '_');
+            let after_pattern = search_text[pattern_pos + pattern.len()..].trim();
+            let keyword = after_pattern.strip_suffix('=').map(str::trim_end);
+            if whole_name && (after_pattern.is_empty() || matches!(keyword, Some("sql" | "query"))) {
+                return Some((string_start, is_triple_quote));
             }
         }
     }
 
-    false
+    None
 }
 
 /// Check if cursor is inside any string (helper function)
@@ -152,6 +209,189 @@ fn is_in_string(rope: &Rope, cursor_pos: usize) -> bool {
     in_double_quote || in_single_quote
 }
 
+/// Words that end a table reference in FROM/JOIN, so they can't be its alias
+const CLAUSE_KEYWORDS: &[&str] = &[
+    "WHERE", "JOIN", "INNER", "LEFT", "RIGHT", "FULL", "OUTER", "CROSS", "NATURAL", "ASOF",
+    "POSITIONAL", "SEMI", "ANTI", "LATERAL", "ON", "USING", "GROUP", "ORDER", "LIMIT",
+    "OFFSET", "HAVING", "QUALIFY", "WINDOW", "UNION", "INTERSECT", "EXCEPT", "SELECT",
+    "FROM", "AS", "SET", "VALUES", "RETURNING", "SAMPLE", "TABLESAMPLE",
+];
+
+/// A table a SQL statement reads from, and the alias it is referred to by
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableRef {
+    pub name: String,
+    pub alias: Option<String>,
+}
+
+/// Tables and CTEs in the SQL statement around the cursor
+#[derive(Debug, Default, PartialEq)]
+pub struct SqlScope {
+    pub tables: Vec<TableRef>,
+    pub ctes: Vec<String>,
+}
+
+impl SqlScope {
+    /// Table that `qualifier` (an alias or a table name) refers to in the statement
+    pub fn resolve(&self, qualifier: &str) -> Option<&str> {
+        self.tables.iter()
+            .find(|t| t.alias.as_deref().is_some_and(|alias| alias.eq_ignore_ascii_case(qualifier)))
+            .or_else(|| self.tables.iter().find(|t| t.name.eq_ignore_ascii_case(qualifier)))
+            .map(|t| t.name.as_str())
+    }
+}
+
+/// Find the tables (with aliases) in the FROM and JOIN clauses and the CTE names of the
+/// statement containing byte offset `cursor` of `sql`
+pub fn parse_sql_scope(sql: &str, cursor: usize) -> SqlScope {
+    let tokens = sql_tokens(sql);
+    // Only the statement the cursor is in
+    let start = tokens.iter().rposition(|(pos, token)| token == ";" && *pos < cursor).map_or(0, |i| i + 1);
+    let end = tokens.iter().position(|(pos, token)| token == ";" && *pos >= cursor).unwrap_or(tokens.len());
+    let tokens: Vec<&str> = tokens[start..end].iter().map(|(_, token)| token.as_str()).collect();
+
+    let is = |i: usize, word: &str| tokens.get(i).is_some_and(|t| t.eq_ignore_ascii_case(word));
+    let is_name = |i: usize| tokens.get(i).is_some_and(|t| {
+        t.starts_with(|c: char| c.is_alphabetic() || c == '_' || c == '"')
+            && !CLAUSE_KEYWORDS.iter().any(|k| t.eq_ignore_ascii_case(k))
+    });
+    // Index just past the parenthesis that closes the one at `i`
+    let skip_parens = |mut i: usize| {
+        let mut depth = 0;
+        while i < tokens.len() {
+            match tokens[i] {
+                "(" => depth += 1,
+                ")" => depth -= 1,
+                _ => {}
+            }
+            i += 1;
+            if depth == 0 {
+                break;
+            }
+        }
+        i
+    };
+
+    let mut scope = SqlScope::default();
+    for i in 0..tokens.len() {
+        // `WITH name AS (`, `, name AS (`
+        if is_name(i) && is(i + 1, "AS") && is(i + 2, "(") && i > 0
+            && (is(i - 1, "WITH") || is(i - 1, "RECURSIVE") || is(i - 1, ","))
+        {
+            scope.ctes.push(unquote(tokens[i]));
+        }
+        if !(is(i, "FROM") || is(i, "JOIN")) {
+            continue;
+        }
+        let mut j = i + 1;
+        loop {
+            let name = if is(j, "(") {
+                // Subquery: its own FROM clauses are found separately
+                j = skip_parens(j);
+                None
+            } else if is_name(j) {
+                let name = unquote(tokens[j]);
+                j += 1;
+                if is(j, "(") {
+                    // Table function like read_csv('...'): no known columns
+                    j = skip_parens(j);
+                    None
+                } else {
+                    Some(name)
+                }
+            } else {
+                break;
+            };
+            let alias = if is(j, "AS") && is_name(j + 1) {
+                j += 2;
+                Some(unquote(tokens[j - 1]))
+            } else if is_name(j) {
+                j += 1;
+                Some(unquote(tokens[j - 1]))
+            } else {
+                None
+            };
+            if let Some(name) = name {
+                scope.tables.push(TableRef { name, alias });
+            }
+            // FROM a, b
+            if is(i, "FROM") && is(j, ",") {
+                j += 1;
+            } else {
+                break;
+            }
+        }
+    }
+    scope
+}
+
+/// Identifiers (with dots, like `schema.table` or `u.name`) and the symbols `(`, `)`, `,`
+/// and `;` of SQL, with their byte offsets. String literals, comments and Python
+/// f-string fields are skipped.
+fn sql_tokens(sql: &str) -> Vec<(usize, String)> {
+    let mut tokens = Vec::new();
+    let mut chars = sql.char_indices().peekable();
+    while let Some((pos, ch)) = chars.next() {
+        match ch {
+            '(' | ')' | ',' | ';' => tokens.push((pos, ch.to_string())),
+            '\'' => {
+                for (_, c) in chars.by_ref() {
+                    if c == '\'' {
+                        break;
+                    }
+                }
+            }
+            '{' => {
+                for (_, c) in chars.by_ref() {
+                    if c == '}' {
+                        break;
+                    }
+                }
+            }
+            '-' if chars.peek().is_some_and(|&(_, c)| c == '-') => {
+                for (_, c) in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek().is_some_and(|&(_, c)| c == '*') => {
+                chars.next();
+                let mut star = false;
+                for (_, c) in chars.by_ref() {
+                    if star && c == '/' {
+                        break;
+                    }
+                    star = c == '*';
+                }
+            }
+            c if c.is_alphanumeric() || c == '_' || c == '"' => {
+                let mut token = String::from(c);
+                let mut quoted = c == '"';
+                while let Some(&(_, next)) = chars.peek() {
+                    if quoted || next.is_alphanumeric() || next == '_' || next == '.' || next == '$' || next == '"' {
+                        if next == '"' {
+                            quoted = !quoted;
+                        }
+                        token.push(next);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                tokens.push((pos, token));
+            }
+            _ => {}
+        }
+    }
+    tokens
+}
+
+/// Identifier without its double quotes
+fn unquote(identifier: &str) -> String {
+    identifier.replace('"', "")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -169,5 +409,53 @@ mod tests {
         // Test Spark SQL context
         let rope = Rope::from_str("spark.sql(\"SELECT \")");
         assert!(is_in_sql_context(&rope, 15));
+
+        // DB-API cursors, pandas with a keyword argument, SQLAlchemy text()
+        let rope = Rope::from_str("cur.execute(\"SELECT \")");
+        assert!(is_in_sql_context(&rope, 17));
+        let rope = Rope::from_str("pd.read_sql(sql = \"SELECT \", con)");
+        assert!(is_in_sql_context(&rope, 23));
+        let rope = Rope::from_str("conn.execute(text('SELECT '))");
+        assert!(is_in_sql_context(&rope, 24));
+        let rope = Rope::from_str("context('SELECT ')");
+        assert!(!is_in_sql_context(&rope, 14));
+        let rope = Rope::from_str("f(name='SELECT ')");
+        assert!(!is_in_sql_context(&rope, 13));
+    }
+
+    #[test]
+    fn test_sql_string_at() {
+        let rope = Rope::from_str("x = db.sql(\"SELECT u. FROM users u\")\n");
+        let (sql, cursor) = sql_string_at(&rope, 21).unwrap();
+        assert_eq!(sql, "SELECT u. FROM users u");
+        assert_eq!(&sql[..cursor], "SELECT u.");
+
+        // The body of a SQL cell, up to the next cell
+        let rope = Rope::from_str("x = 1\n##$$ sql conn=db\nSELECT o.\nFROM orders o\n##$$\nprint(x)\n");
+        let (sql, cursor) = sql_string_at(&rope, 32).unwrap();
+        assert_eq!(sql, "SELECT o.\nFROM orders o\n");
+        assert_eq!(&sql[..cursor], "SELECT o.");
+        assert!(!is_in_sql_context(&rope, 10)); // The header line
+        assert!(!is_in_sql_context(&rope, 55)); // The Python cell after it
+    }
+
+    #[test]
+    fn test_parse_sql_scope() {
+        let sql = "SELECT o.id, u. FROM orders o JOIN main.users AS u ON o.user_id = u.id, items";
+        let scope = parse_sql_scope(sql, 15);
+        assert_eq!(scope.tables, vec![
+            TableRef { name: "orders".to_string(), alias: Some("o".to_string()) },
+            TableRef { name: "main.users".to_string(), alias: Some("u".to_string()) },
+        ]);
+        assert_eq!(scope.resolve("U"), Some("main.users"));
+        assert_eq!(scope.resolve("orders"), Some("orders"));
+
+        // CTEs, comma-separated tables, subqueries and other statements
+        let sql = "SELECT 1 FROM t; WITH recent AS (SELECT * FROM events WHERE d > '2024') \
+                   SELECT * FROM recent r, (SELECT id FROM users) sub, \"My Table\" WHERE";
+        let scope = parse_sql_scope(sql, sql.len());
+        assert_eq!(scope.ctes, vec!["recent".to_string()]);
+        let names: Vec<_> = scope.tables.iter().map(|t| (t.name.as_str(), t.alias.as_deref())).collect();
+        assert_eq!(names, vec![("events", None), ("recent", Some("r")), ("My Table", None), ("users", None)]);
     }
 }
@@ -0,0 +1,136 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Directory where Sage keeps per-user state (swap files, sessions, ...)
+/// Uses $XDG_STATE_HOME/sage, ~/.local/state/sage, or %LOCALAPPDATA%\sage on Windows
+pub fn state_dir() -> Option<PathBuf> {
+    #[cfg(target_os = "windows")]
+    {
+        std::env::var_os("LOCALAPPDATA").map(|dir| PathBuf::from(dir).join("sage"))
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    {
+        if let Some(dir) = std::env::var_os("XDG_STATE_HOME") {
+            if !dir.is_empty() {
+                return Some(PathBuf::from(dir).join("sage"));
+            }
+        }
+        std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("state").join("sage"))
+    }
+}
+
+/// Get (and create if needed) a subdirectory of the state directory
+pub fn state_subdir(name: &str) -> Option<PathBuf> {
+    let dir = state_dir()?.join(name);
+    fs::create_dir_all(&dir).ok()?;
+    Some(dir)
+}
+
+/// Absolute form of a path, used so the same file always maps to the same state entry
+pub fn absolute_path(path: &Path) -> PathBuf {
+    if let Ok(canonical) = fs::canonicalize(path) {
+        return canonical;
+    }
+    if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()
+            .map(|cwd| cwd.join(path))
+            .unwrap_or_else(|_| path.to_path_buf())
+    }
+}
+
+/// Stable file name for state belonging to `path`: "<file name>-<hash of absolute path>"
+pub fn path_key(path: &Path) -> String {
+    let absolute = absolute_path(path);
+    let name: String = absolute
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("file")
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '.' || c == '_' || c == '-' { c } else { '_' })
+        .collect();
+    format!("{}-{:016x}", name, hash_str(&absolute.to_string_lossy()))
+}
+
+/// FNV-1a hash - stable across runs and Rust versions, unlike DefaultHasher
+pub fn hash_str(text: &str) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in text.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+/// Write a file atomically: write to a temporary file next to the target, flush it to disk,
+/// then rename it over the target. Existing permissions are carried over to the new file.
+/// A crash or full disk mid-write leaves the original file untouched.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    use std::io::Write;
+
+    // Write through symlinks instead of replacing the link itself
+    let target = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let dir = match target.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let file_name = target
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "file".to_string());
+    let temp_path = dir.join(format!(".{}.sage-tmp-{}", file_name, std::process::id()));
+
+    let result = (|| {
+        // Preserve the original file's permissions (e.g. executable scripts), starting with
+        // the temp file's creation so its content is never readable under looser ones
+        let permissions = fs::metadata(&target).ok().map(|metadata| metadata.permissions());
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        if let Some(permissions) = &permissions {
+            use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+            options.mode(permissions.mode() & 0o777);
+        }
+        let mut file = options.open(&temp_path)?;
+        if let Some(permissions) = permissions {
+            // The mode given at creation is narrowed by the umask
+            file.set_permissions(permissions)?;
+        }
+        file.write_all(contents)?;
+        file.sync_all()?;
+        drop(file);
+
+        fs::rename(&temp_path, &target)
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(unix)]
+    fn test_write_atomic_keeps_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = std::env::temp_dir().join(format!("sage-write-atomic-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("script.py");
+        fs::write(&path, "old").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o751)).unwrap();
+
+        write_atomic(&path, b"new").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o751);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
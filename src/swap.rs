@@ -0,0 +1,139 @@
+use crate::state;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Contents of a swap file: a snapshot of an unsaved buffer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SwapFile {
+    pub path: Option<String>, // File the buffer belongs to (None for untitled buffers)
+    pub pid: u32,             // Process that wrote the swap file
+    pub saved_at: u64,        // Unix timestamp of the snapshot
+    pub content: String,      // Buffer text
+}
+
+/// Location of the swap file for a buffer
+/// Untitled buffers get one swap file per process so concurrent sessions don't collide
+pub fn swap_path(file_path: Option<&Path>) -> Option<PathBuf> {
+    let dir = state::state_subdir("swap")?;
+    let name = match file_path {
+        Some(path) => format!("{}.swp", state::path_key(path)),
+        None => format!("untitled-{}.swp", std::process::id()),
+    };
+    Some(dir.join(name))
+}
+
+/// Write a snapshot of the buffer to its swap file
+pub fn write(file_path: Option<&Path>, content: &str) -> io::Result<()> {
+    let swap = swap_path(file_path)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No state directory available"))?;
+    let saved_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let snapshot = SwapFile {
+        path: file_path.map(|p| state::absolute_path(p).to_string_lossy().to_string()),
+        pid: std::process::id(),
+        saved_at,
+        content: content.to_string(),
+    };
+    let json = serde_json::to_string(&snapshot)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    state::write_atomic(&swap, json.as_bytes())
+}
+
+/// Delete the swap file for a buffer (after a save or a clean exit)
+pub fn remove(file_path: Option<&Path>) {
+    if let Some(swap) = swap_path(file_path) {
+        let _ = fs::remove_file(swap);
+    }
+}
+
+/// Delete a specific swap file
+pub fn remove_file(swap: &Path) {
+    let _ = fs::remove_file(swap);
+}
+
+fn read(swap: &Path) -> Option<SwapFile> {
+    let json = fs::read_to_string(swap).ok()?;
+    serde_json::from_str(&json).ok()
+}
+
+/// Find a swap file left behind by a previous session that did not exit cleanly
+/// For untitled buffers the most recent orphaned untitled snapshot is returned
+pub fn find_orphaned(file_path: Option<&Path>) -> Option<(PathBuf, SwapFile)> {
+    match file_path {
+        Some(path) => {
+            let swap = swap_path(Some(path))?;
+            let snapshot = read(&swap)?;
+            if is_orphaned(&snapshot) {
+                Some((swap, snapshot))
+            } else {
+                None
+            }
+        }
+        None => {
+            let dir = state::state_subdir("swap")?;
+            let mut best: Option<(PathBuf, SwapFile)> = None;
+            for entry in fs::read_dir(dir).ok()?.flatten() {
+                let swap = entry.path();
+                let is_untitled = swap
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .is_some_and(|n| n.starts_with("untitled-"));
+                if !is_untitled {
+                    continue;
+                }
+                if let Some(snapshot) = read(&swap) {
+                    if is_orphaned(&snapshot)
+                        && best.as_ref().is_none_or(|(_, b)| snapshot.saved_at > b.saved_at)
+                    {
+                        best = Some((swap, snapshot));
+                    }
+                }
+            }
+            best
+        }
+    }
+}
+
+/// A swap file is orphaned when the process that wrote it is gone
+fn is_orphaned(snapshot: &SwapFile) -> bool {
+    snapshot.pid != std::process::id() && !is_process_alive(snapshot.pid)
+}
+
+fn is_process_alive(pid: u32) -> bool {
+    #[cfg(unix)]
+    {
+        // Signal 0 only checks the process exists; EPERM means it exists but isn't ours
+        let Ok(pid) = libc::pid_t::try_from(pid) else {
+            return false;
+        };
+        let found = unsafe { libc::kill(pid, 0) } == 0;
+        found || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+    }
+
+    #[cfg(not(unix))]
+    {
+        // No cheap check here - keep the swap file rather than recover over a live editor
+        let _ = pid;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(unix)]
+    fn test_is_process_alive() {
+        assert!(is_process_alive(std::process::id()));
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        let pid = child.id();
+        child.wait().unwrap();
+        assert!(!is_process_alive(pid));
+    }
+}
@@ -1,3 +1,5 @@
+use crate::cell::{cell_type_from_header, CellType, CELL_DELIMITER};
+
 /// Represents the syntactic state at a point in the text
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SyntaxState {
@@ -58,6 +60,9 @@ pub struct LineState {
     
     /// Hash of the line content for change detection
     pub content_hash: u64,
+
+    /// Whether the lines after this one are the SQL of a `##$$ sql` cell
+    pub sql_cell: bool,
 }
 
 impl LineState {
@@ -67,6 +72,7 @@ impl LineState {
             exit_state: SyntaxState::Normal,
             spans: Vec::new(),
             content_hash: 0,
+            sql_cell: false,
         }
     }
 }
@@ -109,6 +115,25 @@ impl Language {
             _ => Language::PlainText,
         }
     }
+
+    /// Prefix of a line comment, if the language has them
+    pub fn line_comment(&self) -> Option<&'static str> {
+        match self {
+            Language::Python | Language::R | Language::Yaml | Language::Shell | Language::Toml => Some("#"),
+            Language::Sql => Some("--"),
+            Language::Rust => Some("//"),
+            Language::Json | Language::Markdown | Language::PlainText => None,
+        }
+    }
+
+    /// Opening and closing delimiters of a block comment, if the language has them
+    pub fn block_comment(&self) -> Option<(&'static str, &'static str)> {
+        match self {
+            Language::Sql | Language::Rust => Some(("/*", "*/")),
+            Language::Markdown => Some(("<!--", "-->")),
+            _ => None,
+        }
+    }
 }
 
 impl SyntaxHighlighter {
@@ -135,6 +160,10 @@ impl SyntaxHighlighter {
         }
     }
 
+    pub fn language(&self) -> Language {
+        self.language
+    }
+
     /// Set language from file path
     pub fn set_language_from_path(&mut self, path: &str) {
         if let Some(ext) = path.rsplit('.').next() {
@@ -526,6 +555,16 @@ impl SyntaxHighlighter {
             SyntaxState::Normal
         };
 
+        // Lines of a `##$$ sql` cell in a Python file are highlighted as SQL
+        let in_sql_cell = line_index > 0 && self.line_states[line_index - 1].sql_cell;
+        let cell_header = (self.language == Language::Python && line_content.trim_start().starts_with(CELL_DELIMITER))
+            .then(|| matches!(cell_type_from_header(line_content), CellType::Sql { .. }));
+        let sql_cell = cell_header.unwrap_or(in_sql_cell);
+        let language = self.language;
+        if in_sql_cell && cell_header.is_none() {
+            self.language = Language::Sql;
+        }
+
         // Parse the line and collect all the data we need
         let content_hash = calculate_hash(line_content);
         let bytes = line_content.as_bytes();
@@ -536,6 +575,7 @@ impl SyntaxHighlighter {
         } else {
             self.tokenize_line_simple(line_content, entry_state, bytes)
         };
+        self.language = language;
 
         // Set exit state (line comments don't carry over)
         let new_exit_state = match final_state {
@@ -546,6 +586,7 @@ impl SyntaxHighlighter {
         // Check if we need to mark the next line as dirty before updating
         let should_mark_next = if line_index + 1 < self.line_states.len() {
             self.line_states[line_index + 1].entry_state != new_exit_state
+                || self.line_states[line_index].sql_cell != sql_cell
         } else {
             false
         };
@@ -556,6 +597,7 @@ impl SyntaxHighlighter {
         line_state.exit_state = new_exit_state;
         line_state.spans = new_spans;
         line_state.content_hash = content_hash;
+        line_state.sql_cell = sql_cell;
 
         // Mark next line as dirty if needed
         if should_mark_next {
@@ -0,0 +1,211 @@
+use crate::buffer::{EditOp, UndoGroup};
+use crate::editor::Editor;
+use crossterm::{
+    cursor::{Hide, MoveTo},
+    event::{KeyCode, KeyModifiers},
+    execute,
+    style::{Attribute, Color, Print, ResetColor, SetAttribute, SetBackgroundColor, SetForegroundColor},
+    terminal,
+};
+use std::io::{self, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// What the event loop should do after a key press in the undo tree browser
+pub enum UndoBrowserAction {
+    Continue,
+    Preview(usize), // Put the text in this state while the browser stays open
+    Close,          // Keep the state being previewed
+    Cancel(usize),  // Go back to the state the browser was opened in
+}
+
+/// Every state of the undo tree, drawn over the editor. Moving the selection
+/// previews that state in the editor, so abandoned branches can be looked at
+/// and brought back.
+pub struct UndoBrowser {
+    original: usize,
+    selected: usize, // Row in the tree layout
+}
+
+impl UndoBrowser {
+    /// Open the browser with the current state selected
+    pub fn new(editor: &Editor) -> Self {
+        let tree = editor.undo_tree();
+        let current = tree.current();
+        let selected = tree.layout().iter().position(|&(node, _)| node == current).unwrap_or(0);
+        Self { original: current, selected }
+    }
+
+    pub fn handle_key(&mut self, code: KeyCode, modifiers: KeyModifiers, editor: &Editor) -> UndoBrowserAction {
+        let layout = editor.undo_tree().layout();
+        let last = layout.len().saturating_sub(1);
+        let before = self.selected;
+        match code {
+            KeyCode::Esc => return UndoBrowserAction::Cancel(self.original),
+            KeyCode::Enter => return UndoBrowserAction::Close,
+            KeyCode::Char('u') | KeyCode::Char('U') if modifiers.contains(KeyModifiers::ALT) => {
+                return UndoBrowserAction::Close;
+            }
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down => self.selected = (self.selected + 1).min(last),
+            KeyCode::PageUp => self.selected = self.selected.saturating_sub(10),
+            KeyCode::PageDown => self.selected = (self.selected + 10).min(last),
+            KeyCode::Home => self.selected = 0,
+            KeyCode::End => self.selected = last,
+            _ => {}
+        }
+        match layout.get(self.selected) {
+            Some(&(node, _)) if self.selected != before => UndoBrowserAction::Preview(node),
+            _ => UndoBrowserAction::Continue,
+        }
+    }
+
+    /// Draw the browser centered over the editor area (above the status bar and any bottom pane)
+    pub fn draw<W: Write>(&mut self, writer: &mut W, editor: &Editor, bottom_height: usize) -> io::Result<()> {
+        let tree = editor.undo_tree();
+        let layout = tree.layout();
+        self.selected = self.selected.min(layout.len().saturating_sub(1));
+
+        let (width, height) = terminal::size()?;
+        let area_height = (height as usize).saturating_sub(1 + bottom_height);
+        let box_width = (width as usize).min(80);
+        let inner_width = box_width.saturating_sub(2);
+        let list_height = layout.len().clamp(1, area_height.saturating_sub(6).max(1));
+        let box_height = list_height + 4;
+        let start_x = (width as usize).saturating_sub(box_width) / 2;
+        let start_y = area_height.saturating_sub(box_height) / 2;
+        // Keep the selection roughly centered so the states around it are visible
+        let scroll = self.selected.saturating_sub(list_height / 2).min(layout.len().saturating_sub(list_height));
+
+        let background = Color::Rgb { r: 40, g: 40, b: 45 };
+        let border = Color::Rgb { r: 200, g: 200, b: 205 };
+        let dim = Color::Rgb { r: 120, g: 120, b: 125 };
+
+        let counter = format!("{} states ", layout.len());
+        execute!(
+            writer,
+            MoveTo(start_x as u16, start_y as u16),
+            SetBackgroundColor(background),
+            SetForegroundColor(border),
+            Print(format!("╭{}╮", "─".repeat(inner_width))),
+            MoveTo(start_x as u16, (start_y + 1) as u16),
+            Print("│ "),
+            SetAttribute(Attribute::Bold),
+            SetForegroundColor(Color::White),
+            Print(fit("Undo History", inner_width.saturating_sub(1 + counter.chars().count()))),
+            SetAttribute(Attribute::Reset),
+            SetBackgroundColor(background),
+            SetForegroundColor(dim),
+            Print(&counter),
+            SetForegroundColor(border),
+            Print("│"),
+            MoveTo(start_x as u16, (start_y + 2) as u16),
+            Print(format!("├{}┤", "─".repeat(inner_width))),
+        )?;
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        for row in 0..list_height {
+            let y = (start_y + 3 + row) as u16;
+            execute!(writer, MoveTo(start_x as u16, y), SetBackgroundColor(background), SetForegroundColor(border), Print("│"))?;
+            match layout.get(scroll + row) {
+                Some(&(node, depth)) => {
+                    let selected = scroll + row == self.selected;
+                    let row_background = if selected { Color::Rgb { r: 60, g: 90, b: 130 } } else { background };
+                    let text = if selected { Color::White } else { Color::Rgb { r: 180, g: 180, b: 185 } };
+                    let state = tree.node(node);
+                    let (glyph, glyph_color) = if node == tree.current() {
+                        ('●', Color::Rgb { r: 110, g: 170, b: 110 })
+                    } else {
+                        ('○', Color::Rgb { r: 90, g: 90, b: 90 })
+                    };
+                    let label = if node == 0 {
+                        "Original".to_string()
+                    } else {
+                        format!("#{}  {}", state.seq, describe(&state.group))
+                    };
+                    let mut right = format_ago(now.saturating_sub(state.time));
+                    if node == editor.buffer().saved_state() {
+                        right = format!("saved · {}", right);
+                    }
+                    let right = format!(" {} ", right);
+                    let left = format!(" {}{} ", "  ".repeat(depth), glyph);
+                    let label_width = inner_width.saturating_sub(left.chars().count() + right.chars().count());
+                    execute!(
+                        writer,
+                        SetBackgroundColor(row_background),
+                        SetForegroundColor(glyph_color),
+                        Print(&left),
+                        SetForegroundColor(text),
+                        Print(fit(&label, label_width)),
+                        SetForegroundColor(dim),
+                        Print(&right),
+                    )?;
+                }
+                None => execute!(writer, Print(" ".repeat(inner_width)))?,
+            }
+            execute!(writer, SetBackgroundColor(background), SetForegroundColor(border), Print("│"))?;
+        }
+
+        let footer = " ↑↓: Preview  Enter: Keep  Esc: Back to start ";
+        execute!(
+            writer,
+            MoveTo(start_x as u16, (start_y + 3 + list_height) as u16),
+            Print(format!("╰{}╯", fit_with(footer, inner_width, '─'))),
+            ResetColor,
+            Hide
+        )?;
+        writer.flush()
+    }
+}
+
+/// Short description of an edit: characters added and removed, and a sample of the text
+fn describe(group: &UndoGroup) -> String {
+    let mut inserted = 0;
+    let mut deleted = 0;
+    let mut sample = None;
+    for op in &group.ops {
+        let (text, count) = match op {
+            EditOp::Insert { text, .. } => (text, &mut inserted),
+            EditOp::Delete { text, .. } => (text, &mut deleted),
+        };
+        *count += text.chars().count();
+        if sample.is_none() {
+            sample = text.lines().map(str::trim).find(|line| !line.is_empty());
+        }
+    }
+    let mut summary = match (inserted, deleted) {
+        (0, d) => format!("-{}", d),
+        (i, 0) => format!("+{}", i),
+        (i, d) => format!("+{} -{}", i, d),
+    };
+    if let Some(sample) = sample {
+        summary.push_str(&format!("  \"{}\"", sample));
+    }
+    summary
+}
+
+/// How long ago an edit was made (just now, 5m ago, 2h ago, 3d ago)
+fn format_ago(secs: u64) -> String {
+    if secs < 10 {
+        "just now".to_string()
+    } else if secs < 60 {
+        format!("{}s ago", secs)
+    } else if secs < 3600 {
+        format!("{}m ago", secs / 60)
+    } else if secs < 86400 {
+        format!("{}h ago", secs / 3600)
+    } else {
+        format!("{}d ago", secs / 86400)
+    }
+}
+
+fn fit(text: &str, width: usize) -> String {
+    fit_with(text, width, ' ')
+}
+
+/// Truncate or pad text to exactly `width` characters
+fn fit_with(text: &str, width: usize, pad: char) -> String {
+    let mut result: String = text.chars().take(width).collect();
+    let used = result.chars().count();
+    result.extend(std::iter::repeat_n(pad, width - used));
+    result
+}
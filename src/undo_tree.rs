@@ -0,0 +1,282 @@
+use crate::buffer::UndoGroup;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// One state of the text, reached from its parent state by `group`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UndoNode {
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,     // Oldest first
+    redo_child: Option<usize>,    // Child Redo goes to: the one last undone or created
+    pub group: UndoGroup,         // Edit that led here (empty for the root)
+    pub seq: u64,                 // Number shown in the undo tree browser
+    pub time: u64,                // When the edit was made, in seconds since the Unix epoch
+}
+
+/// Every state the text has been in. An edit after an undo starts a new branch
+/// instead of discarding the states that were undone.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UndoTree {
+    nodes: Vec<UndoNode>,
+    current: usize,
+    next_seq: u64,
+}
+
+/// Undo tree saved with a file's session. It only applies to the text it was saved with.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UndoHistory {
+    pub content_hash: u64,
+    pub tree: UndoTree,
+}
+
+impl UndoTree {
+    pub fn new() -> Self {
+        Self {
+            nodes: vec![UndoNode {
+                parent: None,
+                children: Vec::new(),
+                redo_child: None,
+                group: UndoGroup::default(),
+                seq: 0,
+                time: now_secs(),
+            }],
+            current: 0,
+            next_seq: 1,
+        }
+    }
+
+    /// Index of the state the text is in
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    pub fn node(&self, index: usize) -> &UndoNode {
+        &self.nodes[index]
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Record an edit made in the current state; it becomes the current state
+    pub fn push(&mut self, group: UndoGroup) {
+        let index = self.nodes.len();
+        self.nodes.push(UndoNode {
+            parent: Some(self.current),
+            children: Vec::new(),
+            redo_child: None,
+            group,
+            seq: self.next_seq,
+            time: now_secs(),
+        });
+        self.nodes[self.current].children.push(index);
+        self.nodes[self.current].redo_child = Some(index);
+        self.current = index;
+        self.next_seq += 1;
+    }
+
+    /// Step back to the parent state, returning the edit to revert
+    pub fn undo(&mut self) -> Option<&UndoGroup> {
+        let node = self.current;
+        let parent = self.nodes[node].parent?;
+        self.nodes[parent].redo_child = Some(node);
+        self.current = parent;
+        Some(&self.nodes[node].group)
+    }
+
+    /// Step forward to the child state last visited, returning the edit to apply
+    pub fn redo(&mut self) -> Option<&UndoGroup> {
+        let child = self.nodes[self.current].redo_child?;
+        self.current = child;
+        Some(&self.nodes[child].group)
+    }
+
+    /// Make Redo go to `child` (a child of the current state) next
+    pub fn choose_redo(&mut self, child: usize) {
+        if self.nodes[self.current].children.contains(&child) {
+            self.nodes[self.current].redo_child = Some(child);
+        }
+    }
+
+    /// States to undo (from the current one up) and then to redo (down to `target`)
+    /// to get from the current state to `target`
+    pub fn path_to(&self, target: usize) -> (Vec<usize>, Vec<usize>) {
+        let mut on_target_path = vec![false; self.nodes.len()];
+        let mut node = Some(target);
+        while let Some(n) = node {
+            on_target_path[n] = true;
+            node = self.nodes[n].parent;
+        }
+
+        let mut undo = Vec::new();
+        let mut node = self.current;
+        while !on_target_path[node] {
+            undo.push(node);
+            node = self.nodes[node].parent.unwrap_or(0);
+        }
+        let common = node;
+
+        let mut redo = Vec::new();
+        let mut node = target;
+        while node != common {
+            redo.push(node);
+            node = self.nodes[node].parent.unwrap_or(0);
+        }
+        redo.reverse();
+        (undo, redo)
+    }
+
+    /// Copy of the tree with `target` as the current state, keeping the way back
+    /// to the current state reachable with Redo
+    pub fn moved_to(&self, target: usize) -> UndoTree {
+        let mut tree = self.clone();
+        let (undo, redo) = self.path_to(target);
+        for _ in undo {
+            tree.undo();
+        }
+        for node in redo {
+            tree.choose_redo(node);
+            tree.redo();
+        }
+        tree
+    }
+
+    /// Check the links of a tree read from disk, so navigating it can't go out of bounds or loop
+    pub fn is_valid(&self) -> bool {
+        let count = self.nodes.len();
+        count > 0 && self.current < count && self.nodes[0].parent.is_none()
+            && self.nodes.iter().enumerate().all(|(i, node)| {
+                (i == 0 || node.parent.is_some_and(|p| p < i && self.nodes[p].children.contains(&i)))
+                    && node.children.iter().all(|&c| c > i && c < count)
+                    && node.redo_child.is_none_or(|c| node.children.contains(&c))
+            })
+    }
+
+    /// Copy of the tree cut down to at most `max_nodes` states (if the current state's
+    /// ancestors allow it). The oldest side branches go first, then the oldest states.
+    pub fn pruned(&self, max_nodes: usize) -> UndoTree {
+        let count = self.nodes.len();
+        let mut removed = vec![false; count];
+        let mut child_count: Vec<usize> = self.nodes.iter().map(|node| node.children.len()).collect();
+        let mut leaves: BinaryHeap<Reverse<(u64, usize)>> = self.nodes.iter().enumerate()
+            .filter(|&(i, node)| node.children.is_empty() && i != self.current && i != 0)
+            .map(|(i, node)| Reverse((node.seq, i)))
+            .collect();
+        let mut root = 0;
+        let mut remaining = count;
+
+        while remaining > max_nodes {
+            if let Some(Reverse((_, leaf))) = leaves.pop() {
+                removed[leaf] = true;
+                remaining -= 1;
+                if let Some(parent) = self.nodes[leaf].parent {
+                    child_count[parent] -= 1;
+                    if child_count[parent] == 0 && parent != self.current && parent != root {
+                        leaves.push(Reverse((self.nodes[parent].seq, parent)));
+                    }
+                }
+            } else if child_count[root] == 1 && root != self.current {
+                // Forget the oldest state: its only remaining child becomes the root
+                removed[root] = true;
+                remaining -= 1;
+                root = self.nodes[root].children.iter().copied().find(|&c| !removed[c]).unwrap_or(root);
+            } else {
+                break;
+            }
+        }
+
+        // Renumber what is left; descendants come after their ancestors, so the root is first
+        let mut index = vec![0; count];
+        let mut nodes = Vec::with_capacity(remaining);
+        for (i, node) in self.nodes.iter().enumerate() {
+            if !removed[i] {
+                index[i] = nodes.len();
+                nodes.push(node.clone());
+            }
+        }
+        for node in &mut nodes {
+            node.parent = node.parent.filter(|&p| !removed[p]).map(|p| index[p]);
+            node.children.retain(|&c| !removed[c]);
+            for child in &mut node.children {
+                *child = index[*child];
+            }
+            node.redo_child = node.redo_child.filter(|&c| !removed[c]).map(|c| index[c]);
+        }
+        nodes[0].group = UndoGroup::default();
+
+        UndoTree { nodes, current: index[self.current], next_seq: self.next_seq }
+    }
+
+    /// States in display order with their branch depth: each state's newest child
+    /// continues its line, older children start indented branches above it
+    pub fn layout(&self) -> Vec<(usize, usize)> {
+        let mut rows = Vec::with_capacity(self.nodes.len());
+        let mut stack = vec![(0, 0)];
+        while let Some((node, depth)) = stack.pop() {
+            rows.push((node, depth));
+            let children = &self.nodes[node].children;
+            if let Some((&newest, older)) = children.split_last() {
+                stack.push((newest, depth));
+                stack.extend(older.iter().rev().map(|&c| (c, depth + 1)));
+            }
+        }
+        rows
+    }
+}
+
+impl Default for UndoTree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer::EditOp;
+
+    fn edit(text: &str) -> UndoGroup {
+        UndoGroup { ops: vec![EditOp::Insert { pos: 0, text: text.to_string() }], cursor_before: 0, cursor_after: 0 }
+    }
+
+    #[test]
+    fn test_branches_are_kept() {
+        let mut tree = UndoTree::new();
+        tree.push(edit("a"));
+        tree.push(edit("b"));
+        tree.undo();
+        tree.push(edit("c"));
+        assert_eq!(tree.len(), 4);
+        assert!(tree.redo().is_none());
+
+        // From "c" back to the abandoned "b": undo "c", redo "b"
+        assert_eq!(tree.path_to(2), (vec![3], vec![2]));
+        let moved = tree.moved_to(2);
+        assert_eq!(moved.current(), 2);
+        assert!(moved.is_valid());
+        assert_eq!(tree.layout(), vec![(0, 0), (1, 0), (2, 1), (3, 0)]);
+    }
+
+    #[test]
+    fn test_pruned() {
+        let mut tree = UndoTree::new();
+        for text in ["a", "b", "c"] {
+            tree.push(edit(text));
+        }
+        tree.undo();
+        tree.push(edit("d"));
+        // States: 0 - 1 - 2 - {3, 4}, current 4. The side branch goes first, then the root.
+        let pruned = tree.pruned(3);
+        assert!(pruned.is_valid());
+        assert_eq!(pruned.len(), 3);
+        assert_eq!(pruned.node(0).seq, 1);
+        assert_eq!(pruned.node(pruned.current()).seq, 4);
+        assert!(pruned.node(0).group.ops.is_empty());
+    }
+}